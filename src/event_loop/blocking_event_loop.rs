@@ -0,0 +1,102 @@
+use std::io::{self, BufRead, Write};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::{Storage, HashTable};
+use crate::event_loop::EventLoop;
+use crate::event_loop::commands::{handle_command, merge_if_dead_space_due, perform_merge, Session};
+
+/// Event loop for platforms without mio's unix extensions (e.g. Windows)
+/// A dedicated thread blocks on stdin and sends each line over a channel, so the loop can still
+/// wake up while the user is idle to sync pending writes and run auto-merge
+pub struct BlockingEventLoop {
+    /// How long the loop waits for input before running its idle checks, as TerminalEventLoop's poll timeout
+    pub poll_timeout: Duration,
+    /// Fsync pending writes whenever a wait ends without input (the "dirty since last sync" flag is set)
+    pub sync_on_idle: bool,
+}
+
+impl EventLoop for BlockingEventLoop {
+    fn run(&mut self, store: &Mutex<(Storage, HashTable)>, merge_interval_seconds: u64) {
+        let (lines, input) = mpsc::channel::<io::Result<String>>();
+        // The reader thread exits once stdin closes or the loop stops listening; it isn't joined because
+        // it may be blocked in read_line when the user types exit
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let failed = line.is_err();
+                if lines.send(line).is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        let mut last_activity = Instant::now();
+        let mut session = Session::new(merge_interval_seconds);
+
+        loop {
+            let line = match input.recv_timeout(self.poll_timeout) {
+                Ok(Ok(line)) => Some(line),
+                Ok(Err(e)) => {
+                    println!("Error reading input: {}", e);
+                    return;
+                }
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    println!("\nInput stream closed. Exiting.");
+                    return;
+                }
+            };
+
+            {
+                let storage = &mut store.lock().unwrap().0;
+                // Timed out with no input: sync pending writes while the user is idle
+                if self.sync_on_idle && line.is_none() && session.dirty {
+                    match storage.sync() {
+                        Ok(()) => session.dirty = false,
+                        Err(e) => println!("✗ Failed to sync storage: {}", e),
+                    }
+                }
+                if let Err(e) = storage.sync_if_due() {
+                    println!("✗ Failed to sync storage: {}", e);
+                }
+            }
+
+            if let Some(line) = line {
+                let input = line.trim();
+                if !input.is_empty() {
+                    print!("> ");
+                    io::stdout().flush().unwrap();
+                    println!("{}", input);
+
+                    last_activity = Instant::now();
+                    let mut state = store.lock().unwrap();
+                    let (storage, hash_table) = &mut *state;
+                    if handle_command(input, storage, hash_table, &mut session, &mut io::stdout()) {
+                        return; // Exit command was received
+                    }
+                }
+            }
+
+            if session.auto_merge_due(last_activity.elapsed()) {
+                println!("\nAuto-merge triggered due to inactivity...");
+                let mut state = store.lock().unwrap();
+                let (storage, hash_table) = &mut *state;
+                perform_merge(storage, hash_table, &mut io::stdout());
+                last_activity = Instant::now();
+                session.operation_count = 0;
+                session.dirty = true;
+                print!("> ");
+                io::stdout().flush().unwrap();
+            }
+
+            let mut state = store.lock().unwrap();
+            let (storage, hash_table) = &mut *state;
+            if merge_if_dead_space_due(storage, hash_table, &mut session, &mut io::stdout()) {
+                last_activity = Instant::now();
+                print!("> ");
+                io::stdout().flush().unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,1469 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::{Storage, StorageError, RecordType, HashTable, CollisionResolution, GetOutcome, RemovedValue, WriteOp, LatencyHistogram, ValueCache, FifoPolicy, ScanCursor, ExportFormat, Compression};
+
+/// println! into a command's output: stdout for the terminal loops, the client's connection for TcpEventLoop
+/// Write errors are ignored; a client that disconnected mid-response just misses the rest of it
+macro_rules! outln {
+    ($out:expr) => {{
+        let _ = writeln!($out);
+    }};
+    ($out:expr, $($arg:tt)*) => {{
+        let _ = writeln!($out, $($arg)*);
+    }};
+}
+
+/// State carried across commands within one session, interactive or scripted
+pub struct Session {
+    /// Mutating operations since the last merge
+    pub operation_count: usize,
+    /// Set when a command has written to storage since the last fsync
+    pub dirty: bool,
+    /// Idle seconds before auto-merge; 0 disables auto-merge
+    pub merge_interval_seconds: u64,
+    /// Keys deleted in this session, for `recent-deletes`
+    pub recent_deletes: RecentDeletes,
+}
+
+impl Session {
+    pub fn new(merge_interval_seconds: u64) -> Session {
+        Session { operation_count: 0, dirty: false, merge_interval_seconds, recent_deletes: RecentDeletes::new(RECENT_DELETES_CAPACITY) }
+    }
+
+    /// Whether auto-merge should run after `idle` without input
+    /// Never true when auto-merge is disabled or nothing was written since the last merge
+    pub fn auto_merge_due(&self, idle: Duration) -> bool {
+        self.merge_interval_seconds > 0
+            && self.operation_count > 0
+            && idle >= Duration::from_secs(self.merge_interval_seconds)
+    }
+
+    /// Whether the store's dead space has passed its merge ratio, see Storage::dead_space_merge_due
+    /// Never true when nothing was written since the last merge, so one that couldn't bring the ratio down isn't repeated
+    pub fn dead_space_merge_due(&self, storage: &Storage) -> bool {
+        self.operation_count > 0 && storage.dead_space_merge_due()
+    }
+}
+
+/// How many deletes a session remembers
+const RECENT_DELETES_CAPACITY: usize = 20;
+
+/// Bounded log of deleted keys and their delete timestamps, oldest dropped first
+pub struct RecentDeletes {
+    capacity: usize,
+    entries: VecDeque<(String, u64)>,
+}
+
+impl RecentDeletes {
+    pub fn new(capacity: usize) -> RecentDeletes {
+        RecentDeletes { capacity, entries: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Records a delete, dropping the oldest one once at capacity
+    pub fn push(&mut self, key: &str, timestamp: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((key.to_string(), timestamp));
+    }
+
+    /// Up to `n` most recent deletes, newest first
+    pub fn latest(&self, n: usize) -> impl Iterator<Item = &(String, u64)> {
+        self.entries.iter().rev().take(n)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Splits a command line into words at whitespace, shell style
+/// A double quote starting a word groups words up to the closing quote, so `insert "full name" "John Doe"` has a
+/// two-word key and value, and `""` is an empty word. A quote anywhere else in a word is literal, as before quoting
+/// existed (`insert size 5"`). Inside quotes, `\"` and `\\` stand for a quote and a backslash; backslashes elsewhere are literal
+fn split_command(input: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if word.is_none() => {
+                let word = word.insert(String::new());
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\')) => word.push(escaped),
+                            Some(other) => {
+                                word.push('\\');
+                                word.push(other);
+                            }
+                            None => return Err("unterminated quote".to_string()),
+                        },
+                        Some(other) => word.push(other),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+// Returns true if the command was to exit
+pub fn handle_command(input: &str, storage: &mut Storage, hash_table: &mut HashTable, session: &mut Session, out: &mut dyn Write) -> bool {
+    let words = match split_command(input) {
+        Ok(words) => words,
+        Err(e) => {
+            outln!(out, "✗ Invalid command: {}", e);
+            return false;
+        }
+    };
+    let parts: Vec<&str> = words.iter().map(String::as_str).collect();
+    if parts.is_empty() {
+        return false;
+    }
+
+    match parts[0].to_lowercase().as_str() {
+        "exit" | "quit" => {
+            outln!(out, "Goodbye!");
+            return true;
+        }
+        "help" => {
+            show_help(session.merge_interval_seconds, out);
+        }
+        "stats" => {
+            show_stats(storage, hash_table, session.operation_count, out);
+        }
+        "relocate" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: relocate <directory>");
+            } else {
+                match storage.relocate(parts[1]) {
+                    Ok(()) => outln!(out, "✓ Store relocated to '{}/'", parts[1]),
+                    Err(e) => outln!(out, "✗ Failed to relocate to {}: {}", parts[1], e),
+                }
+            }
+        }
+        "backup" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: backup <directory>");
+            } else {
+                match storage.snapshot(parts[1]) {
+                    Ok(files) => {
+                        outln!(out, "✓ Snapshot of {} files written to '{}/'", files.len(), parts[1]);
+                        for filename in files {
+                            outln!(out, "  {}", filename);
+                        }
+                    }
+                    Err(e) => outln!(out, "✗ Failed to back up to {}: {}", parts[1], e),
+                }
+            }
+        }
+        "sync" => {
+            match storage.sync() {
+                Ok(()) => {
+                    session.dirty = false;
+                    outln!(out, "✓ Synced ({} fsyncs performed so far)", storage.sync_count());
+                }
+                Err(e) => outln!(out, "✗ Failed to sync storage: {}", e),
+            }
+        }
+        "merge" => {
+            match (parts.get(1).copied(), parts.len()) {
+                (None, _) => {
+                    perform_merge(storage, hash_table, out);
+                    session.operation_count = 0;
+                    session.dirty = true;
+                }
+                (Some("--dry-run"), 2) => handle_merge_preview(storage, out),
+                _ => outln!(out, "Usage: merge [--dry-run]"),
+            }
+        }
+        "compact" => {
+            perform_compact(storage, hash_table, out);
+            session.operation_count = 0;
+            session.dirty = true;
+        }
+        "merge-into" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: merge-into <directory>");
+            } else {
+                handle_merge_into(storage, hash_table, parts[1], out);
+            }
+        }
+        "merge-budget" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: merge-budget <bytes>");
+            } else {
+                handle_merge_budget(storage, hash_table, parts[1], out);
+                session.dirty = true;
+            }
+        }
+        "prune" => {
+            match storage.prune_dead_files(hash_table) {
+                Ok(pruned) => outln!(out, "✓ Pruned {} dead files", pruned),
+                Err(e) => outln!(out, "✗ Prune failed: {}", e),
+            }
+        }
+        "rehash" => {
+            perform_rehash(hash_table, out);
+        }
+        "htstats" => {
+            if parts.len() > 2 || parts.len() == 2 && parts[1] != "reset" {
+                outln!(out, "Usage: htstats [reset]");
+            } else {
+                show_hash_table_metrics(hash_table, parts.len() == 2, out);
+            }
+        }
+        "simulate-crash" => {
+            simulate_crash(storage, hash_table, out);
+        }
+        "simulate-duplicate" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: simulate-duplicate <key>");
+            } else {
+                simulate_duplicate(hash_table, parts[1], out);
+            }
+        }
+        "simulate-misdirect" => {
+            if parts.len() != 3 {
+                outln!(out, "Usage: simulate-misdirect <key> <other_key>");
+            } else {
+                simulate_misdirect(hash_table, parts[1], parts[2], out);
+            }
+        }
+        "simulate-locked-files" => {
+            match parts.get(1).copied() {
+                Some("on") => {
+                    storage.set_file_remover(Some(Box::new(|path: &std::path::Path| {
+                        Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, format!("{} is locked (simulated)", path.display())))
+                    })));
+                    outln!(out, "✓ Merged files can no longer be removed");
+                }
+                Some("off") => {
+                    storage.set_file_remover(None);
+                    outln!(out, "✓ Merged files are removed normally");
+                }
+                _ => outln!(out, "Usage: simulate-locked-files <on|off>"),
+            }
+        }
+        "selftest" => {
+            run_self_test(storage, out);
+        }
+        "duplicates" => {
+            let duplicates = hash_table.find_duplicates();
+            if duplicates.is_empty() {
+                outln!(out, "✓ No duplicate keys in the index");
+            } else {
+                outln!(out, "✗ {} duplicate keys in the index: {}", duplicates.len(), duplicates.join(", "));
+            }
+        }
+        "insert" => {
+            if parts.len() < 3 {
+                outln!(out, "Usage: insert <key> <value>");
+            } else if parts[1].is_empty() {
+                outln!(out, "✗ Key must not be empty");
+            } else {
+                let key = parts[1];
+                let value = parts[2..].join(" ");
+                handle_insert(storage, hash_table, key, &value, out);
+                session.operation_count += 1;
+                session.dirty = true;
+            }
+        }
+        "update-in-place" => {
+            if parts.len() < 3 {
+                outln!(out, "Usage: update-in-place <key> <value>");
+            } else {
+                handle_update_in_place(storage, hash_table, parts[1], &parts[2..].join(" "), out);
+                session.operation_count += 1;
+                session.dirty = true;
+            }
+        }
+        "getset" => {
+            if parts.len() < 3 {
+                outln!(out, "Usage: getset <key> <value>");
+            } else if parts[1].is_empty() {
+                outln!(out, "✗ Key must not be empty");
+            } else {
+                let value = parts[2..].join(" ");
+                match storage.put_returning_old(hash_table, parts[1], &value) {
+                    Ok(Some(old)) => outln!(out, "✓ Set {}: {} (was: {})", parts[1], value, old),
+                    Ok(None) => outln!(out, "✓ Set {}: {} (had no value)", parts[1], value),
+                    Err(e) => outln!(out, "✗ Failed to set {}: {}", parts[1], e),
+                }
+                session.operation_count += 1;
+                session.dirty = true;
+            }
+        }
+        "setnx" | "setxx" => {
+            if parts.len() < 3 {
+                outln!(out, "Usage: {} <key> <value>", parts[0]);
+            } else if parts[1].is_empty() {
+                outln!(out, "✗ Key must not be empty");
+            } else {
+                let value = parts[2..].join(" ");
+                handle_conditional_insert(storage, hash_table, parts[1], &value, parts[0].eq_ignore_ascii_case("setnx"), out);
+                session.operation_count += 1;
+                session.dirty = true;
+            }
+        }
+        "delete" => {
+            if parts.len() < 2 {
+                outln!(out, "Usage: delete <key> [reason]");
+            } else if parts[1].is_empty() {
+                outln!(out, "✗ Key must not be empty");
+            } else {
+                let key = parts[1];
+                let reason = parts[2..].join(" ");
+                if let Some(timestamp) = handle_delete(storage, hash_table, key, &reason, out) {
+                    session.recent_deletes.push(key, timestamp);
+                }
+                session.operation_count += 1;
+                session.dirty = true;
+            }
+        }
+        "delprefix" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: delprefix <prefix>");
+            } else if parts[1].is_empty() {
+                outln!(out, "✗ Prefix must not be empty");
+            } else {
+                match storage.delete_prefix(hash_table, parts[1]) {
+                    Ok(deleted) => {
+                        outln!(out, "✓ Deleted {} keys starting with '{}'", deleted, parts[1]);
+                        session.operation_count += deleted;
+                        session.dirty = true;
+                    }
+                    Err(e) => outln!(out, "✗ Failed to delete keys starting with '{}': {}", parts[1], e),
+                }
+            }
+        }
+        "get" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: get <key>");
+            } else {
+                let key = parts[1];
+                handle_get(storage, hash_table, key, out);
+            }
+        }
+        "mget" => {
+            if parts.len() < 2 {
+                outln!(out, "Usage: mget <key>...");
+            } else {
+                handle_mget(storage, hash_table, &parts[1..], out);
+            }
+        }
+        "getmeta" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: getmeta <key>");
+            } else {
+                handle_getmeta(storage, hash_table, parts[1], out);
+            }
+        }
+        "exists" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: exists <key>");
+            } else {
+                handle_exists(storage, hash_table, parts[1], out);
+            }
+        }
+        "verify" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: verify <key>");
+            } else {
+                handle_verify(storage, hash_table, parts[1], out);
+            }
+        }
+        "insert-batch" => {
+            if parts.len() < 2 {
+                outln!(out, "Usage: insert-batch <key>=<value>...");
+            } else {
+                handle_insert_batch(storage, hash_table, &parts[1..], out);
+                session.operation_count += parts.len() - 1;
+                session.dirty = true;
+            }
+        }
+        "transaction" => {
+            if parts.len() < 2 {
+                outln!(out, "Usage: transaction put:<key>=<value>|del:<key>...");
+            } else {
+                handle_transaction(storage, hash_table, &parts[1..], &mut session.recent_deletes, out);
+                session.operation_count += parts.len() - 1;
+                session.dirty = true;
+            }
+        }
+        "insert-hex" => {
+            if parts.len() != 3 {
+                outln!(out, "Usage: insert-hex <key> <hex bytes>");
+            } else {
+                handle_insert_hex(storage, hash_table, parts[1], parts[2], out);
+                session.operation_count += 1;
+                session.dirty = true;
+            }
+        }
+        "get-hex" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: get-hex <key>");
+            } else {
+                handle_get_hex(storage, hash_table, parts[1], out);
+            }
+        }
+        "hexdump" => {
+            if parts.len() < 3 || parts.len() > 4 {
+                outln!(out, "Usage: hexdump <filename> <record_offset> [len]");
+            } else {
+                handle_hexdump(storage, parts[1], parts[2], parts.get(3).copied(), out);
+            }
+        }
+        "list" => {
+            list_keys(hash_table, out);
+        }
+        "count" => {
+            if parts.len() > 2 || parts.len() == 2 && parts[1] != "tombstones" {
+                outln!(out, "Usage: count [tombstones]");
+            } else {
+                count_keys(storage, hash_table, parts.len() == 2, out);
+            }
+        }
+        "layout" => {
+            show_layout(storage, out);
+        }
+        "records" => {
+            show_records(storage, out);
+        }
+        "read-logical" => {
+            match parts.get(1).map(|offset| offset.parse::<u64>()) {
+                Some(Ok(offset)) if parts.len() == 2 => handle_read_logical(storage, offset, out),
+                _ => outln!(out, "Usage: read-logical <offset>"),
+            }
+        }
+        "bench" => {
+            match (parts.get(1).copied(), parts.len()) {
+                (Some("read"), 3) => handle_bench_read(storage, hash_table, parts[2], out),
+                (Some("merge"), 2) => handle_bench_merge(storage, out),
+                (Some("probing"), 2) => handle_bench_probing(BENCH_PROBING_BUCKETS, out),
+                (Some("probing"), 3) => match parts[2].parse::<u64>() {
+                    Ok(buckets) if buckets > 0 => handle_bench_probing(buckets, out),
+                    _ => outln!(out, "✗ Invalid bucket count: {}", parts[2]),
+                },
+                _ => outln!(out, "Usage: bench read <count> | bench merge | bench probing [buckets]"),
+            }
+        }
+        "scan" => {
+            if parts.get(1) == Some(&"prefix") {
+                match parts.get(2) {
+                    Some(prefix) if parts.len() == 3 => handle_scan_prefix(storage, hash_table, prefix, out),
+                    _ => outln!(out, "Usage: scan prefix <prefix>"),
+                }
+            } else if parts.len() < 2 || parts.len() > 3 {
+                outln!(out, "Usage: scan <limit> [cursor] | scan prefix <prefix>");
+            } else {
+                handle_scan(hash_table, parts[1], parts.get(2).copied(), out);
+            }
+        }
+        "cache" => {
+            handle_cache(storage, &parts[1..], out);
+        }
+        "pin" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: pin <key>");
+            } else {
+                handle_pin(storage, hash_table, parts[1], out);
+            }
+        }
+        "unpin" => {
+            if parts.len() != 2 {
+                outln!(out, "Usage: unpin <key>");
+            } else if storage.unpin_key(parts[1]) {
+                outln!(out, "✓ Unpinned {}", parts[1]);
+            } else {
+                outln!(out, "✗ {} is not pinned", parts[1]);
+            }
+        }
+        "recent-deletes" => {
+            match parts.get(1).map(|n| n.parse::<usize>()) {
+                None => show_recent_deletes(&session.recent_deletes, 10, out),
+                Some(Ok(n)) => show_recent_deletes(&session.recent_deletes, n, out),
+                Some(Err(_)) => outln!(out, "Usage: recent-deletes [n]"),
+            }
+        }
+        "tombstones" => {
+            show_tombstones(storage, hash_table, out);
+        }
+        "timestamp" => {
+            let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+            outln!(out, "✓ Current timestamp: {}", since_epoch.as_nanos());
+        }
+        "age" => {
+            show_age(storage, out);
+        }
+        "truncate-to" => {
+            if parts.len() < 2 || parts.len() > 3 {
+                outln!(out, "Usage: truncate-to <timestamp> [confirm]");
+            } else if parts.get(2) != Some(&"confirm") {
+                outln!(out, "⚠ This permanently discards every record written after {}.", parts[1]);
+                outln!(out, "  Re-run as 'truncate-to {} confirm' to proceed.", parts[1]);
+            } else {
+                handle_truncate_to(storage, hash_table, parts[1], out);
+                session.dirty = true;
+            }
+        }
+        "export" => {
+            if parts.len() < 2 || parts.len() > 3 {
+                outln!(out, "Usage: export <file> [jsonl|text]");
+            } else {
+                handle_export(storage, parts[1], parts.get(2).copied().unwrap_or("jsonl"), out);
+            }
+        }
+        _ => {
+            outln!(out, "Unknown command: {}. Type 'help' for available commands.", parts[0]);
+        }
+    }
+    false
+}
+
+fn show_help(merge_interval_seconds: u64, out: &mut dyn Write) {
+    outln!(out, "Available commands:");
+    outln!(out, "  insert <key> <value>  - Insert or update a key-value pair (double-quote keys or values with spaces)");
+    outln!(out, "  setnx <key> <value>   - Insert only if the key doesn't exist (deleted keys count as absent)");
+    outln!(out, "  setxx <key> <value>   - Update only if the key already exists");
+    outln!(out, "  getset <key> <value>  - Insert or update a key, showing the value it replaced");
+    outln!(out, "  update-in-place <key> <value> - Overwrite a value of the same length without appending (not append-only)");
+    outln!(out, "  delete <key> [reason] - Delete a key, optionally recording why, and show the value removed");
+    outln!(out, "  delprefix <prefix>    - Delete every live key starting with <prefix>");
+    outln!(out, "  get <key>             - Retrieve a value by key");
+    outln!(out, "  mget <key>...         - Retrieve several values at once, in the order given");
+    outln!(out, "  list                  - List every key in the index, sorted");
+    outln!(out, "  count [tombstones]    - Show how many live keys are in the index, optionally with deleted keys awaiting compaction");
+    outln!(out, "  stats                 - Show storage statistics");
+    outln!(out, "  merge                 - Manually trigger merge operation");
+    outln!(out, "  merge --dry-run       - Show what a merge would keep and reclaim, without merging");
+    outln!(out, "  compact               - Merge every data file, including the active one, to reclaim all dead space");
+    outln!(out, "  merge-into <directory> - Compact live records into a new store in another directory, verified, leaving this one as is");
+    outln!(out, "  merge-budget <bytes>  - Merge the deadest inactive files first, rewriting at most <bytes> of live records");
+    outln!(out, "  prune                 - Delete inactive files holding only superseded records, without merging");
+    outln!(out, "  relocate <directory>  - Move all data files to another directory and keep running there");
+    outln!(out, "  backup <directory>    - Copy a point-in-time snapshot of the store into an empty directory");
+    outln!(out, "  sync                  - Flush pending writes to disk (skipped if nothing changed)");
+    outln!(out, "  rehash                - Rebuild the hash table and show probe statistics");
+    outln!(out, "  htstats [reset]       - Show probes and collisions counted by index inserts and lookups, optionally zeroing them");
+    outln!(out, "  simulate-crash        - Drop the in-memory index and recover it from data files");
+    outln!(out, "  simulate-duplicate <key> - Corrupt the index by storing a second copy of a key");
+    outln!(out, "  simulate-misdirect <key> <other_key> - Corrupt the index by pointing a key at another key's record");
+    outln!(out, "  simulate-locked-files <on|off> - Make removing merged files fail, as if still open elsewhere");
+    outln!(out, "  selftest              - Write, read, delete and merge keys in a scratch store to check the storage engine");
+    outln!(out, "  duplicates            - Check the index for keys stored more than once");
+    outln!(out, "  getmeta <key>         - Show a key's value with its file, offset, size, CRC and write time");
+    outln!(out, "  exists <key>          - Report whether a key is present without reading its value");
+    outln!(out, "  verify <key>          - Read a key's value from disk, bypassing the cache, checking the record belongs to it");
+    outln!(out, "  insert-batch <key>=<value>... - Insert several keys with a single write per data file");
+    outln!(out, "  transaction put:<key>=<value>|del:<key>... - Apply several puts and deletes all together or not at all");
+    outln!(out, "  insert-hex <key> <hex> - Insert a binary value given as hex digits");
+    outln!(out, "  get-hex <key>         - Retrieve a value as hex, even if it is not valid UTF-8");
+    outln!(out, "  hexdump <file> <offset> [len] - Show raw bytes of a record as hex and ASCII");
+    outln!(out, "  layout                - Show each data file's byte range in the store viewed as one log");
+    outln!(out, "  records               - List every record on disk, superseded ones and deletes included");
+    outln!(out, "  read-logical <offset> - Decode the record at an offset in that single-log view");
+    outln!(out, "  bench read <count>    - Time <count> random gets and report latency percentiles");
+    outln!(out, "  bench merge           - Estimate merge cost on the current data without merging");
+    outln!(out, "  bench probing [buckets] - Compare longest probes of linear probing and Robin Hood at 0.9 load");
+    outln!(out, "  scan <limit> [cursor] - Page through keys; without a cursor, walks every page");
+    outln!(out, "  scan prefix <prefix>  - Show every live key starting with <prefix> and its value, sorted");
+    outln!(out, "  cache [<capacity> [lru|fifo] | off] - Show value cache stats, or enable/disable it");
+    outln!(out, "  cache pin-budget <bytes> - Limit the total size of pinned cached values");
+    outln!(out, "  pin <key> / unpin <key> - Keep a key's value in the cache, never evicted / allow eviction again");
+    outln!(out, "  recent-deletes [n]    - Show the last n keys deleted this session (default 10)");
+    outln!(out, "  tombstones            - List every deleted key whose tombstone is still on disk, with when it was deleted");
+    outln!(out, "  timestamp             - Show the current record timestamp (ns since epoch)");
+    outln!(out, "  age                   - Show the oldest and newest record timestamps and the span between them");
+    outln!(out, "  truncate-to <ts> confirm - Roll the store back to a timestamp (destructive)");
+    outln!(out, "  export <file> [jsonl|text] - Write every live key-value pair to a file, sorted by key");
+    outln!(out, "  help                  - Show this help message");
+    outln!(out, "  exit                  - Exit the program");
+    if merge_interval_seconds == 0 {
+        outln!(out, "\nAuto-merge is disabled; use 'merge' to compact manually.");
+    } else {
+        outln!(out, "\nAuto-merge triggers after {} seconds of inactivity.", merge_interval_seconds);
+    }
+}
+
+fn show_stats(storage: &mut Storage, hash_table: &HashTable, operation_count: usize, out: &mut dyn Write) {
+    outln!(out, "=== Storage Statistics ===");
+    match storage.stats() {
+        Ok(stats) => {
+            let _ = write!(out, "{}", stats);
+        }
+        Err(e) => outln!(out, "Error getting storage stats: {}", e),
+    }
+    outln!(out, "Index: {} live keys and {} tombstones in {} buckets (load factor {:.2})",
+        storage.live_key_count(hash_table), storage.indexed_tombstone_count(), hash_table.capacity(), hash_table.load_factor());
+    if hash_table.tombstone_count() > 0 {
+        outln!(out, "Deleted slots awaiting compaction: {}", hash_table.tombstone_count());
+    }
+    // At its max load factor the table grows, rehashing every key, on the next insert of a new key
+    let max_load_factor = hash_table.max_load_factor();
+    if hash_table.load_factor() >= max_load_factor {
+        outln!(out, "Grow recommended: load factor at the {:.2} limit; {} buckets would bring it back under",
+            max_load_factor, hash_table.capacity_for_load(max_load_factor));
+    }
+    match storage.dead_space() {
+        Ok((dead, total)) => {
+            let percent = if total == 0 { 0.0 } else { dead as f64 * 100.0 / total as f64 };
+            outln!(out, "Dead space in inactive files: {} of {} bytes ({:.0}%)", dead, total, percent);
+        }
+        Err(e) => outln!(out, "Error measuring dead space: {}", e),
+    }
+    outln!(out, "Operations since last merge: {}", operation_count);
+    outln!(out, "Fsyncs performed: {}", storage.sync_count());
+    let handles = storage.read_handle_stats();
+    outln!(out, "Read handles: {} open; {} opens, {} reads reused an open handle", handles.open, handles.opens, handles.reuses);
+    if !storage.pending_deletes().is_empty() {
+        outln!(out, "Files pending deletion: {}", storage.pending_deletes().join(", "));
+    }
+}
+
+pub fn perform_merge(storage: &mut Storage, hash_table: &mut HashTable, out: &mut dyn Write) {
+    outln!(out, "Performing merge operation...");
+    match storage.merge_inactive_files_with_progress(Some(hash_table), out) {
+        Ok(()) => outln!(out, "✓ Merge completed successfully"),
+        Err(e) => outln!(out, "✗ Merge failed: {}", e),
+    }
+}
+
+/// Merges once dead space in the inactive files passes the store's merge ratio, whether or not the session is idle
+/// Returns whether a merge ran
+pub fn merge_if_dead_space_due(storage: &mut Storage, hash_table: &mut HashTable, session: &mut Session, out: &mut dyn Write) -> bool {
+    if !session.dead_space_merge_due(storage) {
+        return false;
+    }
+    let ratio = storage.dead_ratio().unwrap_or(0.0);
+    outln!(out, "\nAuto-merge triggered: {:.0}% of the inactive files' bytes are dead space...", ratio * 100.0);
+    perform_merge(storage, hash_table, out);
+    session.operation_count = 0;
+    session.dirty = true;
+    true
+}
+
+fn perform_compact(storage: &mut Storage, hash_table: &mut HashTable, out: &mut dyn Write) {
+    outln!(out, "Compacting every data file, including the active one...");
+    match storage.compact_all_with_progress(hash_table, out) {
+        Ok(()) => outln!(out, "✓ Compaction completed; new writes go to {}", storage.active_filename()),
+        Err(e) => outln!(out, "✗ Compaction failed: {}", e),
+    }
+}
+
+fn run_self_test(storage: &Storage, out: &mut dyn Write) {
+    outln!(out, "Running self-test in a scratch store...");
+    let report = storage.self_test();
+    for step in &report.steps {
+        let mark = if step.passed { "✓" } else { "✗" };
+        outln!(out, "  {} {}: {}", mark, step.name, step.detail);
+    }
+    if report.all_passed() {
+        outln!(out, "✓ Self-test passed ({} steps)", report.steps.len());
+    } else {
+        outln!(out, "✗ Self-test failed");
+    }
+}
+
+fn handle_merge_budget(storage: &mut Storage, hash_table: &mut HashTable, max_bytes: &str, out: &mut dyn Write) {
+    let max_bytes = match max_bytes.parse::<u64>() {
+        Ok(max_bytes) => max_bytes,
+        Err(_) => {
+            outln!(out, "✗ Invalid byte count: {}", max_bytes);
+            return;
+        }
+    };
+    match storage.merge_budget(hash_table, max_bytes) {
+        Ok(report) => {
+            for skipped in &report.skipped {
+                outln!(out, "  ✗ {}", skipped);
+            }
+            outln!(out, "✓ Merged {} files, rewriting {} of at most {} bytes", report.files_merged.len(), report.bytes_rewritten, max_bytes);
+            outln!(out, "  Kept {} live records, dropped {} superseded or deleted ones", report.records_kept, report.records_dropped);
+        }
+        Err(e) => outln!(out, "✗ Merge failed: {}", e),
+    }
+}
+
+fn handle_merge_into(storage: &Storage, hash_table: &HashTable, directory: &str, out: &mut dyn Write) {
+    match storage.merge_into_dir(std::path::Path::new(directory), hash_table) {
+        Ok(report) => {
+            for skipped in &report.skipped {
+                outln!(out, "  ✗ {}", skipped);
+            }
+            if report.skipped.is_empty() {
+                outln!(out, "✓ Merged {} live records from {} files into '{}/', all verified", report.records_kept, report.files_merged.len(), directory);
+            } else {
+                outln!(out, "✓ Merged {} live records from {} files into '{}/', all verified; {} damaged records left out",
+                    report.records_kept, report.files_merged.len(), directory, report.skipped.len());
+            }
+            outln!(out, "  Dropped {} superseded or deleted records; {} of {} bytes rewritten", report.records_dropped, report.bytes_rewritten, report.bytes_read);
+            outln!(out, "  This store is unchanged; point the config at '{}' to switch over", directory);
+        }
+        Err(e) => outln!(out, "✗ Merge into {} failed: {}", directory, e),
+    }
+}
+
+fn perform_rehash(hash_table: &mut HashTable, out: &mut dyn Write) {
+    let before = hash_table.probe_stats();
+    hash_table.rehash();
+    let after = hash_table.probe_stats();
+    outln!(out, "✓ Rehashed {} entries", after.entries);
+    outln!(out, "  Before: max probe length {}, avg probe length {:.2}", before.max_probe_length, before.avg_probe_length);
+    outln!(out, "  After:  max probe length {}, avg probe length {:.2}", after.max_probe_length, after.avg_probe_length);
+}
+
+fn show_hash_table_metrics(hash_table: &HashTable, reset: bool, out: &mut dyn Write) {
+    let metrics = hash_table.metrics();
+    let operations = metrics.inserts + metrics.lookups;
+    let avg_probes = if operations == 0 { 0.0 } else { metrics.total_probes as f64 / operations as f64 };
+    outln!(out, "Hash table metrics ({} buckets, {} entries):", hash_table.capacity(), hash_table.len());
+    outln!(out, "  Inserts: {}, lookups: {}", metrics.inserts, metrics.lookups);
+    outln!(out, "  Total probes: {} ({:.2} per operation)", metrics.total_probes, avg_probes);
+    outln!(out, "  Collisions: {}", metrics.collisions);
+    outln!(out, "  Longest probe sequence: {}", metrics.longest_probe);
+    outln!(out, "  Average chain length: {:.2}", metrics.avg_chain_length);
+    if reset {
+        hash_table.reset_metrics();
+        outln!(out, "✓ Metrics reset");
+    }
+}
+
+fn simulate_crash(storage: &mut Storage, hash_table: &mut HashTable, out: &mut dyn Write) {
+    outln!(out, "Simulating crash: dropping {} in-memory index entries...", hash_table.len());
+    hash_table.clear();
+
+    let start = Instant::now();
+    match storage.rebuild_index(hash_table) {
+        Ok(report) => {
+            for skipped in &report.skipped {
+                outln!(out, "  ✗ {}", skipped);
+            }
+            outln!(out, "✓ Recovered {} keys from data files in {:.2?}", report.live_keys, start.elapsed());
+        }
+        Err(e) => outln!(out, "✗ Recovery failed: {}", e),
+    }
+}
+
+fn handle_bench_read(storage: &mut Storage, hash_table: &mut HashTable, count: &str, out: &mut dyn Write) {
+    let count = match count.parse::<usize>() {
+        Ok(count) if count > 0 => count,
+        _ => {
+            outln!(out, "✗ Invalid count: {}", count);
+            return;
+        }
+    };
+    let keys: Vec<String> = hash_table.keys().map(str::to_string).collect();
+    if keys.is_empty() {
+        outln!(out, "✗ No keys to read");
+        return;
+    }
+
+    // xorshift64, seeded from the clock; good enough to spread reads across keys
+    let mut state = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_nanos() as u64 | 1;
+    let mut histogram = LatencyHistogram::new();
+    let mut errors = 0;
+    let start = Instant::now();
+    for _ in 0..count {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let key = &keys[(state % keys.len() as u64) as usize];
+
+        let read_start = Instant::now();
+        let result = storage.get_outcome(hash_table, key);
+        histogram.record(read_start.elapsed());
+        if result.is_err() {
+            errors += 1;
+        }
+    }
+
+    outln!(out, "✓ Read {} random keys ({} distinct) in {:.2?}, {} errors", count, keys.len(), start.elapsed(), errors);
+    outln!(out, "  min: {} ns", histogram.min().as_nanos());
+    outln!(out, "  p50: {} ns", histogram.percentile(50.0).as_nanos());
+    outln!(out, "  p95: {} ns", histogram.percentile(95.0).as_nanos());
+    outln!(out, "  p99: {} ns", histogram.percentile(99.0).as_nanos());
+    outln!(out, "  max: {} ns", histogram.max().as_nanos());
+    outln!(out, "  mean: {} ns", histogram.mean().as_nanos());
+}
+
+fn handle_merge_preview(storage: &Storage, out: &mut dyn Write) {
+    match storage.merge_preview() {
+        Ok(preview) => {
+            outln!(out, "✓ Merge preview for {} inactive files (dry run, nothing modified):", preview.files.len());
+            outln!(out, "  Records: {} ({} tombstones), unique keys: {}, live keys: {}",
+                preview.records, preview.tombstones, preview.unique_keys, preview.live_keys);
+            if preview.damaged_records > 0 {
+                outln!(out, "  Damaged records skipped: {}", preview.damaged_records);
+            }
+            outln!(out, "  Bytes retained: {}", preview.bytes_retained);
+            outln!(out, "  Bytes reclaimed: {}", preview.bytes_removed);
+        }
+        Err(e) => outln!(out, "✗ Merge preview failed: {}", e),
+    }
+}
+
+fn handle_bench_merge(storage: &Storage, out: &mut dyn Write) {
+    // A 1 MiB sample is enough for a stable throughput figure without slowing the command down
+    match storage.estimate_merge(1024 * 1024) {
+        Ok(estimate) => {
+            let report = &estimate.report;
+            outln!(out, "✓ Merge estimate for {} inactive files (dry run, nothing modified):", report.files_merged.len());
+            outln!(out, "  Would read: {} bytes", report.bytes_read);
+            outln!(out, "  Would write: {} bytes", report.bytes_rewritten);
+            outln!(out, "  Records kept: {}, dropped: {}", report.records_kept, report.records_dropped);
+            outln!(out, "  Read throughput: {:.1} MB/s", estimate.read_bytes_per_sec / 1_000_000.0);
+            outln!(out, "  Estimated duration: {:.2?}", estimate.estimated_duration);
+        }
+        Err(e) => outln!(out, "✗ Merge estimate failed: {}", e),
+    }
+}
+
+/// Buckets in each table `bench probing` fills, unless given
+const BENCH_PROBING_BUCKETS: u64 = 10007;
+
+/// Load factor `bench probing` fills each table to
+const BENCH_PROBING_LOAD: f64 = 0.9;
+
+/// Fills a linear probing and a Robin Hood table with the same keys to 0.9 load and compares their probe lengths
+/// Both tables hold the same keys in the same buckets' clusters, so the average is identical; Robin Hood only evens it out
+fn handle_bench_probing(buckets: u64, out: &mut dyn Write) {
+    let keys = (buckets as f64 * BENCH_PROBING_LOAD) as u64;
+    outln!(out, "✓ Probe lengths with {} keys in {} buckets (load factor {:.2}):", keys, buckets, keys as f64 / buckets as f64);
+    for method in [CollisionResolution::LinearProbing, CollisionResolution::RobinHood] {
+        // A load factor threshold of 1 keeps the table from growing before it reaches the target load
+        let mut table: HashTable<u64> = HashTable::with_max_load_factor(buckets, method.clone(), 1.0);
+        let start = Instant::now();
+        for i in 0..keys {
+            table.insert(&format!("bench_key_{}", i), i);
+        }
+        let elapsed = start.elapsed();
+        let stats = table.probe_stats();
+        outln!(out, "  {:?}: longest probe {}, average {:.2} (filled in {:.2?})", method, stats.max_probe_length, stats.avg_probe_length, elapsed);
+    }
+}
+
+fn handle_scan(hash_table: &HashTable, limit: &str, cursor: Option<&str>, out: &mut dyn Write) {
+    let limit = match limit.parse::<usize>() {
+        Ok(limit) if limit > 0 => limit,
+        _ => {
+            outln!(out, "✗ Invalid limit: {}", limit);
+            return;
+        }
+    };
+    let single_page = cursor.is_some();
+    let mut cursor = match cursor.map(str::parse::<ScanCursor>) {
+        None => ScanCursor::default(),
+        Some(Ok(cursor)) => cursor,
+        Some(Err(e)) => {
+            outln!(out, "✗ {}", e);
+            return;
+        }
+    };
+
+    let mut page_number = 1;
+    loop {
+        let (keys, next) = hash_table.scan_page(cursor, limit);
+        outln!(out, "Page {} ({} keys): {}", page_number, keys.len(), keys.join(", "));
+        match next {
+            Some(next) if single_page => {
+                outln!(out, "  Next cursor: {}", next);
+                return;
+            }
+            Some(next) => cursor = next,
+            None => {
+                outln!(out, "✓ Scan complete");
+                return;
+            }
+        }
+        page_number += 1;
+    }
+}
+
+fn handle_cache(storage: &mut Storage, args: &[&str], out: &mut dyn Write) {
+    match args {
+        [] => match storage.value_cache() {
+            Some(cache) => {
+                let stats = cache.stats();
+                outln!(out, "✓ Cache: {}/{} entries, {} hits, {} misses, {} evictions",
+                    stats.entries, stats.capacity, stats.hits, stats.misses, stats.evictions);
+                outln!(out, "  Cached keys: {}", cache.keys().join(", "));
+                outln!(out, "  Pinned keys: {} ({}/{} bytes)", cache.pinned_keys().join(", "), stats.pinned_bytes, stats.max_pinned_bytes);
+            }
+            None => outln!(out, "Cache is disabled"),
+        },
+        ["off"] => {
+            storage.set_value_cache(None);
+            outln!(out, "✓ Cache disabled");
+        }
+        ["pin-budget", bytes] => match (bytes.parse::<usize>(), storage.value_cache_mut()) {
+            (Ok(bytes), Some(cache)) => {
+                cache.set_max_pinned_bytes(bytes);
+                outln!(out, "✓ Pinned values limited to {} bytes", bytes);
+            }
+            (Err(_), _) => outln!(out, "✗ Invalid byte count: {}", bytes),
+            (_, None) => outln!(out, "Cache is disabled"),
+        },
+        [capacity, policy @ ..] if policy.len() <= 1 => {
+            let capacity = match capacity.parse::<usize>() {
+                Ok(capacity) => capacity,
+                Err(_) => {
+                    outln!(out, "✗ Invalid capacity: {}", capacity);
+                    return;
+                }
+            };
+            let policy = policy.first().copied().unwrap_or("lru");
+            let cache = match policy {
+                "lru" => ValueCache::new(capacity),
+                "fifo" => ValueCache::with_policy(capacity, Box::new(FifoPolicy::new())),
+                _ => {
+                    outln!(out, "✗ Unknown cache policy: {} (expected lru or fifo)", policy);
+                    return;
+                }
+            };
+            storage.set_value_cache(Some(cache));
+            outln!(out, "✓ Cache enabled: {} entries, {} eviction", capacity, policy);
+        }
+        _ => outln!(out, "Usage: cache [<capacity> [lru|fifo] | off | pin-budget <bytes>]"),
+    }
+}
+
+/// Reads the key so its value is cached, then pins it
+fn handle_pin(storage: &mut Storage, hash_table: &HashTable, key: &str, out: &mut dyn Write) {
+    match storage.get_outcome(hash_table, key) {
+        Ok(GetOutcome::Found(_)) => match storage.pin_key(key) {
+            Ok(()) => outln!(out, "✓ Pinned {}", key),
+            Err(e) => outln!(out, "✗ Cannot pin {}: {}", key, e),
+        },
+        Ok(GetOutcome::Deleted) => outln!(out, "✗ Key '{}' has been deleted", key),
+        Ok(GetOutcome::Missing) => outln!(out, "✗ Key '{}' not found", key),
+        Err(e) => outln!(out, "✗ Error reading {}: {}", key, e),
+    }
+}
+
+fn handle_scan_prefix(storage: &mut Storage, hash_table: &HashTable, prefix: &str, out: &mut dyn Write) {
+    let matches = storage.scan_prefix(hash_table, prefix);
+    outln!(out, "✓ {} keys starting with '{}':", matches.len(), prefix);
+    for (key, value) in matches {
+        outln!(out, "  {}: {}", key, value);
+    }
+}
+
+fn list_keys(hash_table: &HashTable, out: &mut dyn Write) {
+    let mut keys: Vec<&str> = hash_table.keys().collect();
+    keys.sort();
+    outln!(out, "✓ {} keys in the index:", keys.len());
+    for key in keys {
+        outln!(out, "  {}", key);
+    }
+}
+
+/// Reports the index's entry counter rather than walking the keys
+fn count_keys(storage: &Storage, hash_table: &HashTable, show_tombstones: bool, out: &mut dyn Write) {
+    outln!(out, "✓ {} live keys in the index", storage.live_key_count(hash_table));
+    if show_tombstones {
+        outln!(out, "  Tombstones pending compaction: {}", storage.indexed_tombstone_count());
+    }
+}
+
+/// Lists every record on disk, reading each value back through the offset and checksum the iterator reported
+fn show_records(storage: &mut Storage, out: &mut dyn Write) {
+    let records: Vec<_> = storage.iter_all_records().collect();
+    outln!(out, "✓ {} records on disk:", records.len());
+    for record in records {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                outln!(out, "  ✗ {}", e);
+                continue;
+            }
+        };
+        if record.record_type == RecordType::Tombstone {
+            outln!(out, "  {}@{} delete {}", record.filename, record.record_offset, record.key);
+            continue;
+        }
+        let stored = match record.compression {
+            Compression::None => String::new(),
+            compression => format!(" [{:?}, {} bytes stored]", compression, record.value_size),
+        };
+        match storage.read_value_bytes(&record.filename, record.value_offset, record.value_size, record.crc, &record.key) {
+            Ok(value) => outln!(out, "  {}@{} put {} = {}{}", record.filename, record.record_offset, record.key, String::from_utf8_lossy(&value), stored),
+            Err(e) => outln!(out, "  {}@{} put {}: ✗ {}", record.filename, record.record_offset, record.key, e),
+        }
+    }
+}
+
+fn show_layout(storage: &Storage, out: &mut dyn Write) {
+    match storage.logical_layout() {
+        Ok(layout) => {
+            outln!(out, "✓ Logical layout ({} files):", layout.len());
+            for (filename, start, len) in layout {
+                outln!(out, "  {}: start {}, length {}", filename, start, len);
+            }
+        }
+        Err(e) => outln!(out, "✗ Failed to compute layout: {}", e),
+    }
+}
+
+fn handle_read_logical(storage: &Storage, offset: u64, out: &mut dyn Write) {
+    match storage.resolve_logical(offset) {
+        Ok(Some((filename, local_offset))) => outln!(out, "  Logical offset {} is {}:{}", offset, filename, local_offset),
+        Ok(None) => {}
+        Err(e) => {
+            outln!(out, "✗ Failed to resolve logical offset {}: {}", offset, e);
+            return;
+        }
+    }
+    match storage.read_logical(offset) {
+        Ok((key, value)) => outln!(out, "✓ {}: {}", key, value),
+        Err(e) => outln!(out, "✗ {}", e),
+    }
+}
+
+fn show_age(storage: &Storage, out: &mut dyn Write) {
+    match storage.timestamp_range() {
+        Ok(Some((oldest, newest))) => {
+            outln!(out, "✓ Oldest record: {}", oldest);
+            outln!(out, "  Newest record: {}", newest);
+            outln!(out, "  Span: {:.2?}", Duration::from_nanos(newest - oldest));
+        }
+        Ok(None) => outln!(out, "✓ Store is empty"),
+        Err(e) => outln!(out, "✗ Failed to scan record timestamps: {}", e),
+    }
+}
+
+fn handle_truncate_to(storage: &mut Storage, hash_table: &mut HashTable, timestamp: &str, out: &mut dyn Write) {
+    let timestamp = match timestamp.parse::<u64>() {
+        Ok(timestamp) => timestamp,
+        Err(_) => {
+            outln!(out, "✗ Invalid timestamp: {}", timestamp);
+            return;
+        }
+    };
+    match storage.truncate_to(timestamp, hash_table) {
+        Ok(report) => {
+            for skipped in &report.skipped {
+                outln!(out, "  ✗ {}", skipped);
+            }
+            outln!(out, "✓ Rolled back to {}: discarded {} records, {} keys live", timestamp, report.records_discarded, report.live_keys);
+            for (filename, bytes) in &report.files_truncated {
+                outln!(out, "  Truncated {} by {} bytes", filename, bytes);
+            }
+            for filename in &report.files_removed {
+                outln!(out, "  Removed {}", filename);
+            }
+        }
+        Err(e) => outln!(out, "✗ Truncate failed: {}", e),
+    }
+}
+
+fn handle_export(storage: &Storage, path: &str, format: &str, out: &mut dyn Write) {
+    let format = match format {
+        "jsonl" => ExportFormat::JsonLines,
+        "text" => ExportFormat::Text,
+        other => {
+            outln!(out, "✗ Unknown export format '{}' (expected jsonl or text)", other);
+            return;
+        }
+    };
+    let file = match std::fs::File::create(path) {
+        Ok(file) => file,
+        Err(e) => {
+            outln!(out, "✗ Failed to create {}: {}", path, e);
+            return;
+        }
+    };
+    let mut writer = std::io::BufWriter::new(file);
+    match storage.export_to(&mut writer, format) {
+        Ok(report) => {
+            for skipped in &report.skipped {
+                outln!(out, "  ✗ {}", skipped);
+            }
+            outln!(out, "✓ Exported {} records to {}", report.records, path);
+        }
+        Err(e) => outln!(out, "✗ Export failed: {}", e),
+    }
+}
+
+fn simulate_misdirect(hash_table: &mut HashTable, key: &str, other_key: &str, out: &mut dyn Write) {
+    if hash_table.get(key).is_none() {
+        outln!(out, "✗ Key '{}' not found", key);
+        return;
+    }
+    match hash_table.get(other_key).cloned() {
+        Some(location) => {
+            hash_table.insert(key, location);
+            outln!(out, "✓ Index entry for '{}' now points at the record for '{}'", key, other_key);
+        }
+        None => outln!(out, "✗ Key '{}' not found", other_key),
+    }
+}
+
+fn simulate_duplicate(hash_table: &mut HashTable, key: &str, out: &mut dyn Write) {
+    let location = match hash_table.get(key) {
+        Some(location) => location.clone(),
+        None => {
+            outln!(out, "✗ Key '{}' not found", key);
+            return;
+        }
+    };
+    if hash_table.insert_raw_duplicate(crate::Entry::new(key, location)) {
+        outln!(out, "✓ Stored a duplicate copy of '{}' in the index", key);
+    } else {
+        outln!(out, "✗ No free bucket for a duplicate of '{}'", key);
+    }
+}
+
+fn handle_insert(storage: &mut Storage, hash_table: &mut HashTable, key: &str, value: &str, out: &mut dyn Write) {
+    match storage.write(key, value) {
+        Ok(location) => {
+            outln!(out, "✓ Inserted {}: {} (file: {}, value_offset: {})", key, value, location.filename, location.value_offset);
+            storage.index_location(hash_table, key, location);
+        }
+        Err(e) => outln!(out, "✗ Failed to insert {}: {}", key, e),
+    }
+}
+
+/// Overwrites the stored value when the new one has the same length, appending a record like insert otherwise
+fn handle_update_in_place(storage: &mut Storage, hash_table: &mut HashTable, key: &str, value: &str, out: &mut dyn Write) {
+    match storage.update_in_place(hash_table, key, value.as_bytes()) {
+        Ok(location) => outln!(out, "✓ Overwrote {} in place: {} (file: {}, value_offset: {})", key, value, location.filename, location.value_offset),
+        Err(StorageError::KeyNotFound(_)) => outln!(out, "✗ Key '{}' not found", key),
+        Err(e @ (StorageError::SizeMismatch { .. } | StorageError::IncompatibleFormat(_) | StorageError::KeyDeleted(_))) => {
+            outln!(out, "  {}; appending a new record instead", e);
+            handle_insert(storage, hash_table, key, value, out);
+        }
+        Err(e) => outln!(out, "✗ Failed to update {} in place: {}", key, e),
+    }
+}
+
+/// setnx writes only absent keys, setxx only existing ones; deleted keys count as absent
+fn handle_conditional_insert(storage: &mut Storage, hash_table: &mut HashTable, key: &str, value: &str, if_absent: bool, out: &mut dyn Write) {
+    let result = if if_absent {
+        storage.put_if_absent(hash_table, key, value)
+    } else {
+        storage.replace(hash_table, key, value)
+    };
+    match result {
+        Ok(true) => outln!(out, "✓ Inserted {}: {}", key, value),
+        Ok(false) if if_absent => outln!(out, "✗ '{}' already exists, not overwritten", key),
+        Ok(false) => outln!(out, "✗ '{}' does not exist, nothing replaced", key),
+        Err(e) => outln!(out, "✗ Failed to insert {}: {}", key, e),
+    }
+}
+
+fn handle_insert_batch(storage: &mut Storage, hash_table: &mut HashTable, pairs: &[&str], out: &mut dyn Write) {
+    let mut entries = Vec::with_capacity(pairs.len());
+    for pair in pairs {
+        match pair.split_once('=') {
+            Some((key, value)) if !key.is_empty() => entries.push((key, value)),
+            _ => {
+                outln!(out, "✗ Expected <key>=<value>, got '{}'", pair);
+                return;
+            }
+        }
+    }
+    match storage.write_batch(&entries) {
+        Ok(locations) => {
+            for ((key, value), location) in entries.iter().zip(locations) {
+                outln!(out, "✓ Inserted {}: {} (file: {}, value_offset: {})", key, value, location.filename, location.value_offset);
+                storage.index_location(hash_table, key, location);
+            }
+            outln!(out, "✓ Wrote {} keys in one batch", entries.len());
+        }
+        Err(e) => outln!(out, "✗ Failed to insert batch: {}", e),
+    }
+}
+
+fn handle_transaction(storage: &mut Storage, hash_table: &mut HashTable, args: &[&str], recent_deletes: &mut RecentDeletes, out: &mut dyn Write) {
+    let mut ops = Vec::with_capacity(args.len());
+    for arg in args {
+        let op = match (arg.strip_prefix("put:").and_then(|pair| pair.split_once('=')), arg.strip_prefix("del:")) {
+            (Some((key, value)), _) if !key.is_empty() => WriteOp::Put(key, value),
+            (_, Some(key)) if !key.is_empty() => WriteOp::Delete(key),
+            _ => {
+                outln!(out, "✗ Expected put:<key>=<value> or del:<key>, got '{}'", arg);
+                return;
+            }
+        };
+        ops.push(op);
+    }
+    match storage.write_transaction(&ops) {
+        Ok(locations) => {
+            for (op, location) in ops.iter().zip(locations) {
+                match op {
+                    WriteOp::Put(key, value) => {
+                        outln!(out, "✓ Inserted {}: {} (file: {}, value_offset: {})", key, value, location.filename, location.value_offset);
+                        storage.index_location(hash_table, key, location);
+                    }
+                    WriteOp::Delete(key) => {
+                        outln!(out, "✓ Deleted {} (tombstone: file {}, value_offset {})", key, location.filename, location.value_offset);
+                        recent_deletes.push(key, location.timestamp);
+                        storage.index_tombstone(hash_table, key, location);
+                    }
+                }
+            }
+            outln!(out, "✓ Committed transaction of {} operations", ops.len());
+        }
+        Err(e) => outln!(out, "✗ Transaction failed: {}", e),
+    }
+}
+
+/// Returns the delete timestamp if the tombstone was written
+fn handle_delete(storage: &mut Storage, hash_table: &mut HashTable, key: &str, reason: &str, out: &mut dyn Write) -> Option<u64> {
+    match storage.delete_with_reason_returning_old(hash_table, key, reason) {
+        Ok(old) => {
+            let location = hash_table.get(key).expect("the tombstone was just indexed");
+            outln!(out, "✓ Deleted {} (tombstone: file {}, value_offset {})", key, location.filename, location.value_offset);
+            match old {
+                RemovedValue::Value(old) => outln!(out, "  Removed value: {}", old),
+                RemovedValue::Absent => outln!(out, "  Key had no value to remove (not found or already deleted)"),
+                RemovedValue::Unreadable(e) => outln!(out, "  Removed value could not be read: {}", e),
+            }
+            Some(location.timestamp)
+        }
+        Err(e) => {
+            outln!(out, "✗ Failed to delete {}: {}", key, e);
+            None
+        }
+    }
+}
+
+fn show_recent_deletes(recent_deletes: &RecentDeletes, n: usize, out: &mut dyn Write) {
+    if recent_deletes.is_empty() {
+        outln!(out, "No keys deleted this session");
+        return;
+    }
+    outln!(out, "✓ Last {} of {} recent deletes (newest first):", n.min(recent_deletes.len()), recent_deletes.len());
+    for (key, timestamp) in recent_deletes.latest(n) {
+        outln!(out, "  {} at {}", key, timestamp);
+    }
+}
+
+/// Unlike recent-deletes, covers every delete still on disk, from any session
+fn show_tombstones(storage: &Storage, hash_table: &HashTable, out: &mut dyn Write) {
+    let tombstones = match storage.list_tombstones() {
+        Ok(tombstones) => tombstones,
+        Err(e) => {
+            outln!(out, "✗ Failed to list tombstones: {}", e);
+            return;
+        }
+    };
+    if tombstones.is_empty() {
+        outln!(out, "No tombstones on disk (merge discards them)");
+        return;
+    }
+    outln!(out, "✓ {} deleted keys with tombstones on disk (oldest delete first):", tombstones.len());
+    for (key, timestamp) in tombstones {
+        // The index only points past the tombstone if the key was written again
+        let rewritten = hash_table.get(&key).is_some_and(|location| location.timestamp > timestamp);
+        outln!(out, "  {} deleted {}{}", key, format_timestamp(timestamp), if rewritten { ", written again since" } else { "" });
+    }
+}
+
+fn handle_get(storage: &mut Storage, hash_table: &mut HashTable, key: &str, out: &mut dyn Write) {
+    match storage.get_outcome(hash_table, key) {
+        Ok(GetOutcome::Found(value)) => {
+            outln!(out, "✓ {}: {}", key, value);
+        }
+        Ok(GetOutcome::Deleted) => match storage.delete_reason(hash_table, key) {
+            Ok(Some(reason)) if !reason.is_empty() => {
+                outln!(out, "✗ Key '{}' has been deleted (reason: {})", key, reason);
+            }
+            _ => outln!(out, "✗ Key '{}' has been deleted", key),
+        },
+        Ok(GetOutcome::Missing) => {
+            outln!(out, "✗ Key '{}' not found", key);
+        }
+        // The index and files are locked together, so looking the key up again would find the same entry
+        Err(StorageError::FileMissing(filename)) => {
+            outln!(out, "✗ Can't read {}: its index entry points into {}, which no longer exists (restart to rebuild the index)", key, filename);
+        }
+        Err(e) => {
+            outln!(out, "✗ Error reading {}: {}", key, e);
+        }
+    }
+}
+
+fn handle_mget(storage: &mut Storage, hash_table: &HashTable, keys: &[&str], out: &mut dyn Write) {
+    for (key, result) in keys.iter().zip(storage.get_many(hash_table, keys)) {
+        match result {
+            Ok(value) => outln!(out, "✓ {}: {}", key, value),
+            Err(e @ (StorageError::KeyNotFound(_) | StorageError::KeyDeleted(_))) => outln!(out, "✗ {}", e),
+            Err(e) => outln!(out, "✗ Error reading {}: {}", key, e),
+        }
+    }
+}
+
+fn handle_getmeta(storage: &mut Storage, hash_table: &HashTable, key: &str, out: &mut dyn Write) {
+    match storage.get_full(hash_table, key) {
+        Ok(Some((value, location))) => {
+            outln!(out, "✓ {}: {}", key, value);
+            outln!(out, "  File: {}, value offset {}", location.filename, location.value_offset);
+            outln!(out, "  Stored size: {} bytes, CRC {:#06x}", location.value_size, location.crc);
+            outln!(out, "  Written: {}", format_timestamp(location.timestamp));
+        }
+        // A deleted key is still indexed at its tombstone until the next merge
+        Ok(None) => match hash_table.get(key) {
+            Some(location) => outln!(out, "✗ Key '{}' has been deleted (tombstone in {} at value offset {}, written {})",
+                key, location.filename, location.value_offset, format_timestamp(location.timestamp)),
+            None => outln!(out, "✗ Key '{}' not found", key),
+        },
+        Err(e) => outln!(out, "✗ Error reading {}: {}", key, e),
+    }
+}
+
+/// Formats nanoseconds since the Unix epoch as a UTC date and time, with how long ago that was
+fn format_timestamp(nanos: u64) -> String {
+    let secs = nanos / 1_000_000_000;
+    let (days, time_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's days_from_civil, inverted)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_nanos() as u64;
+    let age = match now.checked_sub(nanos) {
+        Some(age) => format!("{:.1?} ago", Duration::from_nanos(age)),
+        None => "in the future".to_string(),
+    };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09} UTC ({})",
+        year, month, day, time_of_day / 3600, time_of_day % 3600 / 60, time_of_day % 60, nanos % 1_000_000_000, age)
+}
+
+/// Answers from the index alone, except that a key deleted since the last merge is still indexed (at its
+/// tombstone), so a present key's record header is read to tell the two apart; the value is never read
+fn handle_exists(storage: &Storage, hash_table: &HashTable, key: &str, out: &mut dyn Write) {
+    match hash_table.get(key) {
+        None => outln!(out, "✗ '{}' is absent", key),
+        Some(_) if storage.is_indexed_tombstone(key) => outln!(out, "✗ '{}' is absent (deleted)", key),
+        Some(_) => outln!(out, "✓ '{}' is present", key),
+    }
+}
+
+fn handle_verify(storage: &mut Storage, hash_table: &HashTable, key: &str, out: &mut dyn Write) {
+    let location = match hash_table.get(key) {
+        Some(location) => location,
+        None => {
+            outln!(out, "✗ Key '{}' not found", key);
+            return;
+        }
+    };
+    match storage.read_verified(location, key) {
+        Ok(value) => outln!(out, "✓ {}: {} (record verified on disk in {} at value offset {})", key, value, location.filename, location.value_offset),
+        Err(StorageError::KeyDeleted(_)) => outln!(out, "✓ Key '{}' is deleted (tombstone verified on disk in {})", key, location.filename),
+        Err(e) => outln!(out, "✗ Verification of {} failed: {}", key, e),
+    }
+}
+
+fn handle_insert_hex(storage: &mut Storage, hash_table: &mut HashTable, key: &str, hex: &str, out: &mut dyn Write) {
+    let value = match parse_hex(hex) {
+        Some(value) => value,
+        None => {
+            outln!(out, "✗ Invalid hex bytes: {}", hex);
+            return;
+        }
+    };
+    match storage.write_bytes(key, &value) {
+        Ok(location) => {
+            outln!(out, "✓ Inserted {}: {} bytes (file: {}, value_offset: {})", key, value.len(), location.filename, location.value_offset);
+            storage.index_location(hash_table, key, location);
+        }
+        Err(e) => outln!(out, "✗ Failed to insert {}: {}", key, e),
+    }
+}
+
+fn handle_get_hex(storage: &mut Storage, hash_table: &HashTable, key: &str, out: &mut dyn Write) {
+    let location = match hash_table.get(key) {
+        Some(location) => location.clone(),
+        None => {
+            outln!(out, "✗ Key '{}' not found", key);
+            return;
+        }
+    };
+    match storage.read_location_bytes(&location, key) {
+        Ok(value) => {
+            let hex: String = value.iter().map(|byte| format!("{:02x}", byte)).collect();
+            outln!(out, "✓ {}: {}", key, hex);
+        }
+        Err(StorageError::KeyDeleted(_)) => outln!(out, "✗ Key '{}' has been deleted", key),
+        Err(e) => outln!(out, "✗ Error reading {}: {}", key, e),
+    }
+}
+
+/// Parses an even-length string of hex digits into bytes
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+fn handle_hexdump(storage: &mut Storage, filename: &str, offset: &str, len: Option<&str>, out: &mut dyn Write) {
+    let offset = match offset.parse::<u64>() {
+        Ok(offset) => offset,
+        Err(_) => {
+            outln!(out, "✗ Invalid offset: {}", offset);
+            return;
+        }
+    };
+    let len = match len.map(str::parse::<u64>) {
+        None => None,
+        Some(Ok(len)) => Some(len),
+        Some(Err(_)) => {
+            outln!(out, "✗ Invalid length: {}", len.unwrap_or_default());
+            return;
+        }
+    };
+
+    match storage.read_raw(filename, offset, len) {
+        Ok(bytes) => {
+            let _ = write!(out, "{}", format_hexdump(&bytes, offset));
+        }
+        Err(e) => outln!(out, "✗ Failed to read {} at {}: {}", filename, offset, e),
+    }
+}
+
+/// Formats bytes like `xxd`: offset, 16 bytes as grouped hex pairs, then an ASCII gutter
+/// Non-printable bytes show as '.' in the gutter
+fn format_hexdump(bytes: &[u8], base_offset: u64) -> String {
+    let mut output = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let mut hex = String::new();
+        for (i, byte) in chunk.iter().enumerate() {
+            if i > 0 && i % 2 == 0 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        let ascii: String = chunk.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        output.push_str(&format!("{:08x}: {:<39}  {}\n", base_offset + line as u64 * 16, hex, ascii));
+    }
+    output
+}
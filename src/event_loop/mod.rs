@@ -1,8 +1,21 @@
 
+#[cfg(unix)]
 pub mod terminal_event_loop;
+// Built everywhere so it keeps compiling, though main only selects it where TerminalEventLoop is unavailable
+#[cfg_attr(unix, allow(dead_code))]
+pub mod blocking_event_loop;
+pub mod commands;
+pub mod tcp_event_loop;
 
+use std::sync::Mutex;
+use std::time::Duration;
 use crate::{Storage, HashTable};
 
+/// How long the event loops wait for input before waking to sync idle writes and check for auto-merge
+/// Auto-merge starts at most this long after it becomes due
+pub const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub trait EventLoop {
-    fn run(&mut self, storage: &mut Storage, hash_table: &mut HashTable, merge_interval_seconds: u64);
+    /// Runs until exit; `store` is locked for each command, so a background merge can run between them
+    fn run(&mut self, store: &Mutex<(Storage, HashTable)>, merge_interval_seconds: u64);
 }
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use mio::{Events, Interest, Poll, Token};
+use mio::net::{TcpListener, TcpStream};
+use crate::{Storage, HashTable};
+use crate::event_loop::EventLoop;
+use crate::event_loop::commands::{handle_command, merge_if_dead_space_due, perform_merge, Session};
+
+/// Serves the command line protocol to clients over TCP instead of reading stdin
+/// Each client sends one command per line and gets back the same text the terminal prints for it.
+/// Clients share the store and one Session, so auto-merge waits until every client has been idle;
+/// `exit` closes only that client's connection, and the server runs until it is killed
+pub struct TcpEventLoop {
+    pub address: SocketAddr,
+    /// Longest wait for client activity before the idle sync and auto-merge checks run
+    pub poll_timeout: Duration,
+    /// Fsync pending writes whenever a wait ends without input (the "dirty since last sync" flag is set)
+    pub sync_on_idle: bool,
+    /// Longest command line a client may send, newline excluded; a client going past it is disconnected
+    /// rather than buffered without bound
+    pub max_line_length: usize,
+}
+
+const LISTENER_TOKEN: Token = Token(0);
+
+/// Default TcpEventLoop::max_line_length
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+/// A connected client: bytes received but not yet forming a whole line, and response bytes not yet sent
+struct Client {
+    stream: TcpStream,
+    address: SocketAddr,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    /// Set once the client sent `exit` or closed its end; the connection closes when output is drained
+    closing: bool,
+}
+
+impl EventLoop for TcpEventLoop {
+    fn run(&mut self, store: &Mutex<(Storage, HashTable)>, merge_interval_seconds: u64) {
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(128);
+
+        let mut listener = match TcpListener::bind(self.address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                println!("✗ Failed to listen on {}: {}", self.address, e);
+                return;
+            }
+        };
+        poll.registry().register(&mut listener, LISTENER_TOKEN, Interest::READABLE).unwrap();
+        // Printed from the bound socket, so a configured port of 0 shows the port actually chosen
+        match listener.local_addr() {
+            Ok(address) => println!("✓ Listening on {}", address),
+            Err(_) => println!("✓ Listening on {}", self.address),
+        }
+
+        let mut clients: HashMap<Token, Client> = HashMap::new();
+        let mut next_token = LISTENER_TOKEN.0 + 1;
+        let mut last_activity = Instant::now();
+        let mut session = Session::new(merge_interval_seconds);
+
+        loop {
+            // Same timeout as TerminalEventLoop, so idle sync and auto-merge still get checked
+            match poll.poll(&mut events, Some(self.poll_timeout)) {
+                Ok(_) => (),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    println!("Error polling for events: {}", e);
+                    break;
+                }
+            }
+
+            // Poll timed out with no client activity: sync pending writes while idle
+            {
+                let storage = &mut store.lock().unwrap().0;
+                if self.sync_on_idle && events.is_empty() && session.dirty {
+                    match storage.sync() {
+                        Ok(()) => session.dirty = false,
+                        Err(e) => println!("✗ Failed to sync storage: {}", e),
+                    }
+                }
+                if let Err(e) = storage.sync_if_due() {
+                    println!("✗ Failed to sync storage: {}", e);
+                }
+            }
+
+            for event in events.iter() {
+                if event.token() == LISTENER_TOKEN {
+                    accept_clients(&listener, &poll, &mut clients, &mut next_token);
+                    continue;
+                }
+                let Some(client) = clients.get_mut(&event.token()) else {
+                    continue;
+                };
+                if event.is_readable() && read_commands(client, store, &mut session, self.max_line_length) {
+                    last_activity = Instant::now();
+                }
+                let drained = match flush_output(client) {
+                    Ok(drained) => drained,
+                    Err(e) => {
+                        println!("Dropping client {}: {}", client.address, e);
+                        client.output.clear();
+                        client.closing = true;
+                        true
+                    }
+                };
+                if drained && client.closing {
+                    let mut client = clients.remove(&event.token()).unwrap();
+                    let _ = poll.registry().deregister(&mut client.stream);
+                    println!("Client {} disconnected", client.address);
+                    continue;
+                }
+                // Only ask for writable events while a response is waiting, or they'd wake the loop constantly
+                let interest = if drained { Interest::READABLE } else { Interest::READABLE | Interest::WRITABLE };
+                if let Err(e) = poll.registry().reregister(&mut client.stream, event.token(), interest) {
+                    println!("Dropping client {}: {}", client.address, e);
+                    let mut client = clients.remove(&event.token()).unwrap();
+                    let _ = poll.registry().deregister(&mut client.stream);
+                }
+            }
+
+            if session.auto_merge_due(last_activity.elapsed()) {
+                println!("\nAuto-merge triggered due to inactivity...");
+                let mut state = store.lock().unwrap();
+                let (storage, hash_table) = &mut *state;
+                perform_merge(storage, hash_table, &mut io::stdout());
+                last_activity = Instant::now();
+                session.operation_count = 0;
+                session.dirty = true;
+            }
+
+            let mut state = store.lock().unwrap();
+            let (storage, hash_table) = &mut *state;
+            if merge_if_dead_space_due(storage, hash_table, &mut session, &mut io::stdout()) {
+                last_activity = Instant::now();
+            }
+        }
+    }
+}
+
+/// Accepts every pending connection, registering each under a fresh token
+fn accept_clients(listener: &TcpListener, poll: &Poll, clients: &mut HashMap<Token, Client>, next_token: &mut usize) {
+    loop {
+        match listener.accept() {
+            Ok((mut stream, address)) => {
+                let token = Token(*next_token);
+                *next_token += 1;
+                if let Err(e) = poll.registry().register(&mut stream, token, Interest::READABLE) {
+                    println!("✗ Failed to register client {}: {}", address, e);
+                    continue;
+                }
+                println!("Client {} connected", address);
+                clients.insert(token, Client { stream, address, input: Vec::new(), output: Vec::new(), closing: false });
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                println!("Error accepting connection: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Reads everything the client has sent and runs each complete line as a command, queueing the responses
+/// Returns whether any command ran. Mio only reports readiness once per edge, so reading stops at WouldBlock.
+/// A line longer than `max_line_length`, complete or still arriving, gets an error and closes the connection;
+/// the commands before it still run
+fn read_commands(client: &mut Client, store: &Mutex<(Storage, HashTable)>, session: &mut Session, max_line_length: usize) -> bool {
+    let mut buffer = [0u8; 4096];
+    // Bytes after the last newline received, i.e. the length so far of the line still arriving
+    let mut unterminated = client.input.len() - client.input.iter().rposition(|&b| b == b'\n').map_or(0, |end| end + 1);
+    while unterminated <= max_line_length {
+        match client.stream.read(&mut buffer) {
+            Ok(0) => {
+                // A last command without a trailing newline still runs, as it would from stdin
+                if !client.input.is_empty() && !client.input.ends_with(b"\n") {
+                    client.input.push(b'\n');
+                }
+                client.closing = true;
+                break;
+            }
+            Ok(n) => {
+                let received = &buffer[..n];
+                unterminated = match received.iter().rposition(|&b| b == b'\n') {
+                    Some(end) => n - end - 1,
+                    None => unterminated + n,
+                };
+                client.input.extend_from_slice(received);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                println!("Error reading from client {}: {}", client.address, e);
+                client.closing = true;
+                break;
+            }
+        }
+    }
+
+    let mut ran = false;
+    loop {
+        let end = client.input.iter().position(|&b| b == b'\n');
+        if end.unwrap_or(client.input.len()) > max_line_length {
+            println!("Dropping client {}: sent a line longer than {} bytes", client.address, max_line_length);
+            let _ = writeln!(client.output, "✗ Line longer than {} bytes; closing the connection", max_line_length);
+            client.closing = true;
+            client.input.clear();
+            break;
+        }
+        let Some(end) = end else {
+            break;
+        };
+        let line: Vec<u8> = client.input.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line);
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+        ran = true;
+        let mut state = store.lock().unwrap();
+        let (storage, hash_table) = &mut *state;
+        if handle_command(input, storage, hash_table, session, &mut client.output) {
+            // Commands after `exit` are dropped, as the terminal stops reading once it exits
+            client.closing = true;
+            client.input.clear();
+        }
+    }
+    ran
+}
+
+/// Sends as much queued output as the socket accepts; returns whether all of it was sent
+fn flush_output(client: &mut Client) -> io::Result<bool> {
+    while !client.output.is_empty() {
+        match client.stream.write(&client.output) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                client.output.drain(..n);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
@@ -3,15 +3,22 @@ use std::time::{Duration, Instant};
 use mio::{Events, Interest, Poll, Token};
 use mio::unix::SourceFd;
 use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
 use crate::{Storage, HashTable};
 use crate::event_loop::EventLoop;
+use crate::event_loop::commands::{handle_command, merge_if_dead_space_due, perform_merge, Session};
 
-pub struct TerminalEventLoop;
+pub struct TerminalEventLoop {
+    /// Longest wait for input before the idle sync and auto-merge checks run, see DEFAULT_POLL_TIMEOUT
+    pub poll_timeout: Duration,
+    /// Fsync pending writes whenever a wait ends without input (the "dirty since last sync" flag is set)
+    pub sync_on_idle: bool,
+}
 
 const STDIN_TOKEN: Token = Token(0);
 
 impl EventLoop for TerminalEventLoop {
-    fn run(&mut self, storage: &mut Storage, hash_table: &mut HashTable, merge_interval_seconds: u64) {
+    fn run(&mut self, store: &Mutex<(Storage, HashTable)>, merge_interval_seconds: u64) {
         let mut poll = Poll::new().unwrap();
         let mut events = Events::with_capacity(128);
         
@@ -23,13 +30,13 @@ impl EventLoop for TerminalEventLoop {
         let mut buffer = String::new();
 
         let mut last_activity = Instant::now();
-        let merge_timeout = Duration::from_secs(merge_interval_seconds);
-        let mut operation_count = 0;
+        let mut session = Session::new(merge_interval_seconds);
 
         loop {
-            // Use a short poll timeout to regularly check for auto-merge
+            // Use a short poll timeout to regularly check for auto-merge and idle sync
+            // (kept even when auto-merge is disabled, so the loop's wakeup rate doesn't change)
             // println!("[DEBUG] Polling for events...");
-            match poll.poll(&mut events, Some(Duration::from_secs(1))) {
+            match poll.poll(&mut events, Some(self.poll_timeout)) {
                 Ok(_) => (),
                 Err(e) if e.kind() == io::ErrorKind::Interrupted => continue, // Retry on interrupt
                 Err(e) => {
@@ -38,6 +45,20 @@ impl EventLoop for TerminalEventLoop {
                 }
             }
 
+            // Poll timed out with no input: sync pending writes while the user is idle
+            {
+                let storage = &mut store.lock().unwrap().0;
+                if self.sync_on_idle && events.is_empty() && session.dirty {
+                    match storage.sync() {
+                        Ok(()) => session.dirty = false,
+                        Err(e) => println!("✗ Failed to sync storage: {}", e),
+                    }
+                }
+                if let Err(e) = storage.sync_if_due() {
+                    println!("✗ Failed to sync storage: {}", e);
+                }
+            }
+
             for event in events.iter() {
                 if event.token() == STDIN_TOKEN {
                     // println!("[DEBUG] STDIN event received.");
@@ -57,11 +78,18 @@ impl EventLoop for TerminalEventLoop {
 
                                     last_activity = Instant::now();
                                     // println!("[DEBUG] Handling command: {}", input);
-                                    if handle_command(input, storage, hash_table, &mut operation_count, merge_interval_seconds) {
+                                    let mut state = store.lock().unwrap();
+                                    let (storage, hash_table) = &mut *state;
+                                    if handle_command(input, storage, hash_table, &mut session, &mut io::stdout()) {
                                         return; // Exit command was received
                                     }
                                 }
                                 buffer.clear();
+                                // stdin is blocking, so only keep reading while whole lines are already buffered;
+                                // otherwise go back to poll so the idle sync and auto-merge checks can run
+                                if reader.buffer().is_empty() {
+                                    break;
+                                }
                             }
                             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
                                 // println!("[DEBUG] read_line WouldBlock. No more lines for now.");
@@ -78,142 +106,26 @@ impl EventLoop for TerminalEventLoop {
             }
 
             // Check for auto-merge after handling events
-            // println!("[DEBUG] Checking auto-merge. Operation count: {}, Elapsed: {:?}, Timeout: {:?}", operation_count, last_activity.elapsed(), merge_timeout);
-            if operation_count > 0 && last_activity.elapsed() >= merge_timeout {
+            // println!("[DEBUG] Checking auto-merge. Operation count: {}, Elapsed: {:?}", operation_count, last_activity.elapsed());
+            if session.auto_merge_due(last_activity.elapsed()) {
                 println!("\nAuto-merge triggered due to inactivity...");
-                perform_merge(storage, hash_table);
+                let mut state = store.lock().unwrap();
+                let (storage, hash_table) = &mut *state;
+                perform_merge(storage, hash_table, &mut io::stdout());
                 last_activity = Instant::now();
-                operation_count = 0;
+                session.operation_count = 0;
+                session.dirty = true;
                 print!("> ");
                 io::stdout().flush().unwrap();
             }
-        }
-    }
-}
 
-// Returns true if the command was to exit
-fn handle_command(input: &str, storage: &mut Storage, hash_table: &mut HashTable, operation_count: &mut usize, merge_interval_seconds: u64) -> bool {
-    let parts: Vec<&str> = input.split_whitespace().collect();
-    if parts.is_empty() {
-        return false;
-    }
-
-    match parts[0].to_lowercase().as_str() {
-        "exit" | "quit" => {
-            println!("Goodbye!");
-            return true;
-        }
-        "help" => {
-            show_help(merge_interval_seconds);
-        }
-        "stats" => {
-            show_stats(storage, *operation_count);
-        }
-        "merge" => {
-            perform_merge(storage, hash_table);
-            *operation_count = 0;
-        }
-        "insert" => {
-            if parts.len() < 3 {
-                println!("Usage: insert <key> <value>");
-            } else {
-                let key = parts[1];
-                let value = parts[2..].join(" ");
-                handle_insert(storage, hash_table, key, &value);
-                *operation_count += 1;
-            }
-        }
-        "delete" => {
-            if parts.len() != 2 {
-                println!("Usage: delete <key>");
-            } else {
-                let key = parts[1];
-                handle_delete(storage, hash_table, key);
-                *operation_count += 1;
-            }
-        }
-        "get" => {
-            if parts.len() != 2 {
-                println!("Usage: get <key>");
-            } else {
-                let key = parts[1];
-                handle_get(storage, hash_table, key);
+            let mut state = store.lock().unwrap();
+            let (storage, hash_table) = &mut *state;
+            if merge_if_dead_space_due(storage, hash_table, &mut session, &mut io::stdout()) {
+                last_activity = Instant::now();
+                print!("> ");
+                io::stdout().flush().unwrap();
             }
         }
-        _ => {
-            println!("Unknown command: {}. Type 'help' for available commands.", parts[0]);
-        }
     }
-    false
 }
-
-fn show_help(merge_interval_seconds: u64) {
-    println!("Available commands:");
-    println!("  insert <key> <value>  - Insert or update a key-value pair");
-    println!("  delete <key>          - Delete a key");
-    println!("  get <key>             - Retrieve a value by key");
-    println!("  stats                 - Show storage statistics");
-    println!("  merge                 - Manually trigger merge operation");
-    println!("  help                  - Show this help message");
-    println!("  exit                  - Exit the program");
-    println!("\nAuto-merge triggers after {} seconds of inactivity.", merge_interval_seconds);
-}
-
-fn show_stats(storage: &mut Storage, operation_count: usize) {
-    println!("=== Storage Statistics ===");
-    if let Err(e) = storage.get_storage_stats() {
-        println!("Error getting storage stats: {}", e);
-    }
-    println!("Operations since last merge: {}", operation_count);
-}
-
-fn perform_merge(storage: &mut Storage, hash_table: &mut HashTable) {
-    println!("Performing merge operation...");
-    match storage.merge_inactive_files(Some(hash_table)) {
-        Ok(()) => println!("✓ Merge completed successfully"),
-        Err(e) => println!("✗ Merge failed: {}", e),
-    }
-}
-
-fn handle_insert(storage: &mut Storage, hash_table: &mut HashTable, key: &str, value: &str) {
-    match storage.write(key, value) {
-        Ok((filename, value_offset, value_size, crc)) => {
-            let file_location = crate::FileLocation::new(filename.clone(),  value_size, value_offset, crc);
-            hash_table.insert(key, file_location);
-            println!("✓ Inserted {}: {} (file: {}, value_offset: {})", key, value, filename, value_offset);
-        }
-        Err(e) => println!("✗ Failed to insert {}: {}", key, e),
-    }
-}
-
-fn handle_delete(storage: &mut Storage, hash_table: &mut HashTable, key: &str) {
-    match storage.delete(key) {
-        Ok((filename, value_offset, value_size, crc)) => {
-            let file_location = crate::FileLocation::new(filename.clone(),  value_size, value_offset, crc);
-            hash_table.insert(key, file_location);
-            println!("✓ Deleted {} (tombstone: file {}, value_offset {})", key, filename, value_offset);
-        }
-        Err(e) => println!("✗ Failed to delete {}: {}", key, e),
-    }
-}
-
-fn handle_get(storage: &mut Storage, hash_table: &mut HashTable, key: &str) {
-    match hash_table.get(key) {
-        Some(file_location) => {
-            match storage.read_value(&file_location.filename, file_location.value_offset, file_location.value_size, file_location.crc, key) {
-                Ok(value) => {
-                    println!("✓ {}: {}", key, value);
-                }
-                Err(crate::StorageError::KeyDeleted(_)) => {
-                    println!("✗ Key '{}' has been deleted", key);
-                }
-                Err(e) => {
-                    println!("✗ Error reading {}: {}", key, e);
-                }
-            }
-        }
-        None => {
-            println!("✗ Key '{}' not found", key);
-        }
-    }
-}
\ No newline at end of file
@@ -1,3 +1,5 @@
+use std::cell::Cell;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use crate::storage::HashTableTrait;
 
@@ -21,11 +23,18 @@ impl FileLocation {
 }
 
 /// Represents a key-value pair in the hash table
-/// Key is stored as String, value as FileLocation (filename + byte offset)
+/// Key is stored as String; the storage index stores a FileLocation (filename + byte offset) as the value
 #[derive(Debug, Clone)]
-pub struct Entry {
+pub struct Entry<V = FileLocation> {
     pub key: String,
-    pub value: FileLocation,
+    pub value: V,
+}
+
+/// An entry as the table holds it: keyed on raw bytes, so keys inserted through insert_bytes need not be UTF-8
+#[derive(Debug, Clone)]
+pub(crate) struct StoredEntry<V> {
+    pub(crate) key: Vec<u8>,
+    pub(crate) value: V,
 }
 
 /// Defines the collision resolution strategy for the hash table
@@ -37,119 +46,593 @@ pub enum CollisionResolution {
     QuadraticProbing,
     /// Double hashing: use second hash function for step size
     DoubleHashing,
+    /// Robin Hood hashing: linear probing where an insert takes the slot of any entry closer to its home bucket
+    /// than the new one is, and carries that entry on instead; deletes shift the rest of the cluster back
+    RobinHood,
     /// Chaining: store colliding entries in linked lists per bucket
     Chaining,
 }
 
+impl std::str::FromStr for CollisionResolution {
+    type Err = String;
+
+    /// Parses the snake_case strategy name used in config files, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "linear_probing" => Ok(CollisionResolution::LinearProbing),
+            "quadratic_probing" => Ok(CollisionResolution::QuadraticProbing),
+            "double_hashing" => Ok(CollisionResolution::DoubleHashing),
+            "robin_hood" => Ok(CollisionResolution::RobinHood),
+            "chaining" => Ok(CollisionResolution::Chaining),
+            _ => Err(format!(
+                "unknown collision method '{}' (expected linear_probing, quadratic_probing, double_hashing, robin_hood or chaining)", s
+            )),
+        }
+    }
+}
+
+/// Probe length statistics across all live entries
+/// Probe length is the number of slots (or chain positions) examined to find an entry
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProbeStats {
+    pub entries: usize,
+    pub max_probe_length: u64,
+    pub avg_probe_length: f64,
+}
+
+/// Cheap statistics maintained incrementally on insert and delete
+/// Counts and the probe-distance total are exact; max_probe_length is a high-water mark
+/// since the table was created or last rehashed and may overstate the current maximum after deletes
+/// (use probe_stats for an exact scan)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuickStats {
+    pub entries: usize,
+    pub total_probe_distance: u64,
+    pub avg_probe_length: f64,
+    pub max_probe_length: u64,
+}
+
+/// Probe counters gathered as insert and get run, for comparing collision resolution methods on a workload
+/// A probe is one slot (or chain entry) examined; a collision is a probed slot or chain entry holding another key.
+/// Counts cover calls since the table was created or its metrics were reset; reinsertions while the table grows or
+/// rehashes are not counted
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HashTableMetrics {
+    pub inserts: u64,
+    pub lookups: u64,
+    pub total_probes: u64,
+    pub collisions: u64,
+    /// Most probes taken by a single insert or get
+    pub longest_probe: u64,
+    /// Live entries per non-empty chain, computed when metrics are read; 0 unless the table uses chaining
+    pub avg_chain_length: f64,
+}
+
+/// Probes taken by one operation, see HashTableMetrics
+#[derive(Debug, Clone, Copy, Default)]
+struct ProbeCount {
+    probes: u64,
+    collisions: u64,
+}
+
+impl ProbeCount {
+    /// Counts one examined slot or chain entry, a collision if it held a different key
+    fn visit(&mut self, collision: bool) {
+        self.probes += 1;
+        self.collisions += collision as u64;
+    }
+}
+
+/// Position to resume a paginated key scan from
+/// Slots 0..size are the open addressing buckets, size..2*size the chains; chain_offset indexes within a chain.
+/// Encoded as "slot:chain_offset" so clients can hand it back verbatim
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanCursor {
+    slot: usize,
+    chain_offset: usize,
+}
+
+impl std::fmt::Display for ScanCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.slot, self.chain_offset)
+    }
+}
+
+impl std::str::FromStr for ScanCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (slot, chain_offset) = s.split_once(':').ok_or_else(|| format!("Invalid cursor: {}", s))?;
+        Ok(ScanCursor {
+            slot: slot.parse().map_err(|_| format!("Invalid cursor: {}", s))?,
+            chain_offset: chain_offset.parse().map_err(|_| format!("Invalid cursor: {}", s))?,
+        })
+    }
+}
+
+/// Load factor above which a table grows, unless configured otherwise
+pub const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.7;
+
+/// Signature of a key hash function over the key's bytes
+type HashFn = dyn Fn(&[u8]) -> u64 + Send + Sync;
+
+/// Hash function used to place keys: any `Fn(&[u8]) -> u64`, taken modulo the number of buckets
+/// A `Fn(&str) -> u64` given to with_hasher is wrapped to hash the key's text
+#[derive(Clone)]
+struct KeyHasher(Arc<HashFn>);
+
+impl std::fmt::Debug for KeyHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("KeyHasher")
+    }
+}
+
+/// Fraction of buckets that may hold deletion tombstones before the table is compacted with a full rehash
+const MAX_TOMBSTONE_FRACTION: f64 = 0.25;
+
+/// An open addressing bucket
+/// Quadratic probing and double hashing leave Deleted behind on delete so probe sequences passing through
+/// the slot stay intact; lookups probe past it and inserts may reuse it
+#[derive(Debug, Clone)]
+enum Slot<V> {
+    Empty,
+    Occupied(StoredEntry<V>),
+    Deleted,
+}
+
+impl<V> Slot<V> {
+    fn entry(&self) -> Option<&StoredEntry<V>> {
+        match self {
+            Slot::Occupied(entry) => Some(entry),
+            Slot::Empty | Slot::Deleted => None,
+        }
+    }
+
+    /// Takes the entry out, leaving the slot empty
+    fn take(&mut self) -> Option<StoredEntry<V>> {
+        std::mem::replace(self, Slot::Empty).into_entry()
+    }
+
+    fn into_entry(self) -> Option<StoredEntry<V>> {
+        match self {
+            Slot::Occupied(entry) => Some(entry),
+            Slot::Empty | Slot::Deleted => None,
+        }
+    }
+}
+
 /// Hash table implementation supporting multiple collision resolution strategies
-/// Stores byte offsets as values (u64)
+/// Values can be any cloneable type; the storage index uses HashTable<FileLocation>, the default
+/// Grows to the next prime above twice its size when an insert would push the load factor past the threshold
 #[derive(Debug, Clone)]
-pub struct HashTable {
+pub struct HashTable<V = FileLocation> {
     /// Main storage array for open addressing methods (linear, quadratic, double hashing)
-    buckets: Vec<Option<Entry>>,
+    buckets: Vec<Slot<V>>,
     /// Separate chaining storage - vector of chains for each bucket
-    chains: Vec<Vec<Entry>>,
+    chains: Vec<Vec<StoredEntry<V>>>,
     /// Number of buckets in the hash table
     size: u64,
+    /// Largest prime below size; double hashing steps by 1..=step_prime, so a prime size is visited in full
+    step_prime: u64,
     /// Which collision resolution method to use
     collision_method: CollisionResolution,
+    /// Number of live entries, kept up to date on insert and delete
+    live_count: usize,
+    /// Sum of the probe lengths of all live entries
+    total_probe_distance: u64,
+    /// Longest probe length seen since creation or the last full rehash
+    max_probe_length: u64,
+    /// Load factor (live entries / buckets) that new keys may not push the table past
+    max_load_factor: f64,
+    /// Buckets holding Slot::Deleted, cleared by a compaction rehash once they exceed MAX_TOMBSTONE_FRACTION
+    tombstones: usize,
+    /// Places keys in buckets (and derives the double hashing step); polynomial_hash unless replaced
+    hasher: KeyHasher,
+    /// Counters behind metrics(); a Cell so that get can update them through &self
+    metrics: Cell<HashTableMetrics>,
 }
 
-impl Entry {
-    /// Creates a new entry with the given key and file location
-    pub fn new(key: &str, value: FileLocation) -> Entry {
+impl<V> Entry<V> {
+    /// Creates a new entry with the given key and value
+    pub fn new(key: &str, value: V) -> Entry<V> {
         Entry { key: key.to_string(), value }
     }
 }
 
-impl HashTable {
+impl<V> StoredEntry<V> {
+    pub(crate) fn new(key: &[u8], value: V) -> StoredEntry<V> {
+        StoredEntry { key: key.to_vec(), value }
+    }
+
+    /// The entry as the public Entry type; a key that isn't valid UTF-8 gets replacement characters
+    fn into_entry(self) -> Entry<V> {
+        let key = String::from_utf8(self.key).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+        Entry { key, value: self.value }
+    }
+}
+
+impl<V: Clone> HashTable<V> {
     /// Creates a new hash table with specified size and collision resolution method
-    pub fn new(size: u64, collision_method: CollisionResolution) -> HashTable {
-        let buckets = vec![None; size as usize];
+    /// Prime sizes are recommended (see next_prime): they spread keys more evenly and, for double hashing, make every
+    /// probe sequence visit each bucket. Panics if size is 0
+    pub fn new(size: u64, collision_method: CollisionResolution) -> HashTable<V> {
+        Self::with_max_load_factor(size, collision_method, DEFAULT_MAX_LOAD_FACTOR)
+    }
+
+    /// Creates a hash table that grows once an insert would push its load factor past `max_load_factor`
+    /// Panics if size is 0 or the threshold is not a positive number
+    pub fn with_max_load_factor(size: u64, collision_method: CollisionResolution, max_load_factor: f64) -> HashTable<V> {
+        assert!(size > 0, "hash table size must be at least 1");
+        assert!(max_load_factor > 0.0, "max load factor must be positive, got {}", max_load_factor);
+        let buckets = vec![Slot::Empty; size as usize];
         let chains = vec![Vec::new(); size as usize];
-        HashTable { size, buckets, chains, collision_method }
+        HashTable { size, step_prime: prev_prime(size), buckets, chains, collision_method, live_count: 0, total_probe_distance: 0, max_probe_length: 0, max_load_factor, tombstones: 0, hasher: KeyHasher(Arc::new(polynomial_hash_bytes)), metrics: Cell::default() }
     }
 
     /// Creates a hash table using linear probing for collision resolution
-    pub fn new_linear_probing(size: u64) -> HashTable {
+    pub fn new_linear_probing(size: u64) -> HashTable<V> {
         Self::new(size, CollisionResolution::LinearProbing)
     }
 
     /// Creates a hash table using quadratic probing for collision resolution
-    pub fn new_quadratic_probing(size: u64) -> HashTable {
+    pub fn new_quadratic_probing(size: u64) -> HashTable<V> {
         Self::new(size, CollisionResolution::QuadraticProbing)
     }
 
     /// Creates a hash table using double hashing for collision resolution
-    pub fn new_double_hashing(size: u64) -> HashTable {
+    pub fn new_double_hashing(size: u64) -> HashTable<V> {
         Self::new(size, CollisionResolution::DoubleHashing)
     }
 
+    /// Creates a hash table using Robin Hood hashing for collision resolution
+    pub fn new_robin_hood(size: u64) -> HashTable<V> {
+        Self::new(size, CollisionResolution::RobinHood)
+    }
+
     /// Creates a hash table using separate chaining for collision resolution
-    pub fn new_chaining(size: u64) -> HashTable {
+    pub fn new_chaining(size: u64) -> HashTable<V> {
         Self::new(size, CollisionResolution::Chaining)
     }
 
-    /// Inserts a key with file location into the hash table
-    /// Uses the configured collision resolution method
-    pub fn insert(&mut self, key: &str, value: FileLocation) {
-        match self.collision_method {
-            CollisionResolution::Chaining => self.insert_chaining(key, value),
-            _ => self.insert_open_addressing(key, value),
+    /// Replaces the hash function, e.g. with FNV, xxHash or SipHash for key sets the default clusters badly on
+    /// Entries already in the table are rehashed into their new buckets. A key inserted as bytes that aren't
+    /// valid UTF-8 is hashed with replacement characters in place of the invalid bytes
+    pub fn with_hasher<H: Fn(&str) -> u64 + Send + Sync + 'static>(self, hasher: H) -> HashTable<V> {
+        self.with_byte_hasher(move |key| match std::str::from_utf8(key) {
+            Ok(key) => hasher(key),
+            Err(_) => hasher(&String::from_utf8_lossy(key)),
+        })
+    }
+
+    /// with_hasher for a hash function over the key's raw bytes, such as polynomial_hash_bytes
+    pub fn with_byte_hasher<H: Fn(&[u8]) -> u64 + Send + Sync + 'static>(mut self, hasher: H) -> HashTable<V> {
+        self.hasher = KeyHasher(Arc::new(hasher));
+        self.rehash();
+        self
+    }
+
+    /// Creates an empty table with the same size, collision resolution method, load factor threshold and hash function
+    pub fn empty_clone(&self) -> HashTable<V> {
+        HashTable {
+            hasher: self.hasher.clone(),
+            ..Self::with_max_load_factor(self.size, self.collision_method.clone(), self.max_load_factor)
+        }
+    }
+
+    /// Hashes a key with the table's hash function
+    fn hash(&self, key: &[u8]) -> u64 {
+        (self.hasher.0)(key)
+    }
+
+    /// Number of live entries across buckets and chains - O(1), kept up to date on insert and delete
+    pub fn len(&self) -> usize {
+        self.live_count
+    }
+
+    /// True if the table holds no live entries; deletion tombstones don't count
+    pub fn is_empty(&self) -> bool {
+        self.live_count == 0
+    }
+
+    /// Number of buckets; changes when the table grows
+    pub fn capacity(&self) -> u64 {
+        self.size
+    }
+
+    /// Live entries per bucket
+    pub fn load_factor(&self) -> f64 {
+        self.live_count as f64 / self.size as f64
+    }
+
+    /// Load factor an insert may not push the table past without it growing first
+    pub fn max_load_factor(&self) -> f64 {
+        self.max_load_factor
+    }
+
+    /// Buckets currently holding a deletion tombstone (quadratic probing and double hashing only)
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Smallest number of buckets that would keep the current entries under a load factor of `target` (at least 1),
+    /// so a table at exactly its max load factor gets room for the key that would otherwise make it grow.
+    /// Panics if `target` is not a positive number
+    pub fn capacity_for_load(&self, target: f64) -> u64 {
+        assert!(target > 0.0, "target load factor must be positive, got {}", target);
+        let fits = |capacity: u64| (self.live_count as f64) < target * capacity as f64;
+        let mut capacity = ((self.live_count as f64 / target).floor() as u64).max(1);
+        // Correct for rounding in the division in either direction
+        while !fits(capacity) {
+            capacity += 1;
+        }
+        while capacity > 1 && fits(capacity - 1) {
+            capacity -= 1;
+        }
+        capacity
+    }
+
+    /// Inserts a key with its value into the hash table
+    /// Uses the configured collision resolution method. A new key grows the table first if it would
+    /// push the load factor past the threshold, or if open addressing finds no free slot for it
+    pub fn insert(&mut self, key: &str, value: V) {
+        self.insert_bytes(key.as_bytes(), value);
+    }
+
+    /// Inserts a value under a key of raw bytes, which need not be UTF-8; see insert
+    pub fn insert_bytes(&mut self, key: &[u8], value: V) {
+        let count = self.insert_entry(key, value);
+        self.record_probes(count, |metrics| metrics.inserts += 1);
+    }
+
+    /// Does the work of insert without updating the metrics, returning the probes taken
+    /// (including any attempt that found no free slot before the table grew)
+    fn insert_entry(&mut self, key: &[u8], value: V) -> ProbeCount {
+        if self.find(key).0.is_none() && (self.live_count + 1) as f64 > self.max_load_factor * self.size as f64 {
+            self.grow();
+        }
+        let mut value = value;
+        let mut count = ProbeCount::default();
+        loop {
+            let rejected = match self.collision_method {
+                CollisionResolution::Chaining => {
+                    self.insert_chaining(key, value, &mut count);
+                    return count;
+                }
+                CollisionResolution::RobinHood => self.insert_robin_hood(key, value, &mut count),
+                _ => self.insert_open_addressing(key, value, &mut count),
+            };
+            match rejected {
+                Some(rejected) => {
+                    self.grow();
+                    value = rejected;
+                }
+                None => return count,
+            }
+        }
+    }
+
+    /// Inserts only if the key is not in the table yet; returns false, leaving the table unchanged, if it is
+    pub fn insert_if_absent(&mut self, key: &str, value: V) -> bool {
+        if self.find(key.as_bytes()).0.is_some() {
+            return false;
+        }
+        self.insert(key, value);
+        true
+    }
+
+    /// Overwrites the value of a key already in the table; returns false, inserting nothing, if it is absent
+    pub fn replace(&mut self, key: &str, value: V) -> bool {
+        if self.find(key.as_bytes()).0.is_none() {
+            return false;
+        }
+        self.insert(key, value);
+        true
+    }
+
+    /// Rehashes every entry into a table about twice as large, sized to the next prime above 2x
+    fn grow(&mut self) {
+        let new_size = next_prime(self.size * 2 + 1);
+        let mut entries: Vec<StoredEntry<V>> = self.buckets.iter_mut().filter_map(Slot::take).collect();
+        for chain in self.chains.drain(..) {
+            entries.extend(chain);
+        }
+        self.size = new_size;
+        self.step_prime = prev_prime(new_size);
+        self.buckets = vec![Slot::Empty; new_size as usize];
+        self.chains = vec![Vec::new(); new_size as usize];
+        self.reset_stats();
+
+        for entry in entries {
+            self.insert_entry(&entry.key, entry.value);
         }
     }
 
     /// Insert using separate chaining - each bucket contains a vector of entries
-    fn insert_chaining(&mut self, key: &str, value: FileLocation) {
-        let index = (get_hash(key) % self.size) as usize;
+    fn insert_chaining(&mut self, key: &[u8], value: V, count: &mut ProbeCount) {
+        let index = (self.hash(key) % self.size) as usize;
         let chain = &mut self.chains[index];
         
         // Check if key already exists in chain and update it
         for entry in chain.iter_mut() {
-            if entry.key == key {
+            let found = entry.key == key;
+            count.visit(!found);
+            if found {
                 entry.value = value;
                 return;
             }
         }
         
         // Key doesn't exist, add new entry to the chain
-        chain.push(Entry::new(key, value));
+        count.visit(false);
+        chain.push(StoredEntry::new(key, value));
+        let probe_length = chain.len() as u64;
+        self.record_insert(probe_length);
     }
 
     /// Insert using open addressing (linear, quadratic, or double hashing)
-    fn insert_open_addressing(&mut self, key: &str, value: FileLocation) {
-        let base_index = (get_hash(key) % self.size) as usize;
-        let mut attempt = 0;
+    /// A new key goes into the first deleted slot on its probe sequence, if any, once probing has shown the key is absent.
+    /// Hands the value back if the probe sequence has no free slot
+    fn insert_open_addressing(&mut self, key: &[u8], value: V, count: &mut ProbeCount) -> Option<V> {
+        let base_index = (self.hash(key) % self.size) as usize;
+        let mut first_deleted = None;
         
-        loop {
+        for attempt in 0..self.size {
             let index = self.get_probe_index(base_index, attempt, key);
+            count.visit(matches!(&self.buckets[index], Slot::Occupied(entry) if entry.key != key));
             
             match &mut self.buckets[index] {
-                None => {
-                    // Found empty slot, insert here
-                    self.buckets[index] = Some(Entry::new(key, value));
-                    return;
+                Slot::Empty => {
+                    // Found empty slot: the key is absent, insert at the earliest free position
+                    let (index, attempt) = first_deleted.unwrap_or((index, attempt));
+                    self.place(index, attempt, StoredEntry::new(key, value));
+                    return None;
                 }
-                Some(entry) => {
+                Slot::Deleted => {
+                    first_deleted.get_or_insert((index, attempt));
+                }
+                Slot::Occupied(entry) => {
                     if entry.key == key {
                         // Key already exists, update value
                         entry.value = value;
-                        return;
+                        return None;
                     }
                     // Collision occurred, try next probe position
-                    attempt += 1;
-                    if attempt >= self.size {
-                        panic!("Hash table is full");
+                }
+            }
+        }
+        match first_deleted {
+            Some((index, attempt)) => {
+                self.place(index, attempt, StoredEntry::new(key, value));
+                None
+            }
+            None => Some(value),
+        }
+    }
+
+    /// Insert using Robin Hood hashing: probe linearly, and wherever the entry being placed is further from its
+    /// home bucket than the slot's occupant, swap them and carry on placing the occupant. Keeps every probe
+    /// length close to the average, so the longest lookup stays short even at high load factors.
+    /// Hands the value back if the table is full
+    fn insert_robin_hood(&mut self, key: &[u8], value: V, count: &mut ProbeCount) -> Option<V> {
+        let size = self.size as usize;
+        let mut index = (self.hash(key) % self.size) as usize;
+        let mut carried = StoredEntry::new(key, value);
+        // Probe distance of the carried entry at `index`
+        let mut distance = 0;
+
+        for _ in 0..size {
+            count.visit(matches!(&self.buckets[index], Slot::Occupied(entry) if entry.key != key));
+            match &mut self.buckets[index] {
+                Slot::Empty => {
+                    self.buckets[index] = Slot::Occupied(carried);
+                    self.record_insert(distance + 1);
+                    return None;
+                }
+                Slot::Occupied(entry) if entry.key == key => {
+                    // Only reachable before any swap, since the key is in the table at most once
+                    entry.value = carried.value;
+                    return None;
+                }
+                Slot::Occupied(_) => {
+                    let occupant_distance = self.home_distance(index);
+                    if occupant_distance < distance {
+                        // The occupant is closer to home than the carried entry, so it gives up its slot
+                        if self.live_count == size {
+                            return Some(carried.value);
+                        }
+                        let Slot::Occupied(occupant) = std::mem::replace(&mut self.buckets[index], Slot::Occupied(carried)) else {
+                            unreachable!("slot {} was checked to be occupied", index);
+                        };
+                        self.total_probe_distance = self.total_probe_distance + distance - occupant_distance;
+                        self.max_probe_length = self.max_probe_length.max(distance + 1);
+                        carried = occupant;
+                        distance = occupant_distance;
                     }
                 }
+                Slot::Deleted => unreachable!("Robin Hood deletes shift entries back instead of leaving tombstones"),
             }
+            index = (index + 1) % size;
+            distance += 1;
+        }
+        Some(carried.value)
+    }
+
+    /// How far the entry in an occupied slot sits from its home bucket, in slots
+    fn home_distance(&self, index: usize) -> u64 {
+        let entry = self.buckets[index].entry().expect("home_distance is only asked about occupied slots");
+        let home = (self.hash(&entry.key) % self.size) as usize;
+        ((index + self.size as usize - home) % self.size as usize) as u64
+    }
+
+    /// Stores a new entry in a free slot reached at probe `attempt`
+    fn place(&mut self, index: usize, attempt: u64, entry: StoredEntry<V>) {
+        if let Slot::Deleted = self.buckets[index] {
+            self.tombstones -= 1;
+        }
+        self.buckets[index] = Slot::Occupied(entry);
+        self.record_insert(attempt + 1);
+    }
+
+    /// Updates the incremental stats for a newly added entry
+    fn record_insert(&mut self, probe_length: u64) {
+        self.live_count += 1;
+        self.total_probe_distance += probe_length;
+        self.max_probe_length = self.max_probe_length.max(probe_length);
+    }
+
+    /// Updates the incremental stats for an entry taken out of the table
+    fn record_removal(&mut self, probe_length: u64) {
+        self.live_count -= 1;
+        self.total_probe_distance -= probe_length;
+    }
+
+    /// Adds one operation's probes to the metrics; `count_operation` bumps the insert or lookup counter
+    fn record_probes(&self, count: ProbeCount, count_operation: impl FnOnce(&mut HashTableMetrics)) {
+        let mut metrics = self.metrics.get();
+        count_operation(&mut metrics);
+        metrics.total_probes += count.probes;
+        metrics.collisions += count.collisions;
+        metrics.longest_probe = metrics.longest_probe.max(count.probes);
+        self.metrics.set(metrics);
+    }
+
+    /// Probe counters for the inserts and gets so far, see HashTableMetrics
+    pub fn metrics(&self) -> HashTableMetrics {
+        let mut metrics = self.metrics.get();
+        if let CollisionResolution::Chaining = self.collision_method {
+            let chains = self.chains.iter().filter(|chain| !chain.is_empty()).count();
+            if chains > 0 {
+                metrics.avg_chain_length = self.live_count as f64 / chains as f64;
+            }
+        }
+        metrics
+    }
+
+    /// Zeroes the metrics counters, e.g. to measure one workload after the index was loaded
+    pub fn reset_metrics(&self) {
+        self.metrics.set(HashTableMetrics::default());
+    }
+
+    /// Returns counts and probe totals without scanning the table - O(1)
+    /// See QuickStats for which fields are exact
+    pub fn quick_stats(&self) -> QuickStats {
+        let avg_probe_length = if self.live_count == 0 {
+            0.0
+        } else {
+            self.total_probe_distance as f64 / self.live_count as f64
+        };
+        QuickStats {
+            entries: self.live_count,
+            total_probe_distance: self.total_probe_distance,
+            avg_probe_length,
+            max_probe_length: self.max_probe_length,
         }
     }
 
     /// Calculates the next probe index based on collision resolution method
-    fn get_probe_index(&self, base_index: usize, attempt: u64, key: &str) -> usize {
+    fn get_probe_index(&self, base_index: usize, attempt: u64, key: &[u8]) -> usize {
         match self.collision_method {
-            CollisionResolution::LinearProbing => {
+            CollisionResolution::LinearProbing | CollisionResolution::RobinHood => {
                 // Linear probing: check next slot sequentially
                 (base_index + attempt as usize) % (self.size as usize)
             }
@@ -159,9 +642,9 @@ impl HashTable {
             }
             CollisionResolution::DoubleHashing => {
                 // Double hashing: derive second hash from first hash
-                let hash1 = get_hash(key);
-                let hash2 = 7 - (hash1 % 7); // Ensures non-zero step size (1-7)
-                (base_index + (attempt * hash2 as u64) as usize) % (self.size as usize)
+                let hash1 = self.hash(key);
+                let hash2 = self.step_prime - (hash1 % self.step_prime); // Non-zero step size below the table size
+                (base_index + (attempt * hash2) as usize) % (self.size as usize)
             }
             CollisionResolution::Chaining => base_index, // Not used for chaining
         }
@@ -170,71 +653,146 @@ impl HashTable {
     /// Removes a key-value pair from the hash table
     /// Returns true if the key was found and deleted, false otherwise
     pub fn delete(&mut self, key: &str) -> bool {
+        self.delete_bytes(key.as_bytes())
+    }
+
+    /// Removes a key of raw bytes; see delete
+    pub fn delete_bytes(&mut self, key: &[u8]) -> bool {
         match self.collision_method {
             CollisionResolution::Chaining => self.delete_chaining(key),
+            CollisionResolution::RobinHood => self.delete_robin_hood(key),
             _ => self.delete_open_addressing(key),
         }
     }
 
     /// Delete from separate chaining - remove from the appropriate chain
-    fn delete_chaining(&mut self, key: &str) -> bool {
-        let index = (get_hash(key) % self.size) as usize;
+    fn delete_chaining(&mut self, key: &[u8]) -> bool {
+        let index = (self.hash(key) % self.size) as usize;
         let chain = &mut self.chains[index];
         
         // Search through the chain for the key
         for i in 0..chain.len() {
             if chain[i].key == key {
                 chain.remove(i);
+                // Every entry after the removed one moves one position closer to the head
+                let shifted = (chain.len() - i) as u64;
+                self.record_removal(i as u64 + 1);
+                self.total_probe_distance -= shifted;
                 return true;
             }
         }
         false // Key not found
     }
 
-    /// Delete from open addressing - requires rehashing to maintain probe sequences
-    fn delete_open_addressing(&mut self, key: &str) -> bool {
-        let base_index = (get_hash(key) % self.size) as usize;
-        let mut attempt = 0;
+    /// Delete from open addressing
+    /// Linear probing shifts the rest of the cluster back; quadratic probing and double hashing leave a tombstone,
+    /// compacting the table with a full rehash once tombstones exceed MAX_TOMBSTONE_FRACTION of the buckets
+    fn delete_open_addressing(&mut self, key: &[u8]) -> bool {
+        let base_index = (self.hash(key) % self.size) as usize;
         
-        loop {
+        for attempt in 0..self.size {
             let index = self.get_probe_index(base_index, attempt, key);
             
             match &self.buckets[index] {
-                None => return false, // Key not found (hit empty slot)
-                Some(entry) => {
-                    if entry.key == key {
-                        // Found the key, delete it
-                        self.buckets[index] = None;
+                Slot::Empty => return false, // Key not found (hit empty slot)
+                Slot::Occupied(entry) if entry.key == key => {
+                    self.record_removal(attempt + 1);
+                    if let CollisionResolution::LinearProbing = self.collision_method {
+                        self.buckets[index] = Slot::Empty;
                         // Rehash entries that might be affected by this deletion
-                        self.rehash_cluster_generic(index);
-                        return true;
-                    }
-                    attempt += 1;
-                    if attempt >= self.size {
-                        return false; // Searched entire table
+                        self.rehash_cluster_linear(index);
+                    } else {
+                        self.buckets[index] = Slot::Deleted;
+                        self.tombstones += 1;
+                        if self.tombstones as f64 > MAX_TOMBSTONE_FRACTION * self.size as f64 {
+                            self.rehash();
+                        }
                     }
+                    return true;
                 }
+                // Keep probing past deleted slots and other keys
+                Slot::Occupied(_) | Slot::Deleted => {}
             }
         }
+        false // Searched entire table
     }
 
-    /// Retrieves the file location for a given key
-    /// Returns Some(file_location) if found, None if key doesn't exist
-    pub fn get(&self, key: &str) -> Option<&FileLocation> {
-        match self.collision_method {
-            CollisionResolution::Chaining => self.get_chaining(key),
-            _ => self.get_open_addressing(key),
+    /// Delete with backward shift: every following entry in the cluster that isn't in its home bucket moves back
+    /// one slot, which keeps the Robin Hood ordering without leaving tombstones
+    fn delete_robin_hood(&mut self, key: &[u8]) -> bool {
+        let size = self.size as usize;
+        let Some(mut index) = self.robin_hood_index(key, &mut ProbeCount::default()) else {
+            return false;
+        };
+        let distance = self.home_distance(index);
+        self.buckets[index] = Slot::Empty;
+        self.record_removal(distance + 1);
+
+        loop {
+            let next = (index + 1) % size;
+            if self.buckets[next].entry().is_none() || self.home_distance(next) == 0 {
+                return true;
+            }
+            self.buckets.swap(index, next);
+            self.total_probe_distance -= 1;
+            index = next;
         }
     }
 
+    /// Slot holding `key` under Robin Hood hashing. The search stops early at an entry closer to its home bucket
+    /// than the key would be there, since an insert of the key would have displaced that entry
+    fn robin_hood_index(&self, key: &[u8], count: &mut ProbeCount) -> Option<usize> {
+        let size = self.size as usize;
+        let mut index = (self.hash(key) % self.size) as usize;
+        for distance in 0..self.size {
+            count.visit(matches!(&self.buckets[index], Slot::Occupied(entry) if entry.key != key));
+            match self.buckets[index].entry() {
+                None => return None,
+                Some(entry) if entry.key == key => return Some(index),
+                Some(_) if self.home_distance(index) < distance => return None,
+                Some(_) => {}
+            }
+            index = (index + 1) % size;
+        }
+        None
+    }
+
+    /// Retrieves the value for a given key
+    /// Returns Some(value) if found, None if key doesn't exist
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.get_bytes(key.as_bytes())
+    }
+
+    /// Looks up a key of raw bytes; see get
+    pub fn get_bytes(&self, key: &[u8]) -> Option<&V> {
+        let (value, count) = self.find(key);
+        self.record_probes(count, |metrics| metrics.lookups += 1);
+        value
+    }
+
+    /// Looks a key up without updating the metrics, returning the probes taken
+    fn find(&self, key: &[u8]) -> (Option<&V>, ProbeCount) {
+        let mut count = ProbeCount::default();
+        let value = match self.collision_method {
+            CollisionResolution::Chaining => self.get_chaining(key, &mut count),
+            CollisionResolution::RobinHood => self.robin_hood_index(key, &mut count)
+                .and_then(|index| self.buckets[index].entry())
+                .map(|entry| &entry.value),
+            _ => self.get_open_addressing(key, &mut count),
+        };
+        (value, count)
+    }
+
     /// Get from separate chaining - search through the appropriate chain
-    fn get_chaining(&self, key: &str) -> Option<&FileLocation> {
-        let index = (get_hash(key) % self.size) as usize;
+    fn get_chaining(&self, key: &[u8], count: &mut ProbeCount) -> Option<&V> {
+        let index = (self.hash(key) % self.size) as usize;
         let chain = &self.chains[index];
         
         // Linear search through the chain
         for entry in chain {
-            if entry.key == key {
+            let found = entry.key == key;
+            count.visit(!found);
+            if found {
                 return Some(&entry.value);
             }
         }
@@ -242,75 +800,251 @@ impl HashTable {
     }
 
     /// Get from open addressing - follow probe sequence until found or empty slot
-    fn get_open_addressing(&self, key: &str) -> Option<&FileLocation> {
-        let base_index = (get_hash(key) % self.size) as usize;
-        let mut attempt = 0;
+    fn get_open_addressing(&self, key: &[u8], count: &mut ProbeCount) -> Option<&V> {
+        let base_index = (self.hash(key) % self.size) as usize;
         
-        loop {
+        for attempt in 0..self.size {
             let index = self.get_probe_index(base_index, attempt, key);
+            count.visit(matches!(&self.buckets[index], Slot::Occupied(entry) if entry.key != key));
             
             match &self.buckets[index] {
-                None => return None, // Hit empty slot, key not found
-                Some(entry) => {
-                    if entry.key == key {
-                        return Some(&entry.value); // Found the key
+                Slot::Empty => return None, // Hit empty slot, key not found
+                Slot::Occupied(entry) if entry.key == key => return Some(&entry.value), // Found the key
+                // Continue probing past deleted slots and other keys
+                Slot::Occupied(_) | Slot::Deleted => {}
+            }
+        }
+        None // Searched entire table
+    }
+
+    /// Whether the key is in the table, following the same probe sequence or chain as get
+    /// Deleted slots are skipped, so a key removed under open addressing is absent
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Walks every live entry in both the open addressing buckets and the chains
+    pub(crate) fn entries(&self) -> impl Iterator<Item = &StoredEntry<V>> {
+        self.buckets.iter().filter_map(Slot::entry).chain(self.chains.iter().flatten())
+    }
+
+    /// Iterates over every key currently in the table, in bucket order, whatever the collision method
+    /// A key inserted through insert_bytes that isn't valid UTF-8 has no &str form and is left out;
+    /// non_utf8_key_count says how many were, and key_bytes lists every key
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries().filter_map(|entry| std::str::from_utf8(&entry.key).ok())
+    }
+
+    /// Iterates over every key as raw bytes, in bucket order, non-UTF-8 keys included
+    pub fn key_bytes(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries().map(|entry| entry.key.as_slice())
+    }
+
+    /// Number of keys that keys(), iter(), scan_page and entries_for_file leave out for not being valid UTF-8
+    /// Always 0 unless keys were inserted through insert_bytes. Walks the whole table, so this is O(n)
+    pub fn non_utf8_key_count(&self) -> usize {
+        self.entries().filter(|entry| std::str::from_utf8(&entry.key).is_err()).count()
+    }
+
+    /// Iterates over every key and its value, in bucket order; chained entries come in chain order
+    /// Like keys(), leaves out keys that aren't valid UTF-8; iter_bytes covers every entry
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &V)> {
+        self.entries().filter_map(|entry| Some((std::str::from_utf8(&entry.key).ok()?, &entry.value)))
+    }
+
+    /// iter with every key as raw bytes, non-UTF-8 keys included
+    pub fn iter_bytes(&self) -> impl Iterator<Item = (&[u8], &V)> {
+        self.entries().map(|entry| (entry.key.as_slice(), &entry.value))
+    }
+
+    /// Consumes the table and returns every live entry, in bucket order like iter()
+    /// Moves the values out instead of cloning them, for when the table itself is no longer needed. Unlike iter(),
+    /// keeps keys that aren't valid UTF-8, with replacement characters in place of the invalid bytes;
+    /// into_byte_entries hands them back exactly
+    pub fn into_entries(self) -> Vec<Entry<V>> {
+        self.into_stored_entries().map(StoredEntry::into_entry).collect()
+    }
+
+    /// into_entries with every key as raw bytes
+    pub fn into_byte_entries(self) -> Vec<(Vec<u8>, V)> {
+        self.into_stored_entries().map(|entry| (entry.key, entry.value)).collect()
+    }
+
+    fn into_stored_entries(self) -> impl Iterator<Item = StoredEntry<V>> {
+        self.buckets.into_iter()
+            .filter_map(Slot::into_entry)
+            .chain(self.chains.into_iter().flatten())
+    }
+
+    /// Returns up to `limit` keys starting at `cursor`, and the cursor to resume from (None once the scan is done)
+    /// Start with ScanCursor::default(). Without mutation between calls every key is returned exactly once;
+    /// inserts or deletes in between may cause keys to be skipped or repeated. Like keys(), leaves out keys that
+    /// aren't valid UTF-8 (see non_utf8_key_count)
+    pub fn scan_page(&self, cursor: ScanCursor, limit: usize) -> (Vec<&str>, Option<ScanCursor>) {
+        let size = self.size as usize;
+        let mut keys = Vec::new();
+        let mut slot = cursor.slot;
+        let mut chain_offset = cursor.chain_offset;
+
+        while slot < 2 * size {
+            if slot < size {
+                if let Some(key) = self.buckets[slot].entry().and_then(|entry| std::str::from_utf8(&entry.key).ok()) {
+                    if keys.len() == limit {
+                        return (keys, Some(ScanCursor { slot, chain_offset: 0 }));
                     }
-                    // Continue probing
-                    attempt += 1;
-                    if attempt >= self.size {
-                        return None; // Searched entire table
+                    keys.push(key);
+                }
+            } else {
+                let chain = &self.chains[slot - size];
+                while chain_offset < chain.len() {
+                    let Ok(key) = std::str::from_utf8(&chain[chain_offset].key) else {
+                        chain_offset += 1;
+                        continue;
+                    };
+                    if keys.len() == limit {
+                        return (keys, Some(ScanCursor { slot, chain_offset }));
                     }
+                    keys.push(key);
+                    chain_offset += 1;
                 }
+                chain_offset = 0;
             }
+            slot += 1;
         }
+        (keys, None)
     }
 
-    /// Rehashes entries after deletion to maintain probe sequence integrity
-    fn rehash_cluster_generic(&mut self, deleted_index: usize) {
-        match self.collision_method {
-            CollisionResolution::LinearProbing => self.rehash_cluster_linear(deleted_index),
-            _ => self.rehash_cluster_general(deleted_index),
+    /// Returns every key stored more than once across buckets and chains, sorted
+    /// A correct table never has any; a non-empty result means probing or deletion left a shadowed copy.
+    /// Keys that aren't valid UTF-8 are shown with replacement characters
+    pub fn find_duplicates(&self) -> Vec<String> {
+        let mut counts: std::collections::HashMap<&[u8], usize> = std::collections::HashMap::new();
+        for entry in self.entries() {
+            *counts.entry(entry.key.as_slice()).or_insert(0) += 1;
+        }
+        let mut duplicates: Vec<String> = counts.into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(key, _)| String::from_utf8_lossy(key).into_owned())
+            .collect();
+        duplicates.sort();
+        duplicates
+    }
+
+    /// Stores an entry without checking whether its key is already present, corrupting the table on purpose
+    /// Exists only so tests and the simulate-duplicate command can exercise find_duplicates; stats are not updated.
+    /// Returns false if an open addressing table has no free bucket
+    #[doc(hidden)]
+    pub fn insert_raw_duplicate(&mut self, entry: Entry<V>) -> bool {
+        let entry = StoredEntry::new(entry.key.as_bytes(), entry.value);
+        let base_index = (self.hash(&entry.key) % self.size) as usize;
+        if let CollisionResolution::Chaining = self.collision_method {
+            self.chains[base_index].push(entry);
+            return true;
+        }
+        let size = self.size as usize;
+        match (0..size).map(|step| (base_index + step) % size).find(|&index| matches!(self.buckets[index], Slot::Empty)) {
+            Some(index) => {
+                self.buckets[index] = Slot::Occupied(entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the number of probes needed to reach a key, or None if it isn't present
+    fn probe_length(&self, key: &[u8]) -> Option<u64> {
+        let base_index = (self.hash(key) % self.size) as usize;
+        if let CollisionResolution::Chaining = self.collision_method {
+            return self.chains[base_index]
+                .iter()
+                .position(|entry| entry.key == key)
+                .map(|position| position as u64 + 1);
+        }
+
+        for attempt in 0..self.size {
+            let index = self.get_probe_index(base_index, attempt, key);
+            match &self.buckets[index] {
+                Slot::Empty => return None,
+                Slot::Occupied(entry) if entry.key == key => return Some(attempt + 1),
+                Slot::Occupied(_) | Slot::Deleted => {}
+            }
+        }
+        None
+    }
+
+    /// Computes max and average probe length over every live entry
+    /// Walks the whole table, so this is O(n) per call
+    pub fn probe_stats(&self) -> ProbeStats {
+        let keys: Vec<&[u8]> = self.key_bytes().collect();
+
+        let mut stats = ProbeStats { entries: keys.len(), ..ProbeStats::default() };
+        let mut total = 0u64;
+        for key in keys {
+            let length = self.probe_length(key).unwrap_or(0);
+            total += length;
+            stats.max_probe_length = stats.max_probe_length.max(length);
+        }
+        if stats.entries > 0 {
+            stats.avg_probe_length = total as f64 / stats.entries as f64;
         }
+        stats
+    }
+
+    /// Removes every entry and re-inserts it, rebuilding probe sequences and chains from scratch
+    /// Also clears every deletion tombstone
+    pub fn rehash(&mut self) {
+        let mut entries: Vec<StoredEntry<V>> = self.buckets.iter_mut().filter_map(Slot::take).collect();
+        for chain in self.chains.iter_mut() {
+            entries.append(chain);
+        }
+        self.reset_stats();
+
+        for entry in entries {
+            self.insert_entry(&entry.key, entry.value);
+        }
+    }
+
+    /// Removes every entry and deletion tombstone, keeping the bucket count, collision method, load factor threshold
+    /// and hash function. The bucket and chain vectors keep their allocations; metrics are left as they are
+    pub fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(|slot| *slot = Slot::Empty);
+        self.chains.iter_mut().for_each(Vec::clear);
+        self.reset_stats();
+    }
+
+    /// Zeroes the incremental stats after every entry (and tombstone) has been taken out for reinsertion
+    fn reset_stats(&mut self) {
+        self.tombstones = 0;
+        self.live_count = 0;
+        self.total_probe_distance = 0;
+        self.max_probe_length = 0;
     }
 
     /// Optimized rehashing for linear probing - only rehash affected cluster
     fn rehash_cluster_linear(&mut self, deleted_index: usize) {
         let mut index = (deleted_index + 1) % (self.size as usize);
+        // The empty slot moves along the cluster as entries are shifted back into it
+        let mut gap = deleted_index;
         
         // Continue until we hit an empty slot (end of cluster)
         while let Some(entry) = self.buckets[index].take() {
-            let original_index = (get_hash(&entry.key) % self.size) as usize;
+            let original_index = (self.hash(&entry.key) % self.size) as usize;
             
             // Check if this entry should be moved to fill the gap
-            if self.should_move_entry(original_index, deleted_index, index) {
-                self.insert(&entry.key, entry.value);
+            if self.should_move_entry(original_index, gap, index) {
+                let size = self.size as usize;
+                self.record_removal(((index + size - original_index) % size) as u64 + 1);
+                self.insert_entry(&entry.key, entry.value);
+                gap = index;
             } else {
                 // Entry stays in current position
-                self.buckets[index] = Some(entry);
+                self.buckets[index] = Slot::Occupied(entry);
             }
             
             index = (index + 1) % (self.size as usize);
         }
     }
 
-    /// General rehashing for quadratic and double hashing - rehash entire table
-    /// This is simpler but less efficient than cluster-specific rehashing
-    fn rehash_cluster_general(&mut self, _deleted_index: usize) {
-        let mut entries_to_reinsert = Vec::new();
-        
-        // Extract all entries from the table
-        for i in 0..self.size as usize {
-            if let Some(entry) = self.buckets[i].take() {
-                entries_to_reinsert.push(entry);
-            }
-        }
-        
-        // Reinsert all entries (they'll find their correct positions)
-        for entry in entries_to_reinsert {
-            self.insert(&entry.key, entry.value);
-        }
-    }
-
     /// Determines if an entry should be moved to fill a deleted slot in linear probing
     /// This handles the wraparound case correctly
     fn should_move_entry(&self, original_index: usize, deleted_index: usize, current_index: usize) -> bool {
@@ -324,19 +1058,62 @@ impl HashTable {
     }
 }
 
-/// Primary hash function using polynomial rolling hash with multiplier 31
-/// This is a simple but effective hash function for strings
-fn get_hash(key: &str) -> u64 {
+impl HashTable<FileLocation> {
+    /// Returns every entry whose location points into the given data file
+    /// Used to drain a file (rewrite its live values elsewhere) before removing it. Like keys(), leaves out keys
+    /// that aren't valid UTF-8 (see non_utf8_key_count); Storage only ever indexes UTF-8 keys
+    pub fn entries_for_file(&self, filename: &str) -> Vec<(&str, &FileLocation)> {
+        self.entries()
+            .filter(|entry| entry.value.filename == filename)
+            .filter_map(|entry| Some((std::str::from_utf8(&entry.key).ok()?, &entry.value)))
+            .collect()
+    }
+}
+
+/// Smallest prime greater than or equal to n (at least 2)
+pub fn next_prime(n: u64) -> u64 {
+    (n.max(2)..).find(|&candidate| is_prime(candidate)).expect("a prime exists above every u64 in range")
+}
+
+/// Largest prime below n, or 1 when there is none (n <= 2)
+fn prev_prime(n: u64) -> u64 {
+    (2..n).rev().find(|&candidate| is_prime(candidate)).unwrap_or(1)
+}
+
+fn is_prime(n: u64) -> bool {
+    n >= 2 && (2..).take_while(|divisor| divisor * divisor <= n).all(|divisor| !n.is_multiple_of(divisor))
+}
+
+/// Default hash function: polynomial rolling hash with multiplier 31
+/// Simple and fast, but similar short keys cluster in neighbouring buckets
+pub fn polynomial_hash(key: &str) -> u64 {
+    polynomial_hash_bytes(key.as_bytes())
+}
+
+/// polynomial_hash over raw bytes, which is what the table runs on by default
+pub fn polynomial_hash_bytes(key: &[u8]) -> u64 {
     let mut hash = 0u64;
-    for byte in key.bytes() {
+    for &byte in key {
         // Polynomial rolling hash: hash = hash * 31 + byte
         hash = hash.wrapping_mul(31).wrapping_add(byte as u64);
     }
     hash
 }
 
+/// 64-bit FNV-1a, an alternative to polynomial_hash that spreads similar keys more evenly
+pub fn fnv1a_hash(key: &str) -> u64 {
+    fnv1a_hash_bytes(key.as_bytes())
+}
+
+/// fnv1a_hash over raw bytes, for with_byte_hasher
+pub fn fnv1a_hash_bytes(key: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    key.iter().fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
 /// Implementation of HashTableTrait for merge operations
-impl HashTableTrait for HashTable {
+impl HashTableTrait for HashTable<FileLocation> {
     fn delete(&mut self, key: &str) -> bool {
         self.delete(key)
     }
@@ -344,6 +1121,18 @@ impl HashTableTrait for HashTable {
     fn insert(&mut self, key: &str, location: FileLocation) {
         self.insert(key, location);
     }
+
+    fn get(&self, key: &str) -> Option<&FileLocation> {
+        self.get(key)
+    }
+
+    fn peek(&self, key: &str) -> Option<&FileLocation> {
+        self.find(key.as_bytes()).0
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(self.keys())
+    }
 }
 
 
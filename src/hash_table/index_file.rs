@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fs::{read, rename, write};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use crc::{Crc, CRC_32_ISO_HDLC};
+use crate::hash_table::{FileLocation, HashTable};
+use crate::hash_table::hash_table_impl::StoredEntry;
+
+/// Name of the index file Storage directories keep the saved index in
+pub const INDEX_FILENAME: &str = ".index";
+
+/// First bytes of every index file
+const INDEX_MAGIC: &[u8; 4] = b"KVIX";
+
+/// Layout written by save_to; any other version is rejected by load_from
+pub const INDEX_FORMAT_VERSION: u32 = 1;
+
+/// Size of an entry before its key: key_size, file number, value_size, value_offset, crc and timestamp
+const INDEX_ENTRY_HEADER_SIZE: usize = 30;
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Each data file's name and length, as recorded with a saved index
+type DataFiles = Vec<(String, u64)>;
+
+impl HashTable<FileLocation> {
+    /// Saves every entry to an index file at `path`, so a restart can load the index instead of replaying the data files
+    /// `data_files` lists each data file's name and length; entries refer to their file by its position in it, and
+    /// load_from hands it back so the caller can tell whether the files changed since. Layout, all little endian:
+    /// [magic "KVIX"][version:4][file_count:4], per file [name_size:2][length:8][name], then [entry_count:8],
+    /// per entry [key_size:4][file:4][value_size:4][value_offset:8][crc:2][timestamp:8][key], then a CRC-32 of
+    /// everything before it. Written to a temp file and renamed, so a crash never leaves a half-written index
+    pub fn save_to<P: AsRef<Path>>(&self, path: P, data_files: &[(String, u64)]) -> std::io::Result<()> {
+        let file_numbers: HashMap<&str, u32> = data_files.iter()
+            .enumerate()
+            .map(|(number, (filename, _))| (filename.as_str(), number as u32))
+            .collect();
+
+        let mut content = Vec::new();
+        content.extend_from_slice(INDEX_MAGIC);
+        content.extend_from_slice(&INDEX_FORMAT_VERSION.to_le_bytes());
+        content.extend_from_slice(&(data_files.len() as u32).to_le_bytes());
+        for (filename, length) in data_files {
+            content.extend_from_slice(&(filename.len() as u16).to_le_bytes());
+            content.extend_from_slice(&length.to_le_bytes());
+            content.extend_from_slice(filename.as_bytes());
+        }
+        content.extend_from_slice(&(self.len() as u64).to_le_bytes());
+        for entry in self.entries() {
+            let Some(&file_number) = file_numbers.get(entry.value.filename.as_str()) else {
+                return Err(Error::new(ErrorKind::InvalidInput,
+                    format!("an index entry points into {}, which is not one of the data files", entry.value.filename)));
+            };
+            content.extend_from_slice(&(entry.key.len() as u32).to_le_bytes());
+            content.extend_from_slice(&file_number.to_le_bytes());
+            content.extend_from_slice(&entry.value.value_size.to_le_bytes());
+            content.extend_from_slice(&entry.value.value_offset.to_le_bytes());
+            content.extend_from_slice(&entry.value.crc.to_le_bytes());
+            content.extend_from_slice(&entry.value.timestamp.to_le_bytes());
+            content.extend_from_slice(&entry.key);
+        }
+        content.extend_from_slice(&CRC32.checksum(&content).to_le_bytes());
+
+        let path = path.as_ref();
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        write(&temp_path, content)?;
+        rename(temp_path, path)
+    }
+
+    /// Loads an index file written by save_to into this table, returning the data files it was saved with
+    /// Fails with NotFound if there is no index file, and with InvalidData if it has the wrong magic or version,
+    /// fails its checksum or is truncated; the table is left untouched then. Entries are inserted alongside any
+    /// already in the table, so callers normally start from an empty one
+    pub fn load_from<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<DataFiles> {
+        let content = read(path)?;
+        let (data_files, entries) = decode(&content)?;
+        for entry in entries {
+            self.insert_bytes(&entry.key, entry.value);
+        }
+        Ok(data_files)
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("invalid index file: {}", message))
+}
+
+/// Splits `len` bytes off the front of `content`
+fn take<'a>(content: &mut &'a [u8], len: usize) -> std::io::Result<&'a [u8]> {
+    if content.len() < len {
+        return Err(invalid("truncated"));
+    }
+    let (taken, rest) = content.split_at(len);
+    *content = rest;
+    Ok(taken)
+}
+
+fn take_array<const N: usize>(content: &mut &[u8]) -> std::io::Result<[u8; N]> {
+    Ok(take(content, N)?.try_into().expect("take returned N bytes"))
+}
+
+/// Decodes an index file into its data file list and entries, checking magic, version and checksum first
+fn decode(content: &[u8]) -> std::io::Result<(DataFiles, Vec<StoredEntry<FileLocation>>)> {
+    let Some((body, checksum)) = content.split_last_chunk::<4>() else {
+        return Err(invalid("truncated"));
+    };
+    if !body.starts_with(INDEX_MAGIC) {
+        return Err(invalid("not an index file"));
+    }
+    if CRC32.checksum(body) != u32::from_le_bytes(*checksum) {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    let mut rest = &body[INDEX_MAGIC.len()..];
+    let version = u32::from_le_bytes(take_array(&mut rest)?);
+    if version != INDEX_FORMAT_VERSION {
+        return Err(invalid(&format!("format version {} (expected {})", version, INDEX_FORMAT_VERSION)));
+    }
+
+    let file_count = u32::from_le_bytes(take_array(&mut rest)?);
+    let mut data_files = Vec::new();
+    for _ in 0..file_count {
+        let name_size = u16::from_le_bytes(take_array(&mut rest)?) as usize;
+        let length = u64::from_le_bytes(take_array(&mut rest)?);
+        let name = std::str::from_utf8(take(&mut rest, name_size)?).map_err(|_| invalid("file name is not UTF-8"))?;
+        data_files.push((name.to_string(), length));
+    }
+
+    let entry_count = u64::from_le_bytes(take_array(&mut rest)?);
+    let mut entries = Vec::new();
+    for _ in 0..entry_count {
+        let header = take(&mut rest, INDEX_ENTRY_HEADER_SIZE)?;
+        let key_size = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let file_number = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        let Some((filename, _)) = data_files.get(file_number) else {
+            return Err(invalid("entry refers to an unknown data file"));
+        };
+        let location = FileLocation {
+            filename: filename.clone(),
+            value_size: u32::from_le_bytes(header[8..12].try_into().unwrap()),
+            value_offset: u64::from_le_bytes(header[12..20].try_into().unwrap()),
+            crc: u16::from_le_bytes(header[20..22].try_into().unwrap()),
+            timestamp: u64::from_le_bytes(header[22..30].try_into().unwrap()),
+        };
+        entries.push(StoredEntry::new(take(&mut rest, key_size)?, location));
+    }
+    if !rest.is_empty() {
+        return Err(invalid("unexpected bytes after the last entry"));
+    }
+    Ok((data_files, entries))
+}
@@ -1,3 +1,5 @@
 pub mod hash_table_impl;
+pub mod index_file;
 
-pub use hash_table_impl::{HashTable, CollisionResolution, Entry, FileLocation};
\ No newline at end of file
+pub use hash_table_impl::{HashTable, CollisionResolution, Entry, FileLocation, HashTableMetrics, ProbeStats, QuickStats, ScanCursor, DEFAULT_MAX_LOAD_FACTOR, next_prime, polynomial_hash, polynomial_hash_bytes, fnv1a_hash, fnv1a_hash_bytes};
+pub use index_file::{INDEX_FILENAME, INDEX_FORMAT_VERSION};
@@ -1,5 +1,12 @@
 pub mod hash_table;
 pub mod storage;
+pub mod metrics;
 
-pub use hash_table::{HashTable, CollisionResolution, Entry, FileLocation};
-pub use storage::{Storage, StorageError, TOMBSTONE_MARKER, HashTableTrait};
\ No newline at end of file
+pub use hash_table::{HashTable, CollisionResolution, Entry, FileLocation, HashTableMetrics, ProbeStats, QuickStats, ScanCursor, DEFAULT_MAX_LOAD_FACTOR, next_prime, polynomial_hash, polynomial_hash_bytes, fnv1a_hash, fnv1a_hash_bytes, INDEX_FILENAME};
+pub use storage::{Storage, StorageError, GetOutcome, RemovedValue, WriteOp, MergeReport, MergePreview, MergeEstimate, SkippedRecord, RebuildReport, ExportReport, TruncateReport, StorageStats, RawRecord, StorageEvent, RecordType, DurabilityMode, ExportFormat, DEFAULT_MAX_KEY_SIZE, DEFAULT_MAX_VALUE_SIZE, HashTableTrait};
+#[allow(deprecated)]
+pub use storage::TOMBSTONE_MARKER;
+pub use storage::{CachePolicy, LruPolicy, FifoPolicy, ValueCache, CacheStats, PinError};
+pub use storage::{DeleteRetry, FileRemover};
+pub use storage::{SelfTestReport, SelfTestStep, BackgroundMerge, Compression, FileNaming, ReadHandleStats};
+pub use metrics::LatencyHistogram;
\ No newline at end of file
@@ -1,14 +1,27 @@
 use std::fs;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use serde::Deserialize;
-use data_intensive_applications::{HashTable, Storage, CollisionResolution, FileLocation, StorageError};
+use data_intensive_applications::{HashTable, Storage, StorageError, RecordType, DurabilityMode, Compression, FileNaming, CollisionResolution, GetOutcome, RemovedValue, WriteOp, LatencyHistogram, ValueCache, FifoPolicy, ScanCursor, Entry, ExportFormat, DEFAULT_MAX_LOAD_FACTOR, DEFAULT_MAX_KEY_SIZE, DEFAULT_MAX_VALUE_SIZE, fnv1a_hash};
+#[cfg(unix)]
 use crate::event_loop::terminal_event_loop::TerminalEventLoop;
-use crate::event_loop::EventLoop;
+#[cfg(not(unix))]
+use crate::event_loop::blocking_event_loop::BlockingEventLoop;
+use crate::event_loop::tcp_event_loop::{TcpEventLoop, DEFAULT_MAX_LINE_LENGTH};
+use crate::event_loop::{EventLoop, DEFAULT_POLL_TIMEOUT};
+use crate::event_loop::commands::{handle_command, Session};
 
 mod event_loop;
 
 #[derive(Deserialize)]
 struct Config {
     storage: StorageConfig,
+    #[serde(default)]
+    hash_table: HashTableConfig,
+    #[serde(default)]
+    server: ServerConfig,
 }
 
 #[derive(Deserialize)]
@@ -16,21 +29,248 @@ struct StorageConfig {
     max_file_size: u64,
     directory: String,
     merge_interval_seconds: u64,
+    /// Longest the event loop waits for input before checking for auto-merge and syncing idle writes
+    #[serde(default = "default_poll_timeout_ms")]
+    poll_timeout_ms: u64,
+    /// "none", "every_write" or "interval:<milliseconds>"
+    #[serde(default = "default_durability")]
+    durability: String,
+    /// Fsync pending writes when the event loop finds no input within poll_timeout_ms
+    #[serde(default = "default_sync_on_idle")]
+    sync_on_idle: bool,
+    /// Opens the directory with Storage::open_read_only; auto-merge is disabled
+    #[serde(default)]
+    read_only: bool,
+    /// Background merge every this many seconds while files are inactive, regardless of activity; 0 disables
+    #[serde(default)]
+    background_merge_seconds: u64,
+    /// Background merge once more than this many files became inactive since the last merge; 0 disables
+    #[serde(default)]
+    background_merge_files: usize,
+    /// Event loop merges once this share of the inactive files' bytes is dead space; 0 disables
+    #[serde(default)]
+    merge_dead_ratio: f64,
+    /// Longest key a write may use, in bytes; at most 4294967295, the format limit
+    #[serde(default = "default_max_key_size")]
+    max_key_size: u64,
+    /// Longest value a write may store, in bytes; at most 4294967295, the format limit
+    #[serde(default = "default_max_value_size")]
+    max_value_size: u64,
+    /// "none", "lz4" or "zstd"; applies to values written from now on
+    #[serde(default = "default_compression")]
+    compression: String,
+    /// Data files are named <file_prefix><counter>.<file_extension>
+    #[serde(default = "default_file_prefix")]
+    file_prefix: String,
+    /// Merge output files are named <merge_file_prefix><counter>.<file_extension>
+    #[serde(default = "default_merge_file_prefix")]
+    merge_file_prefix: String,
+    #[serde(default = "default_file_extension")]
+    file_extension: String,
+    /// Prefixes every file of the store with <namespace>_ (or .<namespace> for .meta and .index), so stores with
+    /// different namespaces can share a directory; unset gives the store the directory to itself
+    #[serde(default)]
+    namespace: Option<String>,
 }
 
-fn init() -> (Storage, StorageConfig) {
+fn default_durability() -> String {
+    "none".to_string()
+}
+
+fn default_sync_on_idle() -> bool {
+    true
+}
+
+fn default_poll_timeout_ms() -> u64 {
+    DEFAULT_POLL_TIMEOUT.as_millis() as u64
+}
+
+fn default_compression() -> String {
+    "none".to_string()
+}
+
+fn default_file_prefix() -> String {
+    FileNaming::default().data_prefix
+}
+
+fn default_merge_file_prefix() -> String {
+    FileNaming::default().merge_prefix
+}
+
+fn default_file_extension() -> String {
+    FileNaming::default().extension
+}
+
+fn default_max_key_size() -> u64 {
+    DEFAULT_MAX_KEY_SIZE
+}
+
+fn default_max_value_size() -> u64 {
+    DEFAULT_MAX_VALUE_SIZE
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct HashTableConfig {
+    size: u64,
+    collision_method: String,
+    max_load_factor: f64,
+    /// "polynomial" or "fnv1a"
+    hash_function: String,
+}
+
+impl Default for HashTableConfig {
+    fn default() -> Self {
+        HashTableConfig { size: 127, collision_method: "chaining".to_string(), max_load_factor: DEFAULT_MAX_LOAD_FACTOR, hash_function: "polynomial".to_string() }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ServerConfig {
+    /// Serve commands over TCP on this address (e.g. "127.0.0.1:7878") instead of reading stdin; unset keeps the terminal
+    listen_address: Option<String>,
+}
+
+fn init() -> (Storage, HashTable, StorageConfig, Option<SocketAddr>) {
     let config_content = fs::read_to_string("config.toml")
         .expect("Failed to read config.toml");
     
-    let config: Config = toml::from_str(&config_content)
+    let mut config: Config = toml::from_str(&config_content)
         .expect("Failed to parse config.toml");
     
-    match Storage::new_with_config(&config.storage.directory, config.storage.max_file_size) {
-        Ok(storage) => {
+    let collision_method: CollisionResolution = match config.hash_table.collision_method.parse() {
+        Ok(method) => method,
+        Err(e) => panic!("Invalid [hash_table] collision_method in config.toml: {}", e),
+    };
+    if config.hash_table.size == 0 {
+        panic!("Invalid [hash_table] size in config.toml: must be at least 1");
+    }
+    if config.storage.poll_timeout_ms == 0 {
+        panic!("Invalid [storage] poll_timeout_ms in config.toml: must be at least 1");
+    }
+    if !(0.0..1.0).contains(&config.storage.merge_dead_ratio) {
+        panic!("Invalid [storage] merge_dead_ratio in config.toml: must be at least 0 and below 1");
+    }
+    if config.hash_table.max_load_factor <= 0.0 {
+        panic!("Invalid [hash_table] max_load_factor in config.toml: must be positive");
+    }
+    let mut hash_table = HashTable::with_max_load_factor(config.hash_table.size, collision_method.clone(), config.hash_table.max_load_factor);
+    match config.hash_table.hash_function.as_str() {
+        "polynomial" => {}
+        "fnv1a" => hash_table = hash_table.with_hasher(fnv1a_hash),
+        other => panic!("Invalid [hash_table] hash_function in config.toml: unknown hash function '{}' (expected polynomial or fnv1a)", other),
+    }
+
+    let durability: DurabilityMode = match config.storage.durability.parse() {
+        Ok(durability) => durability,
+        Err(e) => panic!("Invalid [storage] durability in config.toml: {}", e),
+    };
+
+    let compression: Compression = match config.storage.compression.parse() {
+        Ok(compression) => compression,
+        Err(e) => panic!("Invalid [storage] compression in config.toml: {}", e),
+    };
+
+    let naming = match FileNaming::new(&config.storage.file_prefix, &config.storage.merge_file_prefix, &config.storage.file_extension) {
+        Ok(naming) => naming,
+        Err(e) => panic!("Invalid [storage] file naming in config.toml: {}", e),
+    };
+    let naming = match &config.storage.namespace {
+        Some(namespace) => match naming.with_namespace(namespace) {
+            Ok(naming) => naming,
+            Err(e) => panic!("Invalid [storage] namespace in config.toml: {}", e),
+        },
+        None => naming,
+    };
+
+    let listen_address = config.server.listen_address.as_ref().map(|address| {
+        match address.to_socket_addrs().map(|mut addresses| addresses.next()) {
+            Ok(Some(address)) => address,
+            Ok(None) => panic!("Invalid [server] listen_address in config.toml: '{}' resolves to no address", address),
+            Err(e) => panic!("Invalid [server] listen_address in config.toml: '{}': {}", address, e),
+        }
+    });
+
+    // A read-only store can't merge, so don't let the event loop or background thread try
+    if config.storage.read_only {
+        config.storage.merge_interval_seconds = 0;
+        config.storage.background_merge_seconds = 0;
+        config.storage.background_merge_files = 0;
+        config.storage.merge_dead_ratio = 0.0;
+    }
+    let storage = if config.storage.read_only {
+        Storage::open_read_only(&config.storage.directory, naming)
+    } else {
+        Storage::new_with_config(&config.storage.directory, config.storage.max_file_size, durability, compression, naming)
+    };
+
+    match storage {
+        Ok(mut storage) => {
+            if let Err(e) = storage.set_size_limits(config.storage.max_key_size, config.storage.max_value_size) {
+                panic!("Invalid [storage] size limits in config.toml: {}", e);
+            }
+            storage.set_merge_dead_ratio((config.storage.merge_dead_ratio > 0.0).then_some(config.storage.merge_dead_ratio));
             println!("✓ Storage initialized in '{}/' directory", config.storage.directory);
+            if storage.is_read_only() {
+                println!("  - Read-only: writes, deletes and merges are rejected");
+            }
+            if storage.truncated_on_open() > 0 {
+                println!("  - Truncated a {}-byte uncommitted record left by an interrupted write from {}", storage.truncated_on_open(), storage.active_filename());
+            }
             println!("  - Max file size: {} bytes", config.storage.max_file_size);
-            println!("  - Auto-merge interval: {} seconds", config.storage.merge_interval_seconds);
-            (storage, config.storage)
+            println!("  - Durability: {:?}", durability);
+            if !config.storage.sync_on_idle {
+                println!("  - Idle sync: disabled");
+            }
+            println!("  - Compression: {:?}", storage.compression());
+            let naming = storage.file_naming();
+            if let Some(namespace) = &naming.namespace {
+                println!("  - Namespace: {} (other namespaces in '{}/' are left alone)", namespace, config.storage.directory);
+            }
+            if *naming != FileNaming::default() {
+                println!("  - Data files: {}N.{}, merge files: {}N.{}", naming.data_prefix, naming.extension, naming.merge_prefix, naming.extension);
+            }
+            println!("  - Max key size: {} bytes, max value size: {} bytes", config.storage.max_key_size, config.storage.max_value_size);
+            if config.storage.merge_interval_seconds == 0 {
+                println!("  - Auto-merge: disabled");
+            } else {
+                println!("  - Auto-merge interval: {} seconds (checked every {} ms)", config.storage.merge_interval_seconds, config.storage.poll_timeout_ms);
+            }
+            match (config.storage.background_merge_seconds, config.storage.background_merge_files) {
+                (0, 0) => {}
+                (0, files) => println!("  - Background merge: past {} new inactive files", files),
+                (seconds, 0) => println!("  - Background merge: every {} seconds", seconds),
+                (seconds, files) => println!("  - Background merge: every {} seconds or past {} new inactive files", seconds, files),
+            }
+            if let Some(ratio) = storage.merge_dead_ratio() {
+                println!("  - Dead-space merge: past {:.0}% of inactive bytes", ratio * 100.0);
+            }
+            println!("  - Hash table: {} buckets, {:?}, {} hash, grows past load factor {}",
+                config.hash_table.size, collision_method, config.hash_table.hash_function, config.hash_table.max_load_factor);
+
+            // Load the index saved at the last clean shutdown if the data files are unchanged since. Otherwise
+            // replay the data files so keys written by earlier runs are reachable again, using the hint files
+            // left by merge where they exist
+            let start = std::time::Instant::now();
+            match storage.load_index(&mut hash_table) {
+                Ok(loaded) => {
+                    println!("✓ Loaded {} keys from the saved index in {:.2?}", loaded, start.elapsed());
+                    return (storage, hash_table, config.storage, listen_address);
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => println!("  - Saved index not used ({}), replaying data files", e),
+            }
+            match storage.rebuild_index_from_hints(&mut hash_table) {
+                Ok(report) => {
+                    for skipped in &report.skipped {
+                        println!("  ✗ {}", skipped);
+                    }
+                    println!("✓ Recovered {} keys from data files in {:.2?} ({} read from hint files)", report.live_keys, start.elapsed(), report.hinted_files);
+                }
+                Err(e) => panic!("Failed to rebuild index from data files: {}", e),
+            }
+            (storage, hash_table, config.storage, listen_address)
         },
         Err(e) => {
             panic!("Failed to initialize storage: {}", e);
@@ -38,26 +278,183 @@ fn init() -> (Storage, StorageConfig) {
     }
 }
 
+/// Collects commands from `--exec <command>` and `--script <path>` (or `--exec-file <path>`) arguments, in order
+/// Script files hold one command per line; blank lines and lines starting with '#' are skipped. A path of `-`
+/// reads the script from stdin. Returns None when no arguments were given, i.e. for an interactive session
+fn parse_exec_args(args: &[String]) -> Result<Option<Vec<String>>, String> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    let mut commands = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--exec" => {
+                let command = args.next().ok_or("--exec requires a command")?;
+                commands.push(command.clone());
+            }
+            "--script" | "--exec-file" => {
+                let path = args.next().ok_or(format!("{} requires a path", arg))?;
+                let script = if path == "-" {
+                    io::read_to_string(io::stdin()).map_err(|e| format!("Failed to read stdin: {}", e))?
+                } else {
+                    fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?
+                };
+                commands.extend(script.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(String::from));
+            }
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+    Ok(Some(commands))
+}
+
+/// Runs commands without the interactive event loop, then exits
+/// Nothing merges on its own meanwhile, so what a script does to a store doesn't depend on timing
+fn run_commands(commands: &[String], store: &Mutex<(Storage, HashTable)>) {
+    // Auto-merge stays off: there is no idle time between scripted commands
+    let mut session = Session::new(0);
+    println!("Running {} commands; merges only run when a command asks for one", commands.len());
+    for command in commands {
+        println!("> {}", command);
+        let mut state = store.lock().unwrap();
+        let (storage, hash_table) = &mut *state;
+        if handle_command(command, storage, hash_table, &mut session, &mut io::stdout()) {
+            break;
+        }
+    }
+    if let Err(e) = store.lock().unwrap().0.sync() {
+        println!("✗ Failed to sync storage: {}", e);
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let commands = match parse_exec_args(&args) {
+        Ok(commands) => commands,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: data_intensive_applications [--exec <command>]... [--script <path>|-]...");
+            std::process::exit(2);
+        }
+    };
+
+    let (storage, hash_table, config, listen_address) = init();
+    let store = Arc::new(Mutex::new((storage, hash_table)));
+
+    if let Some(commands) = commands {
+        run_commands(&commands, &store);
+        return;
+    }
+
+    let poll_timeout = Duration::from_millis(config.poll_timeout_ms);
+
+    // Stops when dropped at the end of main
+    let _background_merge = (config.background_merge_seconds > 0 || config.background_merge_files > 0).then(|| {
+        Storage::start_background_merge(
+            Arc::clone(&store),
+            (config.background_merge_seconds > 0).then(|| Duration::from_secs(config.background_merge_seconds)),
+            (config.background_merge_files > 0).then_some(config.background_merge_files),
+        )
+    });
+
+    if let Some(address) = listen_address {
+        println!("=== Hash Table Storage Server ===");
+        println!("Clients send one command per line, as typed at the terminal; 'help' lists them and 'exit' disconnects");
+        if config.merge_interval_seconds > 0 {
+            println!("Auto-merge will trigger after {} seconds without commands from any client.", config.merge_interval_seconds);
+        }
+        TcpEventLoop { address, poll_timeout, sync_on_idle: config.sync_on_idle, max_line_length: DEFAULT_MAX_LINE_LENGTH }.run(&store, config.merge_interval_seconds);
+        return;
+    }
+
     println!("=== Interactive Hash Table Storage System ===");
     
-    let (mut storage, config) = init();
-    let mut hash_table = HashTable::new(127, CollisionResolution::Chaining);
-    
     println!("
 Entering interactive mode...");
     println!("Commands:");
-    println!("  insert <key> <value>  - Insert or update a key-value pair");
-    println!("  delete <key>          - Delete a key");
+    println!("  insert <key> <value>  - Insert or update a key-value pair (double-quote keys or values with spaces)");
+    println!("  setnx <key> <value>   - Insert only if the key doesn't exist (deleted keys count as absent)");
+    println!("  setxx <key> <value>   - Update only if the key already exists");
+    println!("  getset <key> <value>  - Insert or update a key, showing the value it replaced");
+    println!("  update-in-place <key> <value> - Overwrite a value of the same length without appending (not append-only)");
+    println!("  delete <key> [reason] - Delete a key, optionally recording why, and show the value removed");
+    println!("  delprefix <prefix>    - Delete every live key starting with <prefix>");
     println!("  get <key>             - Retrieve a value by key");
+    println!("  mget <key>...         - Retrieve several values at once, in the order given");
+    println!("  list                  - List every key in the index, sorted");
+    println!("  count [tombstones]    - Show how many live keys are in the index, optionally with deleted keys awaiting compaction");
     println!("  stats                 - Show storage statistics");
     println!("  merge                 - Manually trigger merge operation");
+    println!("  merge --dry-run       - Show what a merge would keep and reclaim, without merging");
+    println!("  compact               - Merge every data file, including the active one, to reclaim all dead space");
+    println!("  merge-into <directory> - Compact live records into a new store in another directory, verified, leaving this one as is");
+    println!("  merge-budget <bytes>  - Merge the deadest inactive files first, rewriting at most <bytes> of live records");
+    println!("  prune                 - Delete inactive files holding only superseded records, without merging");
+    println!("  relocate <directory>  - Move all data files to another directory and keep running there");
+    println!("  backup <directory>    - Copy a point-in-time snapshot of the store into an empty directory");
+    println!("  sync                  - Flush pending writes to disk (skipped if nothing changed)");
+    println!("  rehash                - Rebuild the hash table and show probe statistics");
+    println!("  htstats [reset]       - Show probes and collisions counted by index inserts and lookups, optionally zeroing them");
+    println!("  simulate-crash        - Drop the in-memory index and recover it from data files");
+    println!("  simulate-duplicate <key> - Corrupt the index by storing a second copy of a key");
+    println!("  simulate-misdirect <key> <other_key> - Corrupt the index by pointing a key at another key's record");
+    println!("  simulate-locked-files <on|off> - Make removing merged files fail, as if still open elsewhere");
+    println!("  selftest              - Write, read, delete and merge keys in a scratch store to check the storage engine");
+    println!("  duplicates            - Check the index for keys stored more than once");
+    println!("  getmeta <key>         - Show a key's value with its file, offset, size, CRC and write time");
+    println!("  exists <key>          - Report whether a key is present without reading its value");
+    println!("  verify <key>          - Read a key's value from disk, bypassing the cache, checking the record belongs to it");
+    println!("  insert-batch <key>=<value>... - Insert several keys with a single write per data file");
+    println!("  transaction put:<key>=<value>|del:<key>... - Apply several puts and deletes all together or not at all");
+    println!("  insert-hex <key> <hex> - Insert a binary value given as hex digits");
+    println!("  get-hex <key>         - Retrieve a value as hex, even if it is not valid UTF-8");
+    println!("  hexdump <file> <offset> [len] - Show raw bytes of a record as hex and ASCII");
+    println!("  layout                - Show each data file's byte range in the store viewed as one log");
+    println!("  records               - List every record on disk, superseded ones and deletes included");
+    println!("  read-logical <offset> - Decode the record at an offset in that single-log view");
+    println!("  bench read <count>    - Time <count> random gets and report latency percentiles");
+    println!("  bench merge           - Estimate merge cost on the current data without merging");
+    println!("  bench probing [buckets] - Compare longest probes of linear probing and Robin Hood at 0.9 load");
+    println!("  scan <limit> [cursor] - Page through keys; without a cursor, walks every page");
+    println!("  scan prefix <prefix>  - Show every live key starting with <prefix> and its value, sorted");
+    println!("  cache [<capacity> [lru|fifo] | off] - Show value cache stats, or enable/disable it");
+    println!("  cache pin-budget <bytes> - Limit the total size of pinned cached values");
+    println!("  pin <key> / unpin <key> - Keep a key's value in the cache, never evicted / allow eviction again");
+    println!("  recent-deletes [n]    - Show the last n keys deleted this session (default 10)");
+    println!("  tombstones            - List every deleted key whose tombstone is still on disk, with when it was deleted");
+    println!("  timestamp             - Show the current record timestamp (ns since epoch)");
+    println!("  age                   - Show the oldest and newest record timestamps and the span between them");
+    println!("  truncate-to <ts> confirm - Roll the store back to a timestamp (destructive)");
+    println!("  export <file> [jsonl|text] - Write every live key-value pair to a file, sorted by key");
     println!("  help                  - Show this help message");
     println!("  exit                  - Exit the program");
-    println!("
+    if config.merge_interval_seconds == 0 {
+        println!("
+Auto-merge is disabled; use 'merge' to compact manually.
+");
+    } else {
+        println!("
 Auto-merge will trigger after {} seconds of inactivity.
 ", config.merge_interval_seconds);
+    }
     
-    let mut event_loop = TerminalEventLoop {};
-    event_loop.run(&mut storage, &mut hash_table, config.merge_interval_seconds);
+    // TerminalEventLoop polls stdin with mio's unix extensions; elsewhere a reader thread feeds the loop instead
+    #[cfg(unix)]
+    let mut event_loop = TerminalEventLoop { poll_timeout, sync_on_idle: config.sync_on_idle };
+    #[cfg(not(unix))]
+    let mut event_loop = BlockingEventLoop { poll_timeout, sync_on_idle: config.sync_on_idle };
+    event_loop.run(&store, config.merge_interval_seconds);
+
+    // A clean exit: save the index so the next start can load it instead of replaying the data files
+    let (storage, hash_table) = &*store.lock().unwrap();
+    if !storage.is_read_only() {
+        match storage.save_index(hash_table) {
+            Ok(()) => println!("✓ Saved the index ({} keys) for the next start", hash_table.len()),
+            Err(e) => println!("✗ Failed to save the index: {}", e),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+/// Linear sub-buckets per power of two, giving roughly 12.5% resolution
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+/// Enough buckets to cover every u64 nanosecond value
+const BUCKET_COUNT: usize = ((64 - SUB_BUCKET_BITS + 1) as usize) * SUB_BUCKETS as usize;
+
+/// Fixed-size latency histogram with log-linear buckets over nanoseconds
+/// Recording is O(1) and memory stays constant no matter how many samples are added
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    min_nanos: u64,
+    max_nanos: u64,
+    total_nanos: u128,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> LatencyHistogram {
+        LatencyHistogram { buckets: vec![0; BUCKET_COUNT], count: 0, min_nanos: u64::MAX, max_nanos: 0, total_nanos: 0 }
+    }
+
+    /// Adds one sample
+    pub fn record(&mut self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        self.buckets[bucket_index(nanos)] += 1;
+        self.count += 1;
+        self.min_nanos = self.min_nanos.min(nanos);
+        self.max_nanos = self.max_nanos.max(nanos);
+        self.total_nanos += nanos as u128;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Smallest recorded sample, or zero when empty
+    pub fn min(&self) -> Duration {
+        if self.count == 0 { Duration::ZERO } else { Duration::from_nanos(self.min_nanos) }
+    }
+
+    /// Largest recorded sample, or zero when empty
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_nanos)
+    }
+
+    /// Mean of all samples, or zero when empty
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.total_nanos / self.count as u128) as u64)
+    }
+
+    /// Latency at or below which `percentile` percent of samples fall (0.0..=100.0)
+    /// Reported as the upper edge of the matching bucket, clamped to the observed min and max,
+    /// so results never decrease as the percentile grows
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+
+        let mut seen = 0;
+        for (index, &bucket_count) in self.buckets.iter().enumerate() {
+            seen += bucket_count;
+            if seen >= rank {
+                let nanos = bucket_upper_bound(index).clamp(self.min_nanos, self.max_nanos);
+                return Duration::from_nanos(nanos);
+            }
+        }
+        self.max()
+    }
+}
+
+/// Values below SUB_BUCKETS get a bucket each; larger ones are split by their highest bit,
+/// then linearly by the next SUB_BUCKET_BITS bits
+fn bucket_index(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS {
+        return nanos as usize;
+    }
+    let msb = 63 - nanos.leading_zeros();
+    let sub = (nanos >> (msb - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+    ((msb - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS + sub) as usize
+}
+
+/// Largest value that maps to the given bucket
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let shift = (index / SUB_BUCKETS - 1) as u32;
+    let sub = index % SUB_BUCKETS;
+    let lower = (SUB_BUCKETS + sub) << shift;
+    lower + ((1u64 << shift) - 1)
+}
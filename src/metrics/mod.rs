@@ -0,0 +1,3 @@
+pub mod histogram;
+
+pub use histogram::LatencyHistogram;
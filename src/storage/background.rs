@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::storage::storage::{HashTableTrait, Storage};
+
+/// How often the background thread checks whether a merge is due
+const CHECK_PERIOD: Duration = Duration::from_millis(500);
+
+/// Handle to the thread started by Storage::start_background_merge
+/// Dropping it stops the thread, waiting for a merge in progress to finish
+pub struct BackgroundMerge {
+    /// Dropped to tell the thread to stop
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    merges: Arc<AtomicUsize>,
+}
+
+impl BackgroundMerge {
+    /// Number of merges the thread has completed
+    pub fn merge_count(&self) -> usize {
+        self.merges.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for BackgroundMerge {
+    fn drop(&mut self) {
+        // A disconnected channel wakes the thread immediately
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Starts a thread that merges `store` whenever more than `file_threshold` files have become inactive since the
+/// last merge, or once `interval` has passed with at least one inactive file. None disables that trigger.
+/// The storage and its index share one lock, so a merge never runs between a write and its index update
+pub(crate) fn start<T>(store: Arc<Mutex<(Storage, T)>>, interval: Option<Duration>, file_threshold: Option<usize>) -> BackgroundMerge
+where
+    T: HashTableTrait + Send + 'static,
+{
+    let (stop, stopped) = mpsc::channel::<()>();
+    let merges = Arc::new(AtomicUsize::new(0));
+    let thread_merges = Arc::clone(&merges);
+
+    let thread = thread::spawn(move || {
+        let mut last_merge = Instant::now();
+        // Files a merge leaves inactive (its own merge output) don't count towards the threshold,
+        // so live data larger than the threshold can't keep the thread merging
+        let mut baseline = 0;
+        loop {
+            match stopped.recv_timeout(CHECK_PERIOD) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+            // A poisoned lock means the owner panicked mid-operation; leave the store alone
+            let Ok(mut state) = store.lock() else { return };
+            let (storage, hash_table) = &mut *state;
+
+            let inactive = match storage.inactive_file_count() {
+                Ok(inactive) => inactive,
+                Err(e) => {
+                    println!("✗ Background merge could not list data files: {}", e);
+                    continue;
+                }
+            };
+            baseline = baseline.min(inactive);
+            let interval_due = interval.is_some_and(|interval| last_merge.elapsed() >= interval);
+            let threshold_due = file_threshold.is_some_and(|threshold| inactive - baseline > threshold);
+            if interval_due && inactive == 0 {
+                last_merge = Instant::now();
+            }
+            if !(threshold_due || interval_due && inactive > 0) {
+                continue;
+            }
+
+            println!("\nBackground merge of {} inactive files...", inactive);
+            match storage.merge_inactive_files(Some(hash_table)) {
+                Ok(()) => {
+                    thread_merges.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => println!("✗ Background merge failed: {}", e),
+            }
+            last_merge = Instant::now();
+            baseline = storage.inactive_file_count().unwrap_or(0);
+        }
+    });
+
+    BackgroundMerge { stop: Some(stop), thread: Some(thread), merges }
+}
@@ -0,0 +1,288 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+/// Decides which cached key to evict when the value cache is full
+/// The cache reports every access, insertion and removal; the policy keeps whatever bookkeeping it needs
+pub trait CachePolicy: Send {
+    /// A cached key was read
+    fn on_access(&mut self, key: &str);
+    /// A key was added to the cache
+    fn on_insert(&mut self, key: &str);
+    /// A key left the cache without being evicted (invalidated by a write, or the cache was cleared)
+    fn on_remove(&mut self, key: &str);
+    /// Picks the next key to evict and stops tracking it
+    fn evict_candidate(&mut self) -> Option<String>;
+}
+
+/// Evicts the least recently used key (the default policy)
+#[derive(Debug, Default)]
+pub struct LruPolicy {
+    tick: u64,
+    last_used: HashMap<String, u64>,
+    by_tick: BTreeMap<u64, String>,
+}
+
+impl LruPolicy {
+    pub fn new() -> LruPolicy {
+        LruPolicy::default()
+    }
+
+    fn touch(&mut self, key: &str) {
+        if let Some(previous) = self.last_used.remove(key) {
+            self.by_tick.remove(&previous);
+        }
+        self.tick += 1;
+        self.last_used.insert(key.to_string(), self.tick);
+        self.by_tick.insert(self.tick, key.to_string());
+    }
+}
+
+impl CachePolicy for LruPolicy {
+    fn on_access(&mut self, key: &str) {
+        self.touch(key);
+    }
+
+    fn on_insert(&mut self, key: &str) {
+        self.touch(key);
+    }
+
+    fn on_remove(&mut self, key: &str) {
+        if let Some(tick) = self.last_used.remove(key) {
+            self.by_tick.remove(&tick);
+        }
+    }
+
+    fn evict_candidate(&mut self) -> Option<String> {
+        let (_, key) = self.by_tick.pop_first()?;
+        self.last_used.remove(&key);
+        Some(key)
+    }
+}
+
+/// Evicts keys in insertion order, ignoring reads
+#[derive(Debug, Default)]
+pub struct FifoPolicy {
+    queue: VecDeque<String>,
+}
+
+impl FifoPolicy {
+    pub fn new() -> FifoPolicy {
+        FifoPolicy::default()
+    }
+}
+
+impl CachePolicy for FifoPolicy {
+    fn on_access(&mut self, _key: &str) {}
+
+    fn on_insert(&mut self, key: &str) {
+        self.queue.push_back(key.to_string());
+    }
+
+    fn on_remove(&mut self, key: &str) {
+        self.queue.retain(|queued| queued != key);
+    }
+
+    fn evict_candidate(&mut self) -> Option<String> {
+        self.queue.pop_front()
+    }
+}
+
+/// Bytes of pinned values a cache allows unless configured otherwise
+pub const DEFAULT_MAX_PINNED_BYTES: usize = 64 * 1024;
+
+/// Hit/miss counters and occupancy of a ValueCache
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub capacity: usize,
+    /// Bytes of the pinned values currently cached
+    pub pinned_bytes: usize,
+    pub max_pinned_bytes: usize,
+}
+
+/// Why a key could not be pinned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinError {
+    /// Storage has no value cache installed
+    CacheDisabled,
+    /// The key's value is not in the cache; read it first
+    NotCached(String),
+    /// Pinning the value would take pinned bytes past the cache's budget
+    OverBudget { key: String, value_bytes: usize, available_bytes: usize },
+}
+
+impl std::fmt::Display for PinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PinError::CacheDisabled => write!(f, "the value cache is disabled"),
+            PinError::NotCached(key) => write!(f, "'{}' is not in the value cache", key),
+            PinError::OverBudget { key, value_bytes, available_bytes } => write!(
+                f, "'{}' needs {} bytes but only {} pinned bytes are left", key, value_bytes, available_bytes
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PinError {}
+
+/// A cached value and the location it was read from
+struct CachedValue {
+    filename: String,
+    value_offset: u64,
+    value: String,
+}
+
+/// Bounded cache of decoded values, keyed by key and tagged with the record location they came from
+/// A lookup only hits when the caller asks for the same location, so values moved by merge simply miss.
+/// Pinned keys are never evicted; a pin outlives its entry being invalidated, so the key's next value stays pinned too
+pub struct ValueCache {
+    capacity: usize,
+    entries: HashMap<String, CachedValue>,
+    policy: Box<dyn CachePolicy>,
+    stats: CacheStats,
+    pinned: BTreeSet<String>,
+    max_pinned_bytes: usize,
+}
+
+impl ValueCache {
+    /// Creates a cache holding up to `capacity` values with LRU eviction
+    pub fn new(capacity: usize) -> ValueCache {
+        Self::with_policy(capacity, Box::new(LruPolicy::new()))
+    }
+
+    /// Creates a cache holding up to `capacity` values with a custom eviction policy
+    pub fn with_policy(capacity: usize, policy: Box<dyn CachePolicy>) -> ValueCache {
+        ValueCache {
+            capacity,
+            entries: HashMap::new(),
+            policy,
+            stats: CacheStats { capacity, ..CacheStats::default() },
+            pinned: BTreeSet::new(),
+            max_pinned_bytes: DEFAULT_MAX_PINNED_BYTES,
+        }
+    }
+
+    /// Returns the cached value for `key` if it was read from the given location
+    pub fn get(&mut self, key: &str, filename: &str, value_offset: u64) -> Option<String> {
+        match self.entries.get(key) {
+            Some(cached) if cached.filename == filename && cached.value_offset == value_offset => {
+                self.stats.hits += 1;
+                self.policy.on_access(key);
+                Some(cached.value.clone())
+            }
+            _ => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches a value read from the given location, evicting as needed to stay within capacity
+    /// Pinned entries are skipped when evicting. A pinned key whose new value no longer fits the pinned-byte budget is unpinned
+    pub fn insert(&mut self, key: &str, filename: &str, value_offset: u64, value: String) {
+        if self.capacity == 0 {
+            return;
+        }
+        let cached = CachedValue { filename: filename.to_string(), value_offset, value };
+        let replaced = self.entries.insert(key.to_string(), cached).is_some();
+        if self.pinned.contains(key) && self.pinned_bytes() > self.max_pinned_bytes {
+            self.pinned.remove(key);
+        }
+        if replaced {
+            self.policy.on_access(key);
+            return;
+        }
+        self.policy.on_insert(key);
+
+        let mut skipped = Vec::new();
+        while self.entries.len() > self.capacity {
+            match self.policy.evict_candidate() {
+                Some(victim) if self.pinned.contains(&victim) => skipped.push(victim),
+                Some(victim) => {
+                    if self.entries.remove(&victim).is_some() {
+                        self.stats.evictions += 1;
+                    }
+                }
+                None => break,
+            }
+        }
+        // Pinned keys the policy offered up go back under its tracking
+        for key in skipped {
+            self.policy.on_insert(&key);
+        }
+    }
+
+    /// Marks a cached key as non-evictable
+    /// Fails if the key is not cached or its value would take pinned values past the pinned-byte budget
+    pub fn pin(&mut self, key: &str) -> Result<(), PinError> {
+        let value_bytes = match self.entries.get(key) {
+            Some(cached) => cached.value.len(),
+            None => return Err(PinError::NotCached(key.to_string())),
+        };
+        if self.pinned.contains(key) {
+            return Ok(());
+        }
+        let available_bytes = self.max_pinned_bytes.saturating_sub(self.pinned_bytes());
+        if value_bytes > available_bytes {
+            return Err(PinError::OverBudget { key: key.to_string(), value_bytes, available_bytes });
+        }
+        self.pinned.insert(key.to_string());
+        Ok(())
+    }
+
+    /// Makes a key evictable again; returns false if it was not pinned
+    pub fn unpin(&mut self, key: &str) -> bool {
+        self.pinned.remove(key)
+    }
+
+    /// Pinned keys, sorted
+    pub fn pinned_keys(&self) -> Vec<&str> {
+        self.pinned.iter().map(String::as_str).collect()
+    }
+
+    /// Caps the bytes of pinned values; existing pins are kept even if they now exceed it
+    pub fn set_max_pinned_bytes(&mut self, max_pinned_bytes: usize) {
+        self.max_pinned_bytes = max_pinned_bytes;
+    }
+
+    /// Bytes of the pinned values currently cached
+    fn pinned_bytes(&self) -> usize {
+        self.pinned.iter()
+            .filter_map(|key| self.entries.get(key))
+            .map(|cached| cached.value.len())
+            .sum()
+    }
+
+    /// Drops the cached value for a key, e.g. after a newer version was written
+    pub fn invalidate(&mut self, key: &str) {
+        if self.entries.remove(key).is_some() {
+            self.policy.on_remove(key);
+        }
+    }
+
+    /// Drops every cached value
+    pub fn clear(&mut self) {
+        for key in self.entries.keys() {
+            self.policy.on_remove(key);
+        }
+        self.entries.clear();
+    }
+
+    /// Keys currently cached, sorted
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self.entries.keys().map(String::as_str).collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            pinned_bytes: self.pinned_bytes(),
+            max_pinned_bytes: self.max_pinned_bytes,
+            ..self.stats
+        }
+    }
+}
@@ -0,0 +1,61 @@
+/// How a record's value bytes are compressed on disk, stored in the high four bits of the record's flags byte
+/// (format version 4 onwards; older files are never compressed). The value_size and CRC in the header and in
+/// FileLocation describe the stored bytes, so checksums are verified before anything is decompressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None = 0,
+    /// LZ4 block format, prefixed with the uncompressed length
+    Lz4 = 1,
+    /// Zstandard frame at the default level
+    Zstd = 2,
+}
+
+impl Compression {
+    pub(crate) fn from_byte(byte: u8) -> Option<Compression> {
+        match byte {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz4),
+            2 => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Compresses a value for writing, returning the compression actually used and the bytes to store
+    /// Values that don't shrink are stored as they are, so compression never makes a record bigger
+    pub(crate) fn encode(self, value: &[u8]) -> (Compression, Vec<u8>) {
+        let compressed = match self {
+            Compression::None => None,
+            Compression::Lz4 => Some(lz4_flex::compress_prepend_size(value)),
+            Compression::Zstd => zstd::encode_all(value, zstd::DEFAULT_COMPRESSION_LEVEL).ok(),
+        };
+        match compressed {
+            Some(compressed) if compressed.len() < value.len() => (self, compressed),
+            _ => (Compression::None, value.to_vec()),
+        }
+    }
+
+    /// Restores the original value from bytes stored with this compression
+    pub(crate) fn decode(self, stored: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(stored),
+            Compression::Lz4 => lz4_flex::decompress_size_prepended(&stored)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Compression::Zstd => zstd::decode_all(stored.as_slice()),
+        }
+    }
+}
+
+impl std::str::FromStr for Compression {
+    type Err = String;
+
+    /// Parses the config form: "none", "lz4" or "zstd", ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "lz4" => Ok(Compression::Lz4),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression '{}' (expected none, lz4 or zstd)", s)),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::fs::{read_to_string, remove_file, write};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+use crate::storage::naming::FileNaming;
+
+/// Name of the file listing merged data files that could not be removed yet
+pub const PENDING_DELETES_FILENAME: &str = ".pending_deletes";
+
+/// How often Storage retries removing a merged data file before deferring it
+/// The backoff doubles after every failed attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeleteRetry {
+    /// Total removal attempts, including the first
+    pub attempts: u32,
+    /// Wait before the second attempt
+    pub initial_backoff: Duration,
+}
+
+impl Default for DeleteRetry {
+    fn default() -> Self {
+        DeleteRetry { attempts: 3, initial_backoff: Duration::from_millis(10) }
+    }
+}
+
+/// Removes a data file; replaceable to simulate files another process still holds open
+pub type FileRemover = Box<dyn FnMut(&Path) -> std::io::Result<()> + Send>;
+
+/// Reads the pending-delete list, empty if the store has none
+pub fn load(storage_dir: &Path, naming: &FileNaming) -> std::io::Result<Vec<String>> {
+    match read_to_string(storage_dir.join(naming.store_file(PENDING_DELETES_FILENAME))) {
+        Ok(content) => Ok(content.lines().filter(|line| !line.is_empty()).map(String::from).collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Persists the pending-delete list, removing the file once nothing is pending
+pub fn save(storage_dir: &Path, naming: &FileNaming, pending: &[String]) -> std::io::Result<()> {
+    let path = storage_dir.join(naming.store_file(PENDING_DELETES_FILENAME));
+    if pending.is_empty() {
+        return match remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    let mut content = pending.join("\n");
+    content.push('\n');
+    write(path, content)
+}
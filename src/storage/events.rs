@@ -0,0 +1,17 @@
+use std::time::Duration;
+
+/// Events reported by Storage to a registered listener
+#[derive(Debug, Clone)]
+pub enum StorageEvent {
+    /// An operation took at least the configured slow-op threshold
+    SlowOp {
+        /// Operation label: "write", "read_value" or "merge"
+        op: &'static str,
+        duration: Duration,
+        /// Key involved, if the operation targets a single key
+        key: Option<String>,
+    },
+}
+
+/// Callback invoked for every StorageEvent
+pub type EventListener = Box<dyn FnMut(&StorageEvent) + Send>;
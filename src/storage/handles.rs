@@ -0,0 +1,66 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Most data files kept open for reading at once; opening one more closes an arbitrary other
+pub const MAX_READ_HANDLES: usize = 64;
+
+/// Counters describing how well the read handles are reused, see Storage::read_handle_stats
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadHandleStats {
+    /// Data files currently held open for reading
+    pub open: usize,
+    /// Times a data file had to be opened for a read
+    pub opens: u64,
+    /// Reads served through a handle that was already open, each saving an open() call
+    pub reuses: u64,
+}
+
+/// Read handles to data files, shared by point reads so each file is opened once instead of on every read
+/// Appends go through the store's own handle to the same file, so a cached handle sees them as soon as they
+/// are written; readers seek before every read and check the length through the handle, never a cached one.
+/// RefCell and Cell let reads through &Storage open handles; Storage is used from one thread at a time
+#[derive(Debug, Default)]
+pub(crate) struct ReadHandles {
+    handles: RefCell<HashMap<String, Arc<File>>>,
+    opens: Cell<u64>,
+    reuses: Cell<u64>,
+}
+
+impl ReadHandles {
+    /// Handle for `filename` in `dir`, opening the file only if no handle is cached
+    pub fn get(&self, dir: &Path, filename: &str) -> std::io::Result<Arc<File>> {
+        if let Some(handle) = self.handles.borrow().get(filename) {
+            self.reuses.set(self.reuses.get() + 1);
+            return Ok(Arc::clone(handle));
+        }
+
+        let handle = Arc::new(File::open(dir.join(filename))?);
+        self.opens.set(self.opens.get() + 1);
+        let mut handles = self.handles.borrow_mut();
+        if handles.len() >= MAX_READ_HANDLES
+            && let Some(victim) = handles.keys().next().cloned()
+        {
+            handles.remove(&victim);
+        }
+        handles.insert(filename.to_string(), Arc::clone(&handle));
+        Ok(handle)
+    }
+
+    /// Closes the handle for a file about to be removed or replaced; Windows can't delete a file that is open,
+    /// and elsewhere the handle would keep reading the old contents
+    pub fn evict(&self, filename: &str) {
+        self.handles.borrow_mut().remove(filename);
+    }
+
+    /// Closes every handle, e.g. before the files move to another directory
+    pub fn clear(&self) {
+        self.handles.borrow_mut().clear();
+    }
+
+    pub fn stats(&self) -> ReadHandleStats {
+        ReadHandleStats { open: self.handles.borrow().len(), opens: self.opens.get(), reuses: self.reuses.get() }
+    }
+}
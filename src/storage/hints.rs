@@ -0,0 +1,89 @@
+use std::fs::{read, remove_file, rename, write};
+use std::io::ErrorKind;
+use std::path::Path;
+use crate::storage::storage::RecordType;
+
+/// Size of a hint entry before its key: key_size, value_size, value_offset, crc, timestamp and record type
+const HINT_ENTRY_HEADER_SIZE: usize = 27;
+
+/// One record of a data file as described by its hint file: everything the index needs except the value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintEntry {
+    pub record_type: RecordType,
+    pub key: Vec<u8>,
+    pub value_size: u32,
+    pub value_offset: u64,
+    pub crc: u16,
+    /// Write time in nanoseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+/// Name of the hint file that accompanies a data file (data_0000000001.dat -> data_0000000001.hint)
+pub fn hint_filename(data_filename: &str) -> String {
+    let stem = data_filename.rsplit_once('.').map_or(data_filename, |(stem, _)| stem);
+    format!("{}.hint", stem)
+}
+
+/// Writes the hint file for a data file whose first `covered_len` bytes hold exactly `entries`, in order
+/// Layout: [covered_len:8] then per entry
+/// [key_size:4][value_size:4][value_offset:8][crc:2][timestamp:8][record_type:1][key], all little endian.
+/// Written to a temp file and renamed, so a crash never leaves a half-written hint behind
+pub fn save(storage_dir: &Path, data_filename: &str, covered_len: u64, entries: &[HintEntry]) -> std::io::Result<()> {
+    let mut content = Vec::new();
+    content.extend_from_slice(&covered_len.to_le_bytes());
+    for entry in entries {
+        content.extend_from_slice(&(entry.key.len() as u32).to_le_bytes());
+        content.extend_from_slice(&entry.value_size.to_le_bytes());
+        content.extend_from_slice(&entry.value_offset.to_le_bytes());
+        content.extend_from_slice(&entry.crc.to_le_bytes());
+        content.extend_from_slice(&entry.timestamp.to_le_bytes());
+        content.push(entry.record_type as u8);
+        content.extend_from_slice(&entry.key);
+    }
+
+    let path = storage_dir.join(hint_filename(data_filename));
+    let temp_path = storage_dir.join(format!("{}.tmp", hint_filename(data_filename)));
+    write(&temp_path, content)?;
+    rename(temp_path, path)
+}
+
+/// Reads a data file's hint: the number of bytes it covers and its entries
+/// None if the data file has no hint, or the hint can't be decoded and the data file must be scanned instead
+pub fn load(storage_dir: &Path, data_filename: &str) -> std::io::Result<Option<(u64, Vec<HintEntry>)>> {
+    let content = match read(storage_dir.join(hint_filename(data_filename))) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    Ok(decode(&content))
+}
+
+/// Removes a data file's hint, if it has one
+pub fn remove(storage_dir: &Path, data_filename: &str) -> std::io::Result<()> {
+    match remove_file(storage_dir.join(hint_filename(data_filename))) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn decode(content: &[u8]) -> Option<(u64, Vec<HintEntry>)> {
+    let covered_len = u64::from_le_bytes(content.get(0..8)?.try_into().ok()?);
+    let mut entries = Vec::new();
+    let mut position = 8;
+    while position < content.len() {
+        let header = content.get(position..position + HINT_ENTRY_HEADER_SIZE)?;
+        let key_size = u32::from_le_bytes(header[0..4].try_into().ok()?) as usize;
+        let record_type = RecordType::from_byte(header[26])?;
+        let key_start = position + HINT_ENTRY_HEADER_SIZE;
+        entries.push(HintEntry {
+            record_type,
+            key: content.get(key_start..key_start + key_size)?.to_vec(),
+            value_size: u32::from_le_bytes(header[4..8].try_into().ok()?),
+            value_offset: u64::from_le_bytes(header[8..16].try_into().ok()?),
+            crc: u16::from_le_bytes(header[16..18].try_into().ok()?),
+            timestamp: u64::from_le_bytes(header[18..26].try_into().ok()?),
+        });
+        position = key_start + key_size;
+    }
+    Some((covered_len, entries))
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::fs::{read_dir, read_to_string, write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::storage::StorageError;
+use crate::storage::naming::FileNaming;
+
+/// Name of the descriptor file kept in every store directory, see FileNaming::store_file for namespaced stores
+pub const META_FILENAME: &str = ".meta";
+
+/// On-disk format version written by this build
+pub const FORMAT_VERSION: u32 = 5;
+
+/// Byte order of the length prefixes in data files
+pub const ENDIANNESS: &str = "little";
+
+/// Checksum algorithm used for value CRCs (the CRC ending each record is always CRC-32)
+pub const CHECKSUM_ALGORITHM: &str = "crc16-ibm-sdlc";
+
+/// Oldest format version whose data files this build can still decode
+/// Version 1 marks tombstones with a marker value, version 2 adds the record-type byte, version 3 the timestamp,
+/// version 4 the compression bits in the high half of that byte, version 5 a CRC-32 of the whole record after the value
+pub const OLDEST_READABLE_VERSION: u32 = 1;
+
+/// Whether records carry a record-type byte
+pub const RECORD_TYPES: bool = true;
+
+/// Whether records carry their write timestamp
+pub const RECORD_TIMESTAMPS: bool = true;
+
+/// Store-wide format descriptor, persisted as TOML in the `.meta` file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreMeta {
+    pub format_version: u32,
+    pub endianness: String,
+    pub checksum: String,
+    pub record_types: bool,
+    #[serde(default)]
+    pub timestamps: bool,
+    /// Data files still in an older format, by filename, with the version they were written in
+    /// Files not listed use format_version. Entries disappear as merge rewrites or removes the files
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub file_versions: BTreeMap<String, u32>,
+}
+
+impl StoreMeta {
+    /// Describes the format written by this build
+    pub fn current() -> StoreMeta {
+        StoreMeta {
+            format_version: FORMAT_VERSION,
+            endianness: ENDIANNESS.to_string(),
+            checksum: CHECKSUM_ALGORITHM.to_string(),
+            record_types: RECORD_TYPES,
+            timestamps: RECORD_TIMESTAMPS,
+            file_versions: BTreeMap::new(),
+        }
+    }
+
+    /// Describes a store created before .meta files existed (format version 1)
+    pub fn legacy() -> StoreMeta {
+        StoreMeta {
+            format_version: 1,
+            endianness: ENDIANNESS.to_string(),
+            checksum: CHECKSUM_ALGORITHM.to_string(),
+            record_types: false,
+            timestamps: false,
+            file_versions: BTreeMap::new(),
+        }
+    }
+
+    /// Checks that a store described by this meta can be read by this build
+    pub fn validate(&self) -> Result<(), StorageError> {
+        if self.format_version != FORMAT_VERSION {
+            return Err(StorageError::IncompatibleFormat(format!(
+                "store uses format version {}, this build reads version {}",
+                self.format_version, FORMAT_VERSION
+            )));
+        }
+        if self.endianness != ENDIANNESS {
+            return Err(StorageError::IncompatibleFormat(format!(
+                "store uses {} endian length prefixes, expected {}", self.endianness, ENDIANNESS
+            )));
+        }
+        if self.checksum != CHECKSUM_ALGORITHM {
+            return Err(StorageError::IncompatibleFormat(format!(
+                "store uses checksum '{}', expected '{}'", self.checksum, CHECKSUM_ALGORITHM
+            )));
+        }
+        if self.record_types != RECORD_TYPES {
+            return Err(StorageError::IncompatibleFormat(format!(
+                "store {} record-type bytes, this build expects them {}",
+                if self.record_types { "uses" } else { "does not use" },
+                if RECORD_TYPES { "present" } else { "absent" }
+            )));
+        }
+        if self.timestamps != RECORD_TIMESTAMPS {
+            return Err(StorageError::IncompatibleFormat(format!(
+                "store {} record timestamps, this build expects them {}",
+                if self.timestamps { "uses" } else { "does not use" },
+                if RECORD_TIMESTAMPS { "present" } else { "absent" }
+            )));
+        }
+        for (filename, version) in &self.file_versions {
+            if !(OLDEST_READABLE_VERSION..FORMAT_VERSION).contains(version) {
+                return Err(StorageError::IncompatibleFormat(format!(
+                    "data file {} uses format version {}, this build reads versions {} to {}",
+                    filename, version, OLDEST_READABLE_VERSION, FORMAT_VERSION
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Upgrades a store written by an older version in place: its existing data files keep their old
+    /// format and are listed in file_versions, and everything written from now on uses the current format
+    fn upgrade(&mut self, storage_dir: &Path, naming: &FileNaming) -> std::io::Result<()> {
+        for filename in data_files(storage_dir, naming)? {
+            self.file_versions.entry(filename).or_insert(self.format_version);
+        }
+        let file_versions = std::mem::take(&mut self.file_versions);
+        *self = StoreMeta { file_versions, ..StoreMeta::current() };
+        Ok(())
+    }
+}
+
+/// Reads and validates the store's `.meta` file, writing one if the directory has none
+/// A directory that already holds data files but no .meta predates the descriptor and is recorded as format version 1.
+/// Stores in an older readable version are upgraded: their files are listed in file_versions and the meta is rewritten
+pub fn load_or_create(storage_dir: &Path, naming: &FileNaming) -> Result<StoreMeta, StorageError> {
+    let (meta, changed) = read_and_validate(storage_dir, naming)?;
+    if changed {
+        save(storage_dir, naming, &meta)?;
+    }
+    Ok(meta)
+}
+
+/// Reads and validates the store's `.meta` file like load_or_create, but never writes it
+/// A missing or outdated descriptor is only upgraded in memory, for read-only opens
+pub fn load(storage_dir: &Path, naming: &FileNaming) -> Result<StoreMeta, StorageError> {
+    Ok(read_and_validate(storage_dir, naming)?.0)
+}
+
+/// The store's meta, and whether it differs from what is on disk
+fn read_and_validate(storage_dir: &Path, naming: &FileNaming) -> Result<(StoreMeta, bool), StorageError> {
+    let meta_path = storage_dir.join(naming.store_file(META_FILENAME));
+
+    let mut changed = !meta_path.exists();
+    let mut meta = if meta_path.exists() {
+        let content = read_to_string(&meta_path)?;
+        toml::from_str(&content)
+            .map_err(|e| StorageError::CorruptedData(format!("Invalid {} file: {}", META_FILENAME, e)))?
+    } else if data_files(storage_dir, naming)?.is_empty() {
+        StoreMeta::current()
+    } else {
+        StoreMeta::legacy()
+    };
+
+    if (OLDEST_READABLE_VERSION..FORMAT_VERSION).contains(&meta.format_version) {
+        meta.upgrade(storage_dir, naming)?;
+        changed = true;
+    }
+    meta.validate()?;
+    Ok((meta, changed))
+}
+
+/// Writes the store's `.meta` file
+pub fn save(storage_dir: &Path, naming: &FileNaming, meta: &StoreMeta) -> Result<(), StorageError> {
+    let content = toml::to_string(meta)
+        .map_err(|e| StorageError::CorruptedData(format!("Failed to encode {}: {}", META_FILENAME, e)))?;
+    write(storage_dir.join(naming.store_file(META_FILENAME)), content)?;
+    Ok(())
+}
+
+/// Older-format data files listed in the directory's `.meta`, empty if it has none
+/// For offline tools that decode data files without opening a Storage
+pub fn file_versions(storage_dir: &Path, naming: &FileNaming) -> Result<BTreeMap<String, u32>, StorageError> {
+    let meta_path = storage_dir.join(naming.store_file(META_FILENAME));
+    if !meta_path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let meta: StoreMeta = toml::from_str(&read_to_string(meta_path)?)
+        .map_err(|e| StorageError::CorruptedData(format!("Invalid {} file: {}", META_FILENAME, e)))?;
+    Ok(meta.file_versions)
+}
+
+/// Non-empty data files in the directory with the default file names, in `naming`'s namespace if it has one
+/// Only stores that predate .meta are upgraded from this list, and those always used the default file names
+fn data_files(storage_dir: &Path, naming: &FileNaming) -> std::io::Result<Vec<String>> {
+    let naming = match &naming.namespace {
+        Some(namespace) => FileNaming::default().with_namespace(namespace).map_err(std::io::Error::other)?,
+        None => FileNaming::default(),
+    };
+    let mut data_files = Vec::new();
+    for entry in read_dir(storage_dir)? {
+        let entry = entry?;
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if naming.replay_order(&filename).is_some() && entry.metadata()?.len() > 0 {
+            data_files.push(filename);
+        }
+    }
+    Ok(data_files)
+}
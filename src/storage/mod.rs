@@ -1,3 +1,27 @@
+#[allow(clippy::module_inception)]
 pub mod storage;
+pub mod repair;
+pub mod meta;
+pub mod events;
+pub mod cache;
+pub mod deletes;
+pub mod hints;
+pub mod selftest;
+pub mod background;
+pub mod compression;
+pub mod naming;
+pub mod handles;
 
-pub use storage::{Storage, StorageError, TOMBSTONE_MARKER, HashTableTrait};
\ No newline at end of file
+pub use storage::{Storage, StorageError, GetOutcome, RemovedValue, WriteOp, MergeReport, MergePreview, MergeEstimate, SkippedRecord, RebuildReport, ExportReport, TruncateReport, StorageStats, RawRecord, RecordType, DurabilityMode, ExportFormat, DEFAULT_MAX_KEY_SIZE, DEFAULT_MAX_VALUE_SIZE, HashTableTrait};
+#[allow(deprecated)]
+pub use storage::TOMBSTONE_MARKER;
+pub use repair::RepairReport;
+pub use selftest::{SelfTestReport, SelfTestStep};
+pub use background::BackgroundMerge;
+pub use compression::Compression;
+pub use naming::FileNaming;
+pub use handles::{ReadHandleStats, MAX_READ_HANDLES};
+pub use meta::StoreMeta;
+pub use events::{StorageEvent, EventListener};
+pub use cache::{CachePolicy, LruPolicy, FifoPolicy, ValueCache, CacheStats, PinError, DEFAULT_MAX_PINNED_BYTES};
+pub use deletes::{DeleteRetry, FileRemover};
\ No newline at end of file
@@ -0,0 +1,105 @@
+/// Digits in a file counter: enough for any u32, so names sort lexicographically in the same order as their counters
+const COUNTER_WIDTH: usize = 10;
+
+/// How data and merge files are named: `<prefix><counter>.<extension>`, with the counter zero-padded to
+/// ten digits. Files written with a narrower counter (data_000.dat) are still recognised, since the counter
+/// is parsed as a number, so stores from earlier versions open unchanged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileNaming {
+    /// Prefix of files written by the store (default "data_")
+    pub data_prefix: String,
+    /// Prefix of files written by merge_inactive_files (default "merge_")
+    pub merge_prefix: String,
+    /// Extension of both, without the dot (default "dat")
+    pub extension: String,
+    /// Set by with_namespace; None for a store that has its directory to itself
+    pub namespace: Option<String>,
+}
+
+impl Default for FileNaming {
+    fn default() -> FileNaming {
+        FileNaming { data_prefix: "data_".to_string(), merge_prefix: "merge_".to_string(), extension: "dat".to_string(), namespace: None }
+    }
+}
+
+impl FileNaming {
+    /// Creates a naming scheme, rejecting one whose files could be confused with each other or with the
+    /// store's other files (.meta, hint files, temporary files)
+    pub fn new(data_prefix: &str, merge_prefix: &str, extension: &str) -> Result<FileNaming, String> {
+        for (name, part) in [("data prefix", data_prefix), ("merge prefix", merge_prefix), ("extension", extension)] {
+            if part.is_empty() || part.contains(['/', '\\']) {
+                return Err(format!("{} must be non-empty and contain no path separators", name));
+            }
+        }
+        if data_prefix.starts_with(merge_prefix) || merge_prefix.starts_with(data_prefix) {
+            return Err(format!("data prefix '{}' and merge prefix '{}' must not start with one another", data_prefix, merge_prefix));
+        }
+        if extension.contains('.') || ["hint", "tmp", "meta"].contains(&extension) {
+            return Err(format!("extension '{}' is reserved or contains a dot", extension));
+        }
+        Ok(FileNaming { data_prefix: data_prefix.to_string(), merge_prefix: merge_prefix.to_string(), extension: extension.to_string(), namespace: None })
+    }
+
+    /// Moves the store's files into a namespace, so stores in different namespaces can share a directory: both
+    /// prefixes gain `<namespace>_` (users_data_0000000000.dat) and the store's own files become
+    /// .<namespace>.meta, .<namespace>.index and so on (see store_file). A namespace holds only ASCII letters,
+    /// digits, '-' and '_', so no store's files can pass for another's
+    pub fn with_namespace(self, namespace: &str) -> Result<FileNaming, String> {
+        if namespace.is_empty() || !namespace.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+            return Err(format!("namespace '{}' must be non-empty and hold only ASCII letters, digits, '-' and '_'", namespace));
+        }
+        if let Some(existing) = &self.namespace {
+            return Err(format!("file naming is already in namespace '{}'", existing));
+        }
+        Ok(FileNaming {
+            data_prefix: format!("{}_{}", namespace, self.data_prefix),
+            merge_prefix: format!("{}_{}", namespace, self.merge_prefix),
+            namespace: Some(namespace.to_string()),
+            ..self
+        })
+    }
+
+    /// Name of one of the store's own files (e.g. meta::META_FILENAME, ".meta") in this naming's namespace:
+    /// unchanged without one, ".users.meta" in namespace users
+    pub fn store_file(&self, name: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => format!(".{}{}", namespace, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Name of the data file with the given counter
+    pub fn data_file(&self, counter: u32) -> String {
+        format!("{}{:0width$}.{}", self.data_prefix, counter, self.extension, width = COUNTER_WIDTH)
+    }
+
+    /// Name of the merge output file with the given counter
+    pub fn merge_file(&self, counter: u32) -> String {
+        format!("{}{:0width$}.{}", self.merge_prefix, counter, self.extension, width = COUNTER_WIDTH)
+    }
+
+    /// Parses the counter out of a data filename
+    pub fn data_counter(&self, filename: &str) -> Option<u32> {
+        self.counter(filename, &self.data_prefix)
+    }
+
+    /// Parses the counter out of a merge output filename
+    pub fn merge_counter(&self, filename: &str) -> Option<u32> {
+        self.counter(filename, &self.merge_prefix)
+    }
+
+    fn counter(&self, filename: &str, prefix: &str) -> Option<u32> {
+        let digits = filename.strip_prefix(prefix)?.strip_suffix(&self.extension)?.strip_suffix('.')?;
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    }
+
+    /// Where a data file falls when replaying the store, or None if `filename` isn't a data file
+    /// Merge output only ever holds records older than every remaining data file, so it replays first
+    pub fn replay_order(&self, filename: &str) -> Option<(u8, u32)> {
+        self.merge_counter(filename).map(|counter| (0, counter))
+            .or_else(|| self.data_counter(filename).map(|counter| (1, counter)))
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs::remove_dir_all;
+use std::io::ErrorKind;
+use std::path::Path;
+use crate::hash_table::HashTable;
+use crate::storage::compression::Compression;
+use crate::storage::naming::FileNaming;
+use crate::storage::storage::{DurabilityMode, GetOutcome, Storage};
+
+/// Subdirectory of the store that the self-test runs in; removed again when the test finishes
+pub const SELF_TEST_DIR: &str = ".selftest";
+
+/// Keys the self-test writes, with their values; the second one gets deleted
+const SELF_TEST_KEYS: [(&str, &str); 3] = [("selftest_a", "alpha"), ("selftest_b", "bravo"), ("selftest_c", "charlie")];
+
+/// Outcome of one step of the self-test
+#[derive(Debug, Clone)]
+pub struct SelfTestStep {
+    pub name: &'static str,
+    pub passed: bool,
+    /// What the step checked, or why it failed
+    pub detail: String,
+}
+
+/// Steps run by Storage::self_test, in order; the test stops at the first failed step
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    /// True if every step ran and passed
+    pub fn all_passed(&self) -> bool {
+        !self.steps.is_empty() && self.steps.iter().all(|step| step.passed)
+    }
+
+    /// Records a step's result; returns whether it passed
+    fn record(&mut self, name: &'static str, result: Result<String, String>) -> bool {
+        let passed = result.is_ok();
+        let detail = result.unwrap_or_else(|e| e);
+        self.steps.push(SelfTestStep { name, passed, detail });
+        passed
+    }
+}
+
+/// A report whose setup step failed with `reason`, for stores that can't host the scratch store
+pub(crate) fn refused(reason: String) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    report.record("setup", Err(reason));
+    report
+}
+
+/// Writes, reads, deletes and merges a few keys in a scratch store under `storage_dir`, checking the state after each step
+/// The scratch store is removed afterwards, so the real data files are never read or written
+pub fn run(storage_dir: &Path, naming: &FileNaming) -> SelfTestReport {
+    let dir = storage_dir.join(naming.store_file(SELF_TEST_DIR));
+    let mut report = SelfTestReport::default();
+
+    // A scratch store left by an interrupted self-test would skew the results
+    if report.record("setup", clear(&dir).map(|()| format!("scratch store in {}", dir.display()))) {
+        run_steps(&dir, &mut report);
+    }
+    report.record("cleanup", clear(&dir).map(|()| "scratch store removed".to_string()));
+    report
+}
+
+fn run_steps(dir: &Path, report: &mut SelfTestReport) {
+    let mut storage = match Storage::new_with_config(dir, 4096, DurabilityMode::None, Compression::None, FileNaming::default()) {
+        Ok(storage) => storage,
+        Err(e) => {
+            report.record("open", Err(format!("failed to open scratch store: {}", e)));
+            return;
+        }
+    };
+    let mut hash_table = HashTable::new_chaining(11);
+    let deleted = SELF_TEST_KEYS[1].0;
+
+    let written = SELF_TEST_KEYS.iter().try_for_each(|(key, value)| {
+        let location = storage.write(key, value).map_err(|e| format!("failed to write {}: {}", key, e))?;
+        hash_table.insert(key, location);
+        Ok(())
+    });
+    if !report.record("write", written.map(|()| format!("wrote {} keys", SELF_TEST_KEYS.len()))) {
+        return;
+    }
+
+    let read_back = expect_state(&mut storage, &hash_table, None);
+    if !report.record("read", read_back.map(|()| format!("read back {} keys", SELF_TEST_KEYS.len()))) {
+        return;
+    }
+
+    let deleted_state = storage.delete(deleted)
+        .map_err(|e| format!("failed to delete {}: {}", deleted, e))
+        .and_then(|location| {
+            hash_table.insert(deleted, location);
+            expect_state(&mut storage, &hash_table, Some(deleted))
+        });
+    if !report.record("delete", deleted_state.map(|()| format!("{} reads as deleted", deleted))) {
+        return;
+    }
+
+    // Compact rather than merge so that the active file, which holds every record, is covered too
+    let merged = storage.compact_all(&mut hash_table)
+        .map_err(|e| format!("merge failed: {}", e))
+        .and_then(|()| expect_state(&mut storage, &hash_table, Some(deleted)));
+    if !report.record("merge", merged.map(|()| "live keys kept, deleted key dropped".to_string())) {
+        return;
+    }
+
+    // The merged files alone must reproduce the same state, as they would after a restart
+    let mut rebuilt = hash_table.empty_clone();
+    let recovered = storage.rebuild_index(&mut rebuilt)
+        .map_err(|e| format!("failed to rebuild the index: {}", e))
+        .and_then(|report| match report.live_keys {
+            live if live == SELF_TEST_KEYS.len() - 1 => expect_state(&mut storage, &rebuilt, Some(deleted)),
+            live => Err(format!("rebuilt index has {} live keys, expected {}", live, SELF_TEST_KEYS.len() - 1)),
+        });
+    report.record("recover", recovered.map(|()| "index rebuilt from disk matches".to_string()));
+}
+
+/// Checks every self-test key reads back its value, except `deleted`, which must read as deleted or be gone
+fn expect_state(storage: &mut Storage, hash_table: &HashTable, deleted: Option<&str>) -> Result<(), String> {
+    for (key, value) in SELF_TEST_KEYS {
+        let outcome = storage.get_outcome(hash_table, key).map_err(|e| format!("failed to read {}: {}", key, e))?;
+        let expected_live = Some(key) != deleted;
+        match outcome {
+            GetOutcome::Found(found) if expected_live && found == value => {}
+            GetOutcome::Deleted | GetOutcome::Missing if !expected_live => {}
+            outcome => return Err(format!("{} read as {:?}", key, outcome)),
+        }
+    }
+    Ok(())
+}
+
+/// Removes the scratch store, if there is one
+fn clear(dir: &Path) -> Result<(), String> {
+    match remove_dir_all(dir) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(format!("failed to remove {}: {}", dir.display(), e)),
+        _ => Ok(()),
+    }
+}
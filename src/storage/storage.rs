@@ -1,26 +1,101 @@
 use std::fs::{File, OpenOptions, create_dir_all, read_dir, remove_file};
 use std::io::{Read, Write, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
-use crc::{Crc, CRC_16_IBM_SDLC};
-use crate::hash_table::FileLocation;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crc::{Crc, CRC_16_IBM_SDLC, CRC_32_ISO_HDLC};
+use crate::hash_table::{FileLocation, HashTable, INDEX_FILENAME};
+use crate::storage::meta;
+use crate::storage::events::{EventListener, StorageEvent};
+use crate::storage::cache::{PinError, ValueCache};
+use crate::storage::deletes::{self, DeleteRetry, FileRemover};
+use crate::storage::hints::{self, HintEntry};
+use crate::storage::selftest::{self, SelfTestReport};
+use crate::storage::background::{self, BackgroundMerge};
+use crate::storage::compression::Compression;
+use crate::storage::naming::FileNaming;
+use crate::storage::handles::{ReadHandles, ReadHandleStats};
 
-/// Trait for hash table operations needed during merge
+/// Trait for hash table operations needed during merge and lookups
 pub trait HashTableTrait {
     fn delete(&mut self, key: &str) -> bool;
     fn insert(&mut self, key: &str, location: FileLocation);
+    fn get(&self, key: &str) -> Option<&FileLocation>;
+    /// Looks a key up for the storage's own bookkeeping, which shouldn't show in lookup metrics; get by default
+    fn peek(&self, key: &str) -> Option<&FileLocation> {
+        self.get(key)
+    }
+    /// Every key in the table, in no particular order
+    /// Only scan_prefix, delete_prefix and recount_index_stats walk the keys. The default yields none, so to those
+    /// a table that doesn't override it looks empty: prefix scans and deletes find nothing and no bytes count as live
+    fn keys(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(std::iter::empty())
+    }
+}
+
+/// Value format version 1 wrote for deleted keys, still recognized when reading version 1 files
+/// Deletion is now tracked by RecordType, so this is an ordinary value that can be stored like any other
+const LEGACY_TOMBSTONE_MARKER: &str = "\\DELETED\\";
+
+/// The tombstone value of format version 1 files, kept public only for code written against that format
+#[deprecated(note = "deletes are marked by RecordType::Tombstone; this value only means deleted in format version 1 files")]
+pub const TOMBSTONE_MARKER: &str = LEGACY_TOMBSTONE_MARKER;
+
+/// Kind of record, stored after the length prefixes: the whole byte in format versions 2 and 3, the low
+/// four bits of the flags byte (next to the value's Compression) from version 4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    /// Key-value pair
+    Put = 0,
+    /// Deletion marker; the value region holds an optional reason
+    Tombstone = 1,
+}
+
+impl RecordType {
+    pub(crate) fn from_byte(byte: u8) -> Option<RecordType> {
+        match byte {
+            0 => Some(RecordType::Put),
+            1 => Some(RecordType::Tombstone),
+            _ => None,
+        }
+    }
 }
 
-/// Tombstone marker used to indicate deleted keys
-/// Using a special sequence that's unlikely to appear in normal data
-pub const TOMBSTONE_MARKER: &str = "\\DELETED\\";
+/// Size of the fixed record header: key_size, value_size, flags (record type and compression) and timestamp
+pub(crate) const RECORD_HEADER_SIZE: u64 = 17;
+
+/// Size of the CRC-32 ending every record from format version 5, covering its header, key and value
+pub(crate) const RECORD_CRC_SIZE: u64 = 4;
+
+/// Longest key accepted by writes unless configured otherwise
+pub const DEFAULT_MAX_KEY_SIZE: u64 = 64 * 1024;
+
+/// Longest key the u32 length prefix of a record can describe; set_size_limits rejects a larger max_key_size
+const KEY_SIZE_FORMAT_LIMIT: u64 = u32::MAX as u64;
+
+/// Longest value accepted by writes unless configured otherwise: the most the u32 length prefix can describe
+pub const DEFAULT_MAX_VALUE_SIZE: u64 = u32::MAX as u64;
 
 /// Custom error type for storage operations
 #[derive(Debug)]
 pub enum StorageError {
     Io(std::io::Error),
     KeyDeleted(String),
+    /// The key is not in the index, e.g. for a get_many key that was never written
+    KeyNotFound(String),
     CorruptedData(String),
+    IncompatibleFormat(String),
+    /// The data file a location points at no longer exists (e.g. removed by merge under a stale index)
+    FileMissing(String),
+    /// A write, delete, merge or rotation was attempted on a store opened with Storage::open_read_only
+    ReadOnly,
+    /// A write's key is longer than the store's max_key_size
+    KeyTooLarge { size: u64, max: u64 },
+    /// A write's value (or a delete's reason) is longer than the store's max_value_size
+    ValueTooLarge { size: u64, max: u64 },
+    /// update_in_place was given a value whose length differs from the stored one's
+    SizeMismatch { stored: u32, new: u64 },
 }
 
 impl std::fmt::Display for StorageError {
@@ -28,7 +103,14 @@ impl std::fmt::Display for StorageError {
         match self {
             StorageError::Io(e) => write!(f, "IO error: {}", e),
             StorageError::KeyDeleted(key) => write!(f, "Key '{}' has been deleted", key),
+            StorageError::KeyNotFound(key) => write!(f, "Key '{}' not found", key),
             StorageError::CorruptedData(msg) => write!(f, "Data corruption: {}", msg),
+            StorageError::IncompatibleFormat(msg) => write!(f, "Incompatible storage format: {}", msg),
+            StorageError::FileMissing(filename) => write!(f, "Data file '{}' does not exist", filename),
+            StorageError::ReadOnly => write!(f, "Storage was opened read-only"),
+            StorageError::KeyTooLarge { size, max } => write!(f, "Key is {} bytes, over the {} byte limit", size, max),
+            StorageError::ValueTooLarge { size, max } => write!(f, "Value is {} bytes, over the {} byte limit", size, max),
+            StorageError::SizeMismatch { stored, new } => write!(f, "New value is {} bytes but the stored one is {}", new, stored),
         }
     }
 }
@@ -36,14 +118,222 @@ impl std::fmt::Display for StorageError {
 impl std::error::Error for StorageError {}
 
 impl From<std::io::Error> for StorageError {
+    /// Unwraps the StorageErrors (such as ReadOnly) that io::Result-returning methods carry inside an io::Error
     fn from(error: std::io::Error) -> Self {
+        if error.get_ref().is_some_and(|inner| inner.is::<StorageError>()) {
+            return *error.into_inner()
+                .and_then(|inner| inner.downcast::<StorageError>().ok())
+                .expect("inner error was checked to be a StorageError");
+        }
         StorageError::Io(error)
     }
 }
 
+/// Result of a high-level key lookup through the index
+/// Distinguishes a deleted key (tombstone in the index) from one that was never written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GetOutcome {
+    /// Key is live, carries its current value
+    Found(String),
+    /// Key's latest record is a tombstone
+    Deleted,
+    /// Key is not present in the index
+    Missing,
+}
+
+/// The value a delete_returning_old removed, read just before its tombstone was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemovedValue {
+    /// Key was live, carries the value it held
+    Value(String),
+    /// Key was never written or already deleted
+    Absent,
+    /// Key had a value that couldn't be read (e.g. it fails its CRC), carries why; the key was deleted regardless
+    Unreadable(String),
+}
+
+/// Summary of a merge that rewrote live records out of inactive files
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    /// Files whose live records were rewritten and which were then removed
+    pub files_merged: Vec<String>,
+    /// Total size of the merged files
+    pub bytes_read: u64,
+    /// Bytes of live records re-appended elsewhere
+    pub bytes_rewritten: u64,
+    /// Records that were still referenced by the index and got rewritten
+    pub records_kept: usize,
+    /// Superseded records that were discarded
+    pub records_dropped: usize,
+    /// Merged files that could not be removed and were queued for deletion on the next merge or open
+    pub files_pending_delete: Vec<String>,
+    /// Records that failed their CRC and were left out, so their values are gone once their file is removed
+    pub skipped: Vec<SkippedRecord>,
+}
+
+/// A record that failed its CRC and was stepped over by a scan that resumed at the next intact record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRecord {
+    pub filename: String,
+    /// Offset of the damaged record
+    pub offset: u64,
+    /// Offset reading resumed at, None if no intact record follows it in its file
+    pub next: Option<u64>,
+}
+
+impl std::fmt::Display for SkippedRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let error = bad_checksum_error(&self.filename, self.offset);
+        match self.next {
+            Some(next) => write!(f, "{}; skipped {} bytes to the next intact record", error, next - self.offset),
+            None => write!(f, "{}; no intact record follows it", error),
+        }
+    }
+}
+
+/// What merge_inactive_files would keep and reclaim on the current data, see Storage::merge_preview
+#[derive(Debug, Clone, Default)]
+pub struct MergePreview {
+    /// Inactive files the merge would read and then remove
+    pub files: Vec<String>,
+    /// Records read from those files, superseded versions and tombstones included
+    pub records: usize,
+    /// Distinct keys among those records
+    pub unique_keys: usize,
+    /// Keys whose newest record is a Put, which the merge would rewrite
+    pub live_keys: usize,
+    /// Tombstone records read; none survive the merge
+    pub tombstones: usize,
+    /// Records failing their CRC, which the merge skips past, keeping the intact records after them
+    pub damaged_records: usize,
+    /// Size of the newest record of every live key, which the merge would rewrite
+    pub bytes_retained: u64,
+    /// Everything else in the files: superseded records, tombstones, damaged records and any undecodable tail
+    pub bytes_removed: u64,
+}
+
+/// Predicted cost of merge_inactive_files on the current data, see Storage::estimate_merge
+#[derive(Debug, Clone, Default)]
+pub struct MergeEstimate {
+    /// What the merge would read, rewrite and drop
+    pub report: MergeReport,
+    /// Sequential read throughput measured on the inactive files, in bytes per second
+    pub read_bytes_per_sec: f64,
+    /// Time to read and rewrite the reported bytes at the measured throughput
+    pub estimated_duration: Duration,
+}
+
+/// Summary of an index rebuild by rebuild_index or rebuild_index_from_hints
+#[derive(Debug, Clone, Default)]
+pub struct RebuildReport {
+    /// Live keys in the rebuilt index
+    pub live_keys: usize,
+    /// Data files whose hint was read instead of their records; always 0 for rebuild_index
+    pub hinted_files: usize,
+    /// Records failing their CRC, which were left out of the index
+    pub skipped: Vec<SkippedRecord>,
+}
+
+/// Summary of an export by export_to
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    /// Live key-value pairs written
+    pub records: usize,
+    /// Records failing their CRC, whose keys were exported at an older value or not at all
+    pub skipped: Vec<SkippedRecord>,
+}
+
+/// Summary of a point-in-time rollback by truncate_to
+#[derive(Debug, Clone, Default)]
+pub struct TruncateReport {
+    /// Files that lost records but still hold earlier ones, with the bytes of each that were cut, damaged records
+    /// included, as measured in the file's own format version
+    pub files_truncated: Vec<(String, u64)>,
+    /// Files that held only later records and were deleted
+    pub files_removed: Vec<String>,
+    /// Records written after the cutoff that were discarded
+    pub records_discarded: usize,
+    /// Records failing their CRC, whose write time can't be trusted: dropped if their file was rewritten, cut off
+    /// with the records after the cutoff if they followed it, and left in place otherwise
+    pub skipped: Vec<SkippedRecord>,
+    /// Live keys in the rebuilt index
+    pub live_keys: usize,
+}
+
+/// Data files on disk, as reported by Storage::stats
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    pub file_count: usize,
+    pub total_bytes: u64,
+    /// File new records are appended to
+    pub active_file: String,
+    /// Size in bytes of each data file, sorted by filename
+    pub per_file: Vec<(String, u64)>,
+}
+
+impl std::fmt::Display for StorageStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (filename, size) in &self.per_file {
+            let status = if *filename == self.active_file { " (ACTIVE)" } else { "" };
+            writeln!(f, "    {}: {} bytes{}", filename, size, status)?;
+        }
+        writeln!(f, "  Total: {} files, {} bytes", self.file_count, self.total_bytes)
+    }
+}
+
+/// One write in a Storage::write_transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOp<'a> {
+    /// Sets a key to a value
+    Put(&'a str, &'a str),
+    /// Writes a tombstone for a key
+    Delete(&'a str),
+}
+
+/// Output format for Storage::export_to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line: {"key":"...","value":"..."}
+    JsonLines,
+    /// One `key<TAB>value` pair per line
+    Text,
+}
+
+/// When Storage fsyncs appended records, trading write latency for durability
+/// Records are always flushed to the OS on write; only an fsync makes them survive a power loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurabilityMode {
+    /// Never fsync on write; data reaches disk on explicit sync calls or when the OS decides.
+    /// Fastest, but a crash of the machine can lose writes that already returned Ok
+    #[default]
+    None,
+    /// Fsync after every record. Nothing acknowledged is lost, but every write pays a full disk flush
+    EveryWrite,
+    /// Fsync when at least this long has passed since the last one, checked on each write and by sync_if_due.
+    /// Bounds the loss window to roughly the interval while amortizing flushes over many writes
+    Interval(Duration),
+}
+
+impl std::str::FromStr for DurabilityMode {
+    type Err = String;
+
+    /// Parses the config form: "none", "every_write" or "interval:<milliseconds>", ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        match lower.as_str() {
+            "none" => Ok(DurabilityMode::None),
+            "every_write" => Ok(DurabilityMode::EveryWrite),
+            _ => match lower.strip_prefix("interval:").map(str::parse::<u64>) {
+                Some(Ok(millis)) if millis > 0 => Ok(DurabilityMode::Interval(Duration::from_millis(millis))),
+                _ => Err(format!("unknown durability mode '{}' (expected none, every_write or interval:<milliseconds>)", s)),
+            },
+        }
+    }
+}
+
 /// File-based storage for key-value pairs with append-only semantics and file rotation
-/// Stores entries in format: [key_size:4][value_size:4][key][value]
-/// Creates new files when current file exceeds configurable size
+/// Stores entries in format: [key_size:4][value_size:4][flags:1][timestamp:8][key][value][crc32:4]
+/// Starts a new file when the next record would take the active one past max_file_size
 pub struct Storage {
     storage_dir: PathBuf,
     current_file: File,
@@ -51,96 +341,801 @@ pub struct Storage {
     current_file_size: u64,
     file_counter: u32,
     max_file_size: u64,
+    /// Receives StorageEvents, if registered
+    event_listener: Option<EventListener>,
+    /// Operations taking at least this long emit StorageEvent::SlowOp
+    slow_op_threshold: Option<Duration>,
+    /// Serves repeated read_value calls from memory, if enabled
+    value_cache: Option<ValueCache>,
+    /// Set when records were appended or overwritten in place since the last fsync
+    dirty: bool,
+    /// Inactive files update_in_place wrote to since the last fsync, which sync flushes along with the active file
+    unsynced_files: HashSet<String>,
+    /// Number of fsyncs actually performed by sync
+    sync_count: u64,
+    /// When appended records are fsynced
+    durability: DurabilityMode,
+    /// Time of the last fsync (or of opening the store), for DurabilityMode::Interval
+    last_sync: Instant,
+    /// Retry policy for removing merged files
+    delete_retry: DeleteRetry,
+    /// Replaces remove_file for merged files, if set
+    file_remover: Option<FileRemover>,
+    /// Merged files whose removal failed; they are ignored by reads and retried on the next merge or open
+    pending_deletes: Vec<String>,
+    /// Data files written in an older format version, mirrored from .meta
+    file_versions: BTreeMap<String, u32>,
+    /// Set by open_read_only: every operation that would modify the directory fails with StorageError::ReadOnly
+    read_only: bool,
+    /// Writes with a longer key fail with StorageError::KeyTooLarge
+    max_key_size: u64,
+    /// Writes with a longer value fail with StorageError::ValueTooLarge
+    max_value_size: u64,
+    /// Bytes of a partial trailing record cut off the active file when the store was opened
+    truncated_on_open: u64,
+    /// Compression applied to the values of new Put records
+    compression: Compression,
+    /// Names of the data and merge files this store reads and writes
+    naming: FileNaming,
+    /// Open handles that point reads (read_value, read, read_raw) reuse instead of opening the file each time
+    read_handles: ReadHandles,
+    /// Bytes of the records the index points at per data file, tombstones included; see index_location
+    live_bytes: HashMap<String, u64>,
+    /// Keys whose index entry points at a tombstone; they stay indexed until a merge drops them. See index_location
+    indexed_tombstones: HashSet<String>,
+    /// dead_ratio past which dead_space_merge_due reports a merge worthwhile; None never does
+    merge_dead_ratio: Option<f64>,
 }
 
 impl Storage {
     /// Creates a new storage instance with storage directory and default file size (512 bytes)
-    /// Files are named data_000.dat, data_001.dat, etc.
-    pub fn new<P: AsRef<Path>>(storage_dir: P) -> std::io::Result<Storage> {
-        Self::new_with_config(storage_dir, 512)
+    /// Files are named data_0000000000.dat, data_0000000001.dat, etc.
+    pub fn new<P: AsRef<Path>>(storage_dir: P) -> Result<Storage, StorageError> {
+        Self::new_with_config(storage_dir, 512, DurabilityMode::None, Compression::None, FileNaming::default())
     }
     
     /// Creates a new storage instance with configurable directory and file size
-    /// Files are named data_000.dat, data_001.dat, etc.
-    pub fn new_with_config<P: AsRef<Path>>(storage_dir: P, max_file_size: u64) -> std::io::Result<Storage> {
-        let storage_dir = storage_dir.as_ref().to_path_buf();
+    /// Files are named by `naming`: data_0000000000.dat, data_0000000001.dat, etc. by default. Stores whose naming
+    /// has different namespaces (FileNaming::with_namespace) share a directory without seeing each other's files
+    /// Fails with IncompatibleFormat if the directory's .meta was written by an unsupported format
+    /// Merged files left behind by an earlier run are removed now, or stay queued if still locked
+    /// An uncommitted record at the end of the active file, left by a write interrupted by a crash, is truncated away
+    /// `durability` decides when writes are fsynced: EveryWrite makes each write as slow as a disk flush
+    /// (often milliseconds) in exchange for losing nothing on power failure; None keeps writes at memory
+    /// speed but leaves them to explicit sync calls; Interval bounds the loss window at a fraction of the cost
+    /// `compression` applies to values written from now on; records already on disk keep whatever they were
+    /// written with, so files mixing compressed and uncompressed values stay readable
+    pub fn new_with_config<P: AsRef<Path>>(storage_dir: P, max_file_size: u64, durability: DurabilityMode, compression: Compression, naming: FileNaming) -> Result<Storage, StorageError> {
+        Self::open(storage_dir.as_ref(), max_file_size, durability, compression, naming, false)
+    }
+
+    /// Opens an existing store for queries only, with the newest data file as the current file
+    /// Nothing in the directory is created or modified: writes, deletes, merges, rotation and every other
+    /// operation that would change it fail with StorageError::ReadOnly (wrapped in an io::Error where the
+    /// method returns io::Result). Meant for analytics tools and backup verification
+    /// `naming` must match the scheme the store was written with, or its files won't be found
+    pub fn open_read_only<P: AsRef<Path>>(storage_dir: P, naming: FileNaming) -> Result<Storage, StorageError> {
+        Self::open(storage_dir.as_ref(), u64::MAX, DurabilityMode::None, Compression::None, naming, true)
+    }
+
+    fn open(storage_dir: &Path, max_file_size: u64, durability: DurabilityMode, compression: Compression, naming: FileNaming, read_only: bool) -> Result<Storage, StorageError> {
+        let storage_dir = storage_dir.to_path_buf();
         
-        // Create storage directory if it doesn't exist
-        create_dir_all(&storage_dir)?;
+        // Validate (or record) the on-disk format before touching any data file
+        let file_versions = if read_only {
+            meta::load(&storage_dir, &naming)?.file_versions
+        } else {
+            // Create storage directory if it doesn't exist
+            create_dir_all(&storage_dir)?;
+            meta::load_or_create(&storage_dir, &naming)?.file_versions
+        };
         
-        // Start with first file
-        let file_counter = 0;
-        let current_filename = format!("data_{:03}.dat", file_counter);
+        // Continue appending to the newest existing file, or start with the first one
+        let mut newest: Option<(u32, String)> = None;
+        for entry in read_dir(&storage_dir)? {
+            let filename = entry?.file_name().to_string_lossy().to_string();
+            if let Some(counter) = naming.data_counter(&filename)
+                && newest.as_ref().is_none_or(|(newest_counter, _)| counter > *newest_counter) {
+                newest = Some((counter, filename));
+            }
+        }
+        let (mut file_counter, mut current_filename) = newest.unwrap_or_else(|| (0, naming.data_file(0)));
+        // Never append current-format records to a file written in an older format
+        if !read_only && file_versions.contains_key(&current_filename) {
+            file_counter += 1;
+            current_filename = naming.data_file(file_counter);
+        }
         let file_path = storage_dir.join(&current_filename);
         
-        let current_file = OpenOptions::new()
-            .create(true)
-            .append(true)
+        let mut current_file = OpenOptions::new()
+            .create(!read_only)
+            .append(!read_only)
             .read(true)
             .open(&file_path)?;
+
+        // Appending after an uncommitted record left by an interrupted write would misplace every later record
+        let truncated_on_open = if read_only { 0 } else { truncate_partial_tail(&mut current_file)? };
             
         // Get current file size
         let current_file_size = current_file.metadata()?.len();
+
+        let pending_deletes = deletes::load(&storage_dir, &naming)?;
             
-        Ok(Storage { 
+        let mut storage = Storage { 
             storage_dir,
             current_file,
             current_filename,
             current_file_size,
             file_counter,
             max_file_size,
-        })
+            event_listener: None,
+            slow_op_threshold: None,
+            value_cache: None,
+            dirty: false,
+            unsynced_files: HashSet::new(),
+            sync_count: 0,
+            durability,
+            last_sync: Instant::now(),
+            delete_retry: DeleteRetry::default(),
+            file_remover: None,
+            pending_deletes,
+            file_versions,
+            read_only,
+            max_key_size: DEFAULT_MAX_KEY_SIZE,
+            max_value_size: DEFAULT_MAX_VALUE_SIZE,
+            truncated_on_open,
+            compression,
+            naming,
+            read_handles: ReadHandles::default(),
+            live_bytes: HashMap::new(),
+            indexed_tombstones: HashSet::new(),
+            merge_dead_ratio: None,
+        };
+        if !read_only {
+            storage.retry_pending_deletes()?;
+        }
+        Ok(storage)
+    }
+
+    /// Registers a callback that receives every StorageEvent, replacing any previous one
+    pub fn set_event_listener(&mut self, listener: EventListener) {
+        self.event_listener = Some(listener);
+    }
+
+    /// Sets the duration at or above which write, read_value and merge report StorageEvent::SlowOp
+    /// None disables slow-op reporting
+    pub fn set_slow_op_threshold(&mut self, threshold: Option<Duration>) {
+        self.slow_op_threshold = threshold;
+    }
+
+    /// Limits the key and value sizes accepted by write, write_bytes, write_reserving and delete_with_reason
+    /// Records already on disk are unaffected and merge rewrites them regardless. Fails, leaving the limits
+    /// unchanged, if either is over u32::MAX, the longest key or value a record's length prefixes can describe
+    pub fn set_size_limits(&mut self, max_key_size: u64, max_value_size: u64) -> Result<(), String> {
+        if max_key_size > KEY_SIZE_FORMAT_LIMIT {
+            return Err(format!("max_key_size {} is over the {} bytes a record can hold", max_key_size, KEY_SIZE_FORMAT_LIMIT));
+        }
+        if max_value_size > DEFAULT_MAX_VALUE_SIZE {
+            return Err(format!("max_value_size {} is over the {} bytes a record can hold", max_value_size, DEFAULT_MAX_VALUE_SIZE));
+        }
+        self.max_key_size = max_key_size;
+        self.max_value_size = max_value_size;
+        Ok(())
+    }
+
+    /// Fails with KeyTooLarge or ValueTooLarge, wrapped in an io::Error, before anything is written
+    fn check_record_size(&self, key: &str, value_len: usize) -> std::io::Result<()> {
+        let error = if key.len() as u64 > self.max_key_size {
+            StorageError::KeyTooLarge { size: key.len() as u64, max: self.max_key_size }
+        } else if value_len as u64 > self.max_value_size {
+            StorageError::ValueTooLarge { size: value_len as u64, max: self.max_value_size }
+        } else {
+            return Ok(());
+        };
+        Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, error))
+    }
+
+    /// Sets how often removing a merged file is retried before it is queued for later deletion
+    pub fn set_delete_retry(&mut self, retry: DeleteRetry) {
+        self.delete_retry = retry;
+    }
+
+    /// Replaces how merged files are removed, e.g. to simulate files held open by another process
+    /// None restores std::fs::remove_file
+    pub fn set_file_remover(&mut self, remover: Option<FileRemover>) {
+        self.file_remover = remover;
+    }
+
+    /// Merged files still waiting to be removed
+    pub fn pending_deletes(&self) -> &[String] {
+        &self.pending_deletes
+    }
+
+    /// Tries once more to remove every file queued for deletion, keeping the ones that still fail
+    /// Returns the number of files removed
+    pub fn retry_pending_deletes(&mut self) -> std::io::Result<usize> {
+        self.check_writable()?;
+        if self.pending_deletes.is_empty() {
+            return Ok(0);
+        }
+        let queued = std::mem::take(&mut self.pending_deletes);
+        let queued_count = queued.len();
+        for filename in queued {
+            if self.try_remove(&filename).is_err() {
+                self.pending_deletes.push(filename);
+                continue;
+            }
+            self.forget_file(&filename)?;
+        }
+        deletes::save(&self.storage_dir, &self.naming, &self.pending_deletes)?;
+        Ok(queued_count - self.pending_deletes.len())
+    }
+
+    /// Removes a merged or pruned data file, retrying with backoff; if every attempt fails the file is
+    /// queued for deletion instead, so a locked file never aborts the merge that produced it.
+    /// Returns false if the file was queued
+    fn remove_data_file(&mut self, filename: &str) -> std::io::Result<bool> {
+        let mut backoff = self.delete_retry.initial_backoff;
+        for attempt in 1..=self.delete_retry.attempts.max(1) {
+            if self.try_remove(filename).is_ok() {
+                self.forget_file(filename)?;
+                return Ok(true);
+            }
+            if attempt < self.delete_retry.attempts {
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+        }
+        self.pending_deletes.push(filename.to_string());
+        deletes::save(&self.storage_dir, &self.naming, &self.pending_deletes)?;
+        Ok(false)
+    }
+
+    /// Format version a data file was written in
+    fn file_version(&self, filename: &str) -> u32 {
+        self.file_versions.get(filename).copied().unwrap_or(meta::FORMAT_VERSION)
+    }
+
+    /// Drops what the hint file and .meta record about a data file once it has been removed or rewritten
+    fn forget_file(&mut self, filename: &str) -> std::io::Result<()> {
+        hints::remove(&self.storage_dir, filename)?;
+        if self.file_versions.remove(filename).is_none() {
+            return Ok(());
+        }
+        let store_meta = meta::StoreMeta { file_versions: self.file_versions.clone(), ..meta::StoreMeta::current() };
+        meta::save(&self.storage_dir, &self.naming, &store_meta).map_err(into_io_error)
+    }
+
+    /// Removes a data file through the configured remover; a file that is already gone counts as removed
+    fn try_remove(&mut self, filename: &str) -> std::io::Result<()> {
+        self.read_handles.evict(filename);
+        let path = self.storage_dir.join(filename);
+        let result = match self.file_remover.as_mut() {
+            Some(remover) => remover(&path),
+            None => remove_file(&path),
+        };
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Installs a value cache consulted by read_value, replacing any previous one
+    /// None disables caching
+    pub fn set_value_cache(&mut self, cache: Option<ValueCache>) {
+        self.value_cache = cache;
+    }
+
+    /// The installed value cache, if any, for reading its stats
+    pub fn value_cache(&self) -> Option<&ValueCache> {
+        self.value_cache.as_ref()
+    }
+
+    /// The installed value cache, if any, for changing its settings (e.g. the pin budget) in place
+    pub fn value_cache_mut(&mut self) -> Option<&mut ValueCache> {
+        self.value_cache.as_mut()
+    }
+
+    /// Keeps a key's cached value from being evicted, e.g. for a known-hot key
+    /// The value must already be cached (read it first) and fit the cache's pinned-byte budget
+    pub fn pin_key(&mut self, key: &str) -> Result<(), PinError> {
+        self.value_cache.as_mut().ok_or(PinError::CacheDisabled)?.pin(key)
+    }
+
+    /// Lets a pinned key be evicted again; returns false if it was not pinned
+    pub fn unpin_key(&mut self, key: &str) -> bool {
+        self.value_cache.as_mut().is_some_and(|cache| cache.unpin(key))
+    }
+
+    /// Emits a SlowOp event if the operation started at `start` exceeded the threshold
+    fn report_if_slow(&mut self, op: &'static str, start: Instant, key: Option<&str>) {
+        let duration = start.elapsed();
+        let is_slow = self.slow_op_threshold.is_some_and(|threshold| duration >= threshold);
+        if let (true, Some(listener)) = (is_slow, self.event_listener.as_mut()) {
+            listener(&StorageEvent::SlowOp { op, duration, key: key.map(String::from) });
+        }
     }
 
     /// Writes a key-value pair to storage and returns the FileLocation
-    /// Format: [key_size: 4 bytes][value_size: 4 bytes][key: key_size bytes][value: value_size bytes]
-    /// Rotates to new file if current file would exceed 512 bytes
-    /// filename, value_offset, value_size, crc
-    pub fn write(&mut self, key: &str, value: &str) -> std::io::Result<(String, u64, u32, u16)> {
+    /// Format: [key_size: 4 bytes][value_size: 4 bytes][flags: 1 byte][timestamp: 8 bytes][key: key_size bytes][value: value_size bytes][crc32: 4 bytes]
+    /// Rotates to a new file first if the record would take the active file past max_file_size (see needs_rotation)
+    /// The returned location carries the record's persisted timestamp and is ready to insert into the index
+    pub fn write(&mut self, key: &str, value: &str) -> std::io::Result<FileLocation> {
+        self.write_bytes(key, value.as_bytes())
+    }
+
+    /// Writes a key with an arbitrary binary value; see write
+    pub fn write_bytes(&mut self, key: &str, value: &[u8]) -> std::io::Result<FileLocation> {
+        self.check_record_size(key, value.len())?;
+        let start = Instant::now();
+        let (compression, stored) = self.compression.encode(value);
+        let result = self.append_record(key, &stored, RecordType::Put, compression, now_nanos());
+        self.report_if_slow("write", start, Some(key));
+        result
+    }
+
+    /// Appends a record to the active file, rotating first if it would exceed max_file_size (see needs_rotation)
+    /// `value_bytes` are stored as given, already compressed with `compression`
+    fn append_record(&mut self, key: &str, value_bytes: &[u8], record_type: RecordType, compression: Compression, timestamp: u64) -> std::io::Result<FileLocation> {
+        self.check_writable()?;
         // Calculate size of entry to be written
         let key_bytes = key.as_bytes();
-        let value_bytes = value.as_bytes();
-        let entry_size = 8 + key_bytes.len() + value_bytes.len(); // 4 + 4 + key + value
+        let entry_size = record_size(key_bytes.len(), value_bytes.len()); // 4 + 4 + 1 + 8 + key + value + 4
         
         // Check if we need to rotate to a new file
-        if self.current_file_size + entry_size as u64 > self.max_file_size {
+        if self.needs_rotation(0, entry_size) {
             self.rotate_file()?;
         }
         
         // Get current file position (this will be our record start offset)
         let record_start = self.current_file.seek(SeekFrom::End(0))?;
         
-        // Prepare data to write
-        let key_size = key_bytes.len() as u32;
+        // Any cached value for this key is superseded by the new record
+        if let Some(cache) = self.value_cache.as_mut() {
+            cache.invalidate(key);
+        }
+
+        // Write in order: key_size, value_size, record_type, timestamp, key, value, crc
         let value_size = value_bytes.len() as u32;
-        
-        // Write in order: key_size, value_size, key, value
-        self.current_file.write_all(&key_size.to_le_bytes())?;
-        self.current_file.write_all(&value_size.to_le_bytes())?;
-        self.current_file.write_all(key_bytes)?;
-        self.current_file.write_all(value_bytes)?;
+        self.current_file.write_all(&encode_record(record_type, compression, timestamp, key_bytes, value_bytes))?;
         self.current_file.flush()?;
+        self.dirty = true;
+        match self.durability {
+            DurabilityMode::EveryWrite => self.sync()?,
+            DurabilityMode::Interval(_) => {
+                self.sync_if_due()?;
+            }
+            DurabilityMode::None => {}
+        }
         
         // Update current file size
-        self.current_file_size += entry_size as u64;
+        self.current_file_size += entry_size;
 
-        // Calculate value offset: record_start + key_size + value_size + key_bytes
-        let value_offset = record_start + 4 + 4 + key_bytes.len() as u64;
+        // Calculate value offset: record_start + header + key_bytes
+        let value_offset = record_start + RECORD_HEADER_SIZE + key_bytes.len() as u64;
 
-        const X25: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_SDLC);
-        Ok((self.current_filename.clone(), value_offset, value_size, X25.checksum(value_bytes)))
+        let mut location = FileLocation::new(self.current_filename.clone(), value_size, value_offset, value_crc(value_bytes));
+        location.timestamp = timestamp;
+        Ok(location)
+    }
+
+    /// Writes every entry with one write (and under EveryWrite, one fsync) per data file instead of one per record,
+    /// returning their locations in order. Rotation is still decided per record, so a batch that crosses
+    /// max_file_size splits across files exactly as the same writes one at a time would.
+    /// Every entry is checked against the size limits first; if one fails, nothing is written
+    pub fn write_batch(&mut self, entries: &[(&str, &str)]) -> std::io::Result<Vec<FileLocation>> {
+        self.check_writable()?;
+        for (key, value) in entries {
+            self.check_record_size(key, value.len())?;
+        }
+        let start = Instant::now();
+        let mut pending = Vec::new();
+        let mut locations = Vec::with_capacity(entries.len());
+
+        for (key, value) in entries {
+            let timestamp = now_nanos();
+            let (compression, stored) = self.compression.encode(value.as_bytes());
+            let record = encode_record(RecordType::Put, compression, timestamp, key.as_bytes(), &stored);
+            if self.needs_rotation(pending.len() as u64, record.len() as u64) {
+                self.flush_batch(&mut pending)?;
+                self.rotate_file()?;
+            }
+
+            if let Some(cache) = self.value_cache.as_mut() {
+                cache.invalidate(key);
+            }
+            let value_offset = self.current_file_size + pending.len() as u64 + RECORD_HEADER_SIZE + key.len() as u64;
+            let mut location = FileLocation::new(self.current_filename.clone(), stored.len() as u32, value_offset, value_crc(&stored));
+            location.timestamp = timestamp;
+            locations.push(location);
+            pending.extend_from_slice(&record);
+        }
+        self.flush_batch(&mut pending)?;
+
+        self.report_if_slow("write_batch", start, None);
+        Ok(locations)
+    }
+
+    /// Appends the records buffered by write_batch or write_transaction to the active file, syncing as the durability mode requires
+    fn flush_batch(&mut self, pending: &mut Vec<u8>) -> std::io::Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        self.current_file.write_all(pending)?;
+        self.current_file_size += pending.len() as u64;
+        pending.clear();
+        self.dirty = true;
+        match self.durability {
+            DurabilityMode::EveryWrite => self.sync()?,
+            DurabilityMode::Interval(_) => {
+                self.sync_if_due()?;
+            }
+            DurabilityMode::None => {}
+        }
+        Ok(())
+    }
+
+    /// Writes every operation or none of them, returning their locations (tombstones for deletes) in order
+    /// The records go to a single data file in one write, rotating first if they don't fit in the active file.
+    /// If the write or its sync fails, the file is truncated back to where the transaction started, so none of it
+    /// is visible; a machine crash mid-write can still leave a prefix on disk. Size limits are checked before
+    /// anything is written
+    pub fn write_transaction(&mut self, ops: &[WriteOp]) -> std::io::Result<Vec<FileLocation>> {
+        self.check_writable()?;
+        for op in ops {
+            match op {
+                WriteOp::Put(key, value) => self.check_record_size(key, value.len())?,
+                WriteOp::Delete(key) => self.check_record_size(key, 0)?,
+            }
+        }
+        let start = Instant::now();
+        let mut pending = Vec::new();
+        let mut locations = Vec::with_capacity(ops.len());
+        for op in ops {
+            let timestamp = now_nanos();
+            let (key, record_type, (compression, stored)) = match op {
+                WriteOp::Put(key, value) => (key, RecordType::Put, self.compression.encode(value.as_bytes())),
+                WriteOp::Delete(key) => (key, RecordType::Tombstone, (Compression::None, Vec::new())),
+            };
+            let value_offset = pending.len() as u64 + RECORD_HEADER_SIZE + key.len() as u64;
+            let mut location = FileLocation::new(String::new(), stored.len() as u32, value_offset, value_crc(&stored));
+            location.timestamp = timestamp;
+            locations.push(location);
+            pending.extend_from_slice(&encode_record(record_type, compression, timestamp, key.as_bytes(), &stored));
+        }
+
+        // Like a single record, a transaction bigger than max_file_size gets a fresh file to itself
+        if self.needs_rotation(0, pending.len() as u64) {
+            self.rotate_file()?;
+        }
+        let transaction_start = self.current_file_size;
+        if let Err(e) = self.flush_batch(&mut pending) {
+            self.current_file.set_len(transaction_start)?;
+            self.current_file_size = transaction_start;
+            return Err(e);
+        }
+
+        for (op, location) in ops.iter().zip(locations.iter_mut()) {
+            let (WriteOp::Put(key, _) | WriteOp::Delete(key)) = op;
+            if let Some(cache) = self.value_cache.as_mut() {
+                cache.invalidate(key);
+            }
+            location.filename = self.current_filename.clone();
+            location.value_offset += transaction_start;
+        }
+        self.report_if_slow("write_transaction", start, None);
+        Ok(locations)
+    }
+
+    /// Writes a key-value pair, guaranteeing the record is stored contiguously from the start of a file
+    /// whenever it doesn't fit in the active file's remaining space
+    /// Records larger than max_file_size get a fresh file to themselves instead of straddling rotation.
+    /// Space is reserved for the value uncompressed, so a value that would only fit once compressed rotates too
+    pub fn write_reserving(&mut self, key: &str, value: &str) -> std::io::Result<FileLocation> {
+        self.check_record_size(key, value.len())?;
+        if self.needs_rotation(0, record_size(key.len(), value.len())) {
+            self.rotate_file()?;
+        }
+        self.write(key, value)
+    }
+
+    /// Whether a record of `record_len` bytes, appended after `buffered` bytes not yet written to the active file,
+    /// must go to a fresh file instead because it would take the file past max_file_size.
+    /// An empty active file takes any record, even one larger than max_file_size: such a record used to rotate
+    /// first, leaving an empty data file behind, and since write_reserving it is written to the empty file instead
+    fn needs_rotation(&self, buffered: u64, record_len: u64) -> bool {
+        let file_size = self.current_file_size + buffered;
+        file_size > 0 && file_size + record_len > self.max_file_size
+    }
+
+    /// Overwrites the value `key` is indexed at in `hash_table`, instead of appending a record, when the new value has
+    /// exactly the stored value's length; the record's timestamp is rewritten too, which is safe because the index
+    /// only points at a key's newest record. Indexes and returns the new location, with the new value's CRC. This gives up the log's append-only guarantee: the old value is destroyed rather
+    /// than superseded, and a crash mid-write can leave a value that is part old, part new, which only the record
+    /// CRC of a format version 5 file reveals (as corruption). Fails with KeyNotFound for a key that isn't indexed,
+    /// KeyDeleted for a tombstone,
+    /// IncompatibleFormat for a compressed value, whose stored bytes aren't the value itself, and SizeMismatch
+    /// for any other length; callers fall back to write.
+    /// The file's hint and the saved index no longer describe it, so both are removed
+    pub fn update_in_place<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, new_value: &[u8]) -> Result<FileLocation, StorageError> {
+        self.check_writable()?;
+        let location = hash_table.peek(key).cloned().ok_or_else(|| StorageError::KeyNotFound(key.to_string()))?;
+        // Also checks that the record there belongs to `key`
+        let (_, record_type, compression, _) = self.read_record_header(&location.filename, location.value_offset, key)?;
+        if record_type == RecordType::Tombstone {
+            return Err(StorageError::KeyDeleted(key.to_string()));
+        }
+        if compression != Compression::None {
+            return Err(StorageError::IncompatibleFormat(format!(
+                "the value of '{}' is stored {:?}-compressed, so it can't be overwritten in place", key, compression
+            )));
+        }
+        if new_value.len() as u64 != location.value_size as u64 {
+            return Err(StorageError::SizeMismatch { stored: location.value_size, new: new_value.len() as u64 });
+        }
+
+        let mut file = OpenOptions::new().write(true).open(self.storage_dir.join(&location.filename))
+            .map_err(|e| missing_file_error(e, &location.filename))?;
+        let timestamp = now_nanos();
+        let format_version = self.file_version(&location.filename);
+        let header_size = record_header_size(format_version);
+        let record_offset = location.value_offset - key.len() as u64 - header_size;
+        if record_crc_size(format_version) > 0 {
+            // The record's CRC covers the value and timestamp, so the whole record is rewritten with a new one
+            file.seek(SeekFrom::Start(record_offset))?;
+            file.write_all(&encode_record(RecordType::Put, Compression::None, timestamp, key.as_bytes(), new_value))?;
+        } else {
+            file.seek(SeekFrom::Start(location.value_offset))?;
+            file.write_all(new_value)?;
+            if header_size == RECORD_HEADER_SIZE {
+                // The timestamp follows key_size, value_size and the flags byte
+                file.seek(SeekFrom::Start(record_offset + 9))?;
+                file.write_all(&timestamp.to_le_bytes())?;
+            }
+        }
+        if self.durability == DurabilityMode::None {
+            // The idle sync only flushes the active file unless told about this one
+            if location.filename != self.current_filename {
+                self.unsynced_files.insert(location.filename.clone());
+            }
+            self.dirty = true;
+        } else {
+            file.sync_data()?;
+        }
+
+        if let Some(cache) = self.value_cache.as_mut() {
+            cache.invalidate(key);
+        }
+        hints::remove(&self.storage_dir, &location.filename)?;
+        match remove_file(self.storage_dir.join(self.naming.store_file(INDEX_FILENAME))) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        let updated = FileLocation {
+            crc: value_crc(new_value),
+            timestamp: if header_size == RECORD_HEADER_SIZE { timestamp } else { location.timestamp },
+            ..location
+        };
+        self.index_location(hash_table, key, updated.clone());
+        Ok(updated)
     }
 
     /// Marks a key as deleted by writing a tombstone entry
     /// Returns the FileLocation of the tombstone
-    pub fn delete(&mut self, key: &str) -> std::io::Result<(String, u64, u32, u16)> {
-        self.write(key, TOMBSTONE_MARKER)
+    pub fn delete(&mut self, key: &str) -> std::io::Result<FileLocation> {
+        self.delete_with_reason(key, "")
+    }
+
+    /// Marks a key as deleted, recording why in the tombstone's value region
+    /// The reason stays readable through delete_reason until merge discards the tombstone
+    pub fn delete_with_reason(&mut self, key: &str, reason: &str) -> std::io::Result<FileLocation> {
+        self.check_record_size(key, reason.len())?;
+        let start = Instant::now();
+        let result = self.append_record(key, reason.as_bytes(), RecordType::Tombstone, Compression::None, now_nanos());
+        self.report_if_slow("write", start, Some(key));
+        result
+    }
+
+    /// Returns the deletion reason if the key's latest record is a tombstone
+    /// Ok(None) means the key is live or unknown; an empty string means it was deleted without a reason
+    pub fn delete_reason<T: HashTableTrait>(&self, hash_table: &T, key: &str) -> Result<Option<String>, StorageError> {
+        let location = match hash_table.get(key) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let header_size = record_header_size(self.file_version(&location.filename));
+        if location.value_offset < header_size + key.len() as u64 {
+            return Err(StorageError::CorruptedData(format!("Invalid value offset for key '{}'", key)));
+        }
+
+        let record_offset = location.value_offset - header_size - key.len() as u64;
+        match self.read_value_at_record(&location.filename, record_offset) {
+            Ok(_) => Ok(None),
+            Err(StorageError::KeyDeleted(_)) => {
+                let bytes = self.read_raw(&location.filename, location.value_offset, Some(location.value_size as u64))?;
+                let reason = String::from_utf8(bytes)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                Ok(Some(reason))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Forces the active file's contents to disk (fsync), along with any inactive file update_in_place wrote to
+    /// write only flushes to the OS, so records are not durable across power loss until this is called.
+    /// A no-op when nothing was written since the last sync, so callers may sync as often as they like
+    pub fn sync(&mut self) -> std::io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        for filename in std::mem::take(&mut self.unsynced_files) {
+            // A merge may have removed the file since, taking the overwritten value with it
+            match File::open(self.storage_dir.join(&filename)) {
+                Ok(file) => file.sync_data()?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.current_file.sync_data()?;
+        self.dirty = false;
+        self.sync_count += 1;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Syncs if the durability mode is Interval and the interval has elapsed since the last fsync
+    /// Call periodically when idle so the last writes of a burst don't wait for the next write. Returns whether it synced
+    pub fn sync_if_due(&mut self) -> std::io::Result<bool> {
+        match self.durability {
+            DurabilityMode::Interval(interval) if self.dirty && self.last_sync.elapsed() >= interval => {
+                self.sync()?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// When this store fsyncs writes
+    pub fn durability(&self) -> DurabilityMode {
+        self.durability
+    }
+
+    /// Number of fsyncs sync has actually performed
+    pub fn sync_count(&self) -> u64 {
+        self.sync_count
+    }
+
+    /// Moves every file of the store (see store_files) into `new_dir` and continues running against it
+    /// Files are renamed when both directories share a filesystem, otherwise copied then deleted.
+    /// Index locations only hold filenames, so they remain valid. Fails without moving anything if
+    /// `new_dir` already holds data files. If a move fails partway, the files already moved are moved
+    /// back, so the store is left whole in its old directory
+    pub fn relocate<P: AsRef<Path>>(&mut self, new_dir: P) -> std::io::Result<()> {
+        self.check_writable()?;
+        let new_dir = new_dir.as_ref().to_path_buf();
+        self.prepare_empty_dir(&new_dir)?;
+
+        self.sync()?;
+        self.read_handles.clear();
+        let mut moved = Vec::new();
+        for filename in self.store_files()? {
+            if let Err(e) = move_file(&self.storage_dir.join(&filename), &new_dir.join(&filename)) {
+                return Err(move_back(&new_dir, &self.storage_dir, &moved, e));
+            }
+            moved.push(filename);
+        }
+
+        let old_dir = std::mem::replace(&mut self.storage_dir, new_dir);
+        if let Err(e) = self.open_active_file(self.file_counter) {
+            let new_dir = std::mem::replace(&mut self.storage_dir, old_dir);
+            return Err(move_back(&new_dir, &self.storage_dir, &moved, e));
+        }
+        Ok(())
+    }
+
+    /// Copies a point-in-time image of the store into `dest` while it keeps running: the active file is synced,
+    /// then every file of the store (see store_files) is copied. Each copy is written under a
+    /// temporary name, synced and renamed into place, so `dest` never holds a partly copied file under its real
+    /// name and opens like any other store. Writes made after this returns aren't included. Works on read-only
+    /// stores; fails without copying anything if `dest` already holds data files. Returns the files copied
+    pub fn snapshot<P: AsRef<Path>>(&mut self, dest: P) -> std::io::Result<Vec<String>> {
+        let dest = dest.as_ref();
+        self.prepare_empty_dir(dest)?;
+
+        self.sync()?;
+        let filenames = self.store_files()?;
+        for filename in &filenames {
+            let temp_path = dest.join(format!("{}.tmp", filename));
+            std::fs::copy(self.storage_dir.join(filename), &temp_path)?;
+            File::open(&temp_path)?.sync_all()?;
+            std::fs::rename(&temp_path, dest.join(filename))?;
+        }
+        Ok(filenames)
+    }
+
+    /// Every file that makes up the store: the data files oldest first, merged files still pending deletion, then
+    /// their hint files, the .meta descriptor and the pending-delete list
+    fn store_files(&self) -> std::io::Result<Vec<String>> {
+        let mut filenames = self.data_files()?;
+        filenames.extend(self.pending_deletes.iter().filter(|filename| self.storage_dir.join(filename).exists()).cloned());
+        let hint_files: Vec<String> = filenames.iter()
+            .map(|filename| hints::hint_filename(filename))
+            .filter(|hint| self.storage_dir.join(hint).exists())
+            .collect();
+        filenames.extend(hint_files);
+        let meta_filename = self.naming.store_file(meta::META_FILENAME);
+        if self.storage_dir.join(&meta_filename).exists() {
+            filenames.push(meta_filename);
+        }
+        let pending_filename = self.naming.store_file(deletes::PENDING_DELETES_FILENAME);
+        if self.storage_dir.join(&pending_filename).exists() {
+            filenames.push(pending_filename);
+        }
+        Ok(filenames)
+    }
+
+    /// Creates `dir` if needed, failing with AlreadyExists if it already holds data files
+    fn prepare_empty_dir(&self, dir: &Path) -> std::io::Result<()> {
+        create_dir_all(dir)?;
+        for entry in read_dir(dir)? {
+            if self.naming.replay_order(&entry?.file_name().to_string_lossy()).is_some() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already contains data files", dir.display()),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes of an uncommitted trailing record (left by a write interrupted by a crash: partial, or complete in length
+    /// but failing its CRC) that opening the store cut off the end of the active file, or 0 if its last record was
+    /// committed. Read-only stores are never truncated
+    pub fn truncated_on_open(&self) -> u64 {
+        self.truncated_on_open
+    }
+
+    /// Name of the data file new records are appended to
+    pub fn active_filename(&self) -> &str {
+        &self.current_filename
     }
 
-    /// Rotates to a new storage file
-    fn rotate_file(&mut self) -> std::io::Result<()> {
-        self.file_counter += 1;
-        self.current_filename = format!("data_{:03}.dat", self.file_counter);
+    /// Compression applied to newly written values
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// True if the store was opened with open_read_only
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fails with StorageError::ReadOnly, wrapped in an io::Error, if the store was opened with open_read_only
+    fn check_writable(&self) -> std::io::Result<()> {
+        if self.read_only {
+            return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, StorageError::ReadOnly));
+        }
+        Ok(())
+    }
+
+    /// Rotates to a new storage file, first syncing the outgoing one if it holds unsynced records: sync only
+    /// reaches the active file, so they would otherwise never be synced and the Interval bound wouldn't hold
+    pub(crate) fn rotate_file(&mut self) -> std::io::Result<()> {
+        self.check_writable()?;
+        self.sync()?;
+        self.open_active_file(self.file_counter + 1)
+    }
+
+    /// Makes the data file numbered `file_counter` the active file, creating it if needed and appending after any
+    /// existing records. An older-format file is never reopened for appending; the next counter is used instead
+    fn open_active_file(&mut self, mut file_counter: u32) -> std::io::Result<()> {
+        let mut filename = self.data_filename(file_counter)?;
+        if self.file_versions.contains_key(&filename) {
+            file_counter += 1;
+            filename = self.data_filename(file_counter)?;
+        }
+        self.file_counter = file_counter;
+        self.current_filename = filename;
         let file_path = self.storage_dir.join(&self.current_filename);
         
         self.current_file = OpenOptions::new()
@@ -149,250 +1144,2127 @@ impl Storage {
             .read(true)
             .open(&file_path)?;
             
-        self.current_file_size = 0;
+        self.current_file_size = self.current_file.metadata()?.len();
         Ok(())
     }
 
     /// Reads a key-value pair from the specified file at the given byte offset
     /// Returns (key, value) if successful, or error if key is deleted
     pub fn read(&mut self, filename: &str, offset: u64) -> Result<(String, String), StorageError> {
-        let file_path = self.storage_dir.join(filename);
-        let mut file = OpenOptions::new()
-            .read(true)
-            .open(&file_path)?;
-        
-        // Seek to the offset
-        file.seek(SeekFrom::Start(offset))?;
-        
-        // Read key_size and value_size (4 bytes each)
-        let mut size_buf = [0u8; 4];
-        file.read_exact(&mut size_buf)?;
-        let key_size = u32::from_le_bytes(size_buf) as usize;
-        
-        file.read_exact(&mut size_buf)?;
-        let value_size = u32::from_le_bytes(size_buf) as usize;
-        
-        // Read key
-        let mut key_buf = vec![0u8; key_size];
-        file.read_exact(&mut key_buf)?;
-        let key = String::from_utf8(key_buf)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        
-        // Read value
-        let mut value_buf = vec![0u8; value_size];
-        file.read_exact(&mut value_buf)?;
-        let value = String::from_utf8(value_buf)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        
-        // Check if this is a tombstone (deleted key)
-        if value == TOMBSTONE_MARKER {
-            return Err(StorageError::KeyDeleted(key));
-        }
-        
-        Ok((key, value))
+        self.read_value_at_record(filename, offset)
     }
 
     /// Reads only the value from the specified file at the given byte offset
     /// More efficient when key is not needed. Returns error if key is deleted or data is corrupted.
     pub fn read_value(&mut self, filename: &str, value_offset: u64, value_size: u32, expected_crc: u16, key: &str) -> Result<String, StorageError> {
-        let file_path = self.storage_dir.join(filename);
-        let mut file = OpenOptions::new()
-            .read(true)
-            .open(&file_path)?;
+        if let Some(cache) = self.value_cache.as_mut()
+            && let Some(value) = cache.get(key, filename, value_offset)
+        {
+            return Ok(value);
+        }
+
+        let value = decode_utf8(self.read_value_bytes(filename, value_offset, value_size, expected_crc, key)?)?;
+        if let Some(cache) = self.value_cache.as_mut() {
+            cache.insert(key, filename, value_offset, value.clone());
+        }
+        Ok(value)
+    }
+
+    /// Reads the value an index entry points at; read_value with its arguments taken from `location`
+    pub fn read_location(&mut self, location: &FileLocation, key: &str) -> Result<String, StorageError> {
+        self.read_value(&location.filename, location.value_offset, location.value_size, location.crc, key)
+    }
+
+    /// Reads the value an index entry points at as raw bytes; read_value_bytes with its arguments taken from `location`
+    pub fn read_location_bytes(&mut self, location: &FileLocation, key: &str) -> Result<Vec<u8>, StorageError> {
+        self.read_value_bytes(&location.filename, location.value_offset, location.value_size, location.crc, key)
+    }
+
+    /// Reads the value at `location` straight from disk, bypassing the value cache, after checking that the record
+    /// there was written for `key`. A stale or corrupted location is reported as CorruptedData, never as another key's value
+    pub fn read_verified(&mut self, location: &FileLocation, key: &str) -> Result<String, StorageError> {
+        decode_utf8(self.read_location_bytes(location, key)?)
+    }
+
+    /// Reads a value as raw bytes, with the same CRC and tombstone checks as read_value
+    /// Works for values that are not valid UTF-8; bypasses the value cache
+    pub fn read_value_bytes(&mut self, filename: &str, value_offset: u64, value_size: u32, expected_crc: u16, key: &str) -> Result<Vec<u8>, StorageError> {
+        let start = Instant::now();
+        let result = self.read_checked_bytes(filename, value_offset, value_size, expected_crc, key);
+        self.report_if_slow("read_value", start, Some(key));
+        result
+    }
+
+    /// Opens a data file for reading, reporting a missing file as FileMissing rather than a raw NotFound
+    /// For scans that keep their own position in the file; point reads use read_handle
+    fn open_for_read(&self, filename: &str) -> Result<File, StorageError> {
+        File::open(self.storage_dir.join(filename)).map_err(|e| missing_file_error(e, filename))
+    }
+
+    /// Shared read handle for a data file, opened on first use and kept until the file is removed or rewritten
+    /// Its position is shared too, so a caller must seek before reading and finish before the next read
+    fn read_handle(&self, filename: &str) -> Result<Arc<File>, StorageError> {
+        self.read_handles.get(&self.storage_dir, filename).map_err(|e| missing_file_error(e, filename))
+    }
+
+    /// Files opened for point reads versus reads that reused an open handle
+    pub fn read_handle_stats(&self) -> ReadHandleStats {
+        self.read_handles.stats()
+    }
+
+    /// Reads, CRC-checks and UTF-8 decodes a value, see read_value
+    fn read_value_checked(&self, filename: &str, value_offset: u64, value_size: u32, expected_crc: u16, key: &str) -> Result<String, StorageError> {
+        decode_utf8(self.read_checked_bytes(filename, value_offset, value_size, expected_crc, key)?)
+    }
+
+    /// Reads, CRC-checks and decompresses a value's bytes; the record type, not the value, decides whether it is a tombstone
+    /// (except in format version 1 files, which predate the byte and mark tombstones with LEGACY_TOMBSTONE_MARKER).
+    /// The record's own key must be `key`, so an index entry pointing at another key's record is reported as corruption
+    /// rather than returning that key's value. From format version 5 the whole record must also match its own CRC
+    fn read_checked_bytes(&self, filename: &str, value_offset: u64, value_size: u32, expected_crc: u16, key: &str) -> Result<Vec<u8>, StorageError> {
+        let (handle, mut record_type, compression, head) = self.read_record_header(filename, value_offset, key)?;
+        let mut file: &File = &handle;
+
+        let value_size = value_size as usize;
+
+        // Read value, which follows the key
+        let mut value_buf = vec![0u8; value_size];
+        file.read_exact(&mut value_buf)?;
+        
+        // Verify CRC before handing the bytes out
+        let calculated_crc = value_crc(&value_buf);
+        if calculated_crc != expected_crc {
+            return Err(StorageError::CorruptedData(format!(
+                "CRC mismatch for key '{}': expected {}, got {}", 
+                key, expected_crc, calculated_crc
+            )));
+        }
+        if record_crc_size(self.file_version(filename)) > 0 {
+            let mut stored_crc = [0u8; RECORD_CRC_SIZE as usize];
+            file.read_exact(&mut stored_crc)?;
+            if record_crc(&[&head, &value_buf]) != u32::from_le_bytes(stored_crc) {
+                return Err(bad_checksum_error(filename, value_offset - head.len() as u64));
+            }
+        }
         
-        // Seek to the offset
-        file.seek(SeekFrom::Start(value_offset))?;
+        // Check if this is a tombstone (deleted key)
+        if self.file_version(filename) == 1 && value_buf == LEGACY_TOMBSTONE_MARKER.as_bytes() {
+            record_type = RecordType::Tombstone;
+        }
+        if record_type == RecordType::Tombstone {
+            return Err(StorageError::KeyDeleted(key.to_string()));
+        }
+
+        compression.decode(value_buf).map_err(|e| StorageError::CorruptedData(format!(
+            "Failed to decompress the value of '{}': {}", key, e
+        )))
+    }
+
+    /// Reads the header and key of the record whose value starts at `value_offset`, checking the key is `key`
+    /// Returns the file's read handle positioned at the value, with the record type and compression from the header
+    /// (always Put for format version 1 files, whose tombstones are only recognizable by their value) and the
+    /// header and key bytes themselves, which the record CRC covers
+    fn read_record_header(&self, filename: &str, value_offset: u64, key: &str) -> Result<(Arc<File>, RecordType, Compression, Vec<u8>), StorageError> {
+        let handle = self.read_handle(filename)?;
+        let mut file: &File = &handle;
+        let format_version = self.file_version(filename);
+
+        // The key sits between the header and the value, and the flags byte follows the two length prefixes
+        let key_len = key.len() as u64;
+        let header_size = record_header_size(format_version);
+        if value_offset < header_size + key_len {
+            return Err(StorageError::CorruptedData(format!("Invalid value offset for key '{}'", key)));
+        }
+        let record_offset = value_offset - key_len - header_size;
+        file.seek(SeekFrom::Start(record_offset))?;
+        let mut head = vec![0u8; (header_size + key_len) as usize];
+        file.read_exact(&mut head)?;
+        let (header, key_buf) = head.split_at(header_size as usize);
+        let stored_key_len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as u64;
+        if stored_key_len != key_len || key_buf != key.as_bytes() {
+            return Err(StorageError::CorruptedData(format!(
+                "Index entry for '{}' does not point at a record for that key ({}, value offset {})",
+                key, filename, value_offset
+            )));
+        }
+
+        let (record_type, compression) = match format_version {
+            1 => (RecordType::Put, Compression::None),
+            _ => decode_flags(header[8], format_version).ok_or_else(|| StorageError::CorruptedData(format!(
+                "Unknown record flags {} for key '{}'", header[8], key
+            )))?,
+        };
+        Ok((handle, record_type, compression, head))
+    }
+
+    /// Whether the record at `location` is a tombstone for `key`, reading only its header, never the value
+    /// (format version 1 files are the exception: their tombstones are marked in the value)
+    pub fn is_tombstone(&self, location: &FileLocation, key: &str) -> Result<bool, StorageError> {
+        if self.file_version(&location.filename) == 1 {
+            return match self.read_checked_bytes(&location.filename, location.value_offset, location.value_size, location.crc, key) {
+                Err(StorageError::KeyDeleted(_)) => Ok(true),
+                result => result.map(|_| false),
+            };
+        }
+        let (_, record_type, _, _) = self.read_record_header(&location.filename, location.value_offset, key)?;
+        Ok(record_type == RecordType::Tombstone)
+    }
+
+    /// Decodes the full record starting at `record_offset` without needing index metadata
+    /// Length fields are bounds-checked against the file, and a record running past the end is reported as corrupted
+    /// Returns (key, value), or KeyDeleted if the record is a tombstone
+    pub fn read_value_at_record(&self, filename: &str, record_offset: u64) -> Result<(String, String), StorageError> {
+        let handle = self.read_handle(filename)?;
+        let mut file: &File = &handle;
+        let file_len = file.metadata()?.len();
+
+        let raw = match scan_record(&mut file, record_offset, file_len, self.file_version(filename))? {
+            RecordScan::Entry(raw) => raw,
+            RecordScan::Partial | RecordScan::End | RecordScan::Corrupt => {
+                return Err(StorageError::CorruptedData(format!(
+                    "No complete record at offset {} in '{}'", record_offset, filename
+                )));
+            }
+            RecordScan::BadChecksum => return Err(bad_checksum_error(filename, record_offset)),
+        };
+
+        let key = String::from_utf8(raw.key)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if raw.record_type == RecordType::Tombstone {
+            return Err(StorageError::KeyDeleted(key));
+        }
+
+        let value = String::from_utf8(raw.compression.decode(raw.value)?)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok((key, value))
+    }
+
+    /// Iterates over every record in every data file, oldest first, without reading values into memory
+    /// for longer than it takes to checksum them. Superseded records and tombstones are included; each file
+    /// ends at its first partial record or uncommitted tail. A record that can't be decoded, or fails its CRC
+    /// with intact records after it, yields an error and ends its file; a record whose key is not valid UTF-8
+    /// yields an error and iteration continues with the record after it.
+    /// The lowest-level way to walk the store, e.g. to build a custom index
+    pub fn iter_all_records(&self) -> impl Iterator<Item = Result<RawRecord, StorageError>> + '_ {
+        let (files, error) = match self.data_files() {
+            Ok(files) => (files, None),
+            Err(e) => (Vec::new(), Some(StorageError::Io(e))),
+        };
+        RecordIter { storage: self, files: files.into_iter(), current: None, error }
+    }
+
+    /// Like read_value_at_record, but for callers that know which key should be at `record_offset`
+    /// Returns the value, or CorruptedData if the record there belongs to a different key
+    pub fn read_checked(&self, filename: &str, record_offset: u64, expected_key: &str) -> Result<String, StorageError> {
+        let (key, value) = match self.read_value_at_record(filename, record_offset) {
+            Err(StorageError::KeyDeleted(key)) if key != expected_key => (key, String::new()),
+            result => result?,
+        };
+        if key != expected_key {
+            return Err(StorageError::CorruptedData(format!(
+                "Expected a record for '{}' at offset {} in '{}', found '{}'", expected_key, record_offset, filename, key
+            )));
+        }
+        Ok(value)
+    }
+
+    /// Places every data file, oldest first, in a virtual concatenation of the whole store
+    /// Returns (filename, logical start, length) per file; each file starts where the previous one ends
+    pub fn logical_layout(&self) -> std::io::Result<Vec<(String, u64, u64)>> {
+        let mut layout = Vec::new();
+        let mut start = 0u64;
+        for filename in self.data_files()? {
+            let len = std::fs::metadata(self.storage_dir.join(&filename))?.len();
+            layout.push((filename, start, len));
+            start += len;
+        }
+        Ok(layout)
+    }
+
+    /// Translates an offset in the concatenated view into (filename, offset within that file)
+    /// Returns None if the offset lies past the end of the store
+    pub fn resolve_logical(&self, logical_offset: u64) -> std::io::Result<Option<(String, u64)>> {
+        Ok(self.logical_layout()?
+            .into_iter()
+            .find(|(_, start, len)| logical_offset >= *start && logical_offset < start + len)
+            .map(|(filename, start, _)| (filename, logical_offset - start)))
+    }
+
+    /// Decodes the record starting at `logical_offset` in the concatenated view
+    /// Returns (key, value), or KeyDeleted if the record is a tombstone
+    pub fn read_logical(&self, logical_offset: u64) -> Result<(String, String), StorageError> {
+        match self.resolve_logical(logical_offset)? {
+            Some((filename, local_offset)) => self.read_value_at_record(&filename, local_offset),
+            None => Err(StorageError::CorruptedData(format!("Logical offset {} is past the end of the store", logical_offset))),
+        }
+    }
+
+    /// Reads raw bytes from a data file starting at `record_offset`
+    /// With no length, reads exactly the record at that offset as described by its header
+    /// Reads are clamped to the end of the file
+    pub fn read_raw(&self, filename: &str, record_offset: u64, len: Option<u64>) -> Result<Vec<u8>, StorageError> {
+        let handle = self.read_handle(filename)?;
+        let mut file: &File = &handle;
+        let file_len = file.metadata()?.len();
+
+        let len = match len {
+            Some(len) => len,
+            None => {
+                let mut header = [0u8; 8];
+                file.seek(SeekFrom::Start(record_offset))?;
+                file.read_exact(&mut header)?;
+                let key_size = u32::from_le_bytes(header[0..4].try_into().unwrap()) as u64;
+                let value_size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as u64;
+                let format_version = self.file_version(filename);
+                record_header_size(format_version) + key_size + value_size + record_crc_size(format_version)
+            }
+        };
+        let len = len.min(file_len.saturating_sub(record_offset));
+
+        let mut bytes = vec![0u8; len as usize];
+        file.seek(SeekFrom::Start(record_offset))?;
+        file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Writes `value` and indexes it only if `key` has no live value, i.e. it was never written or is deleted
+    /// Returns false, writing nothing, if the key exists
+    pub fn put_if_absent<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, value: &str) -> Result<bool, StorageError> {
+        if self.is_live(hash_table, key)? {
+            return Ok(false);
+        }
+        let location = self.write(key, value)?;
+        self.index_location(hash_table, key, location);
+        Ok(true)
+    }
+
+    /// Writes `value` and indexes it only if `key` has a live value to replace
+    /// Returns false, writing nothing, if the key was never written or is deleted
+    pub fn replace<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, value: &str) -> Result<bool, StorageError> {
+        if !self.is_live(hash_table, key)? {
+            return Ok(false);
+        }
+        let location = self.write(key, value)?;
+        self.index_location(hash_table, key, location);
+        Ok(true)
+    }
+
+    /// Writes `value` and indexes it as insert does, returning the value it replaced: None if the key was never
+    /// written or is deleted. The old value is read before anything is written, so if it can't be read nothing
+    /// changes. An index entry left pointing into a file merge has since removed is resolved from the data files
+    pub fn put_returning_old<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, value: &str) -> std::io::Result<Option<String>> {
+        let old = self.current_value(hash_table, key)?;
+        let location = self.write(key, value)?;
+        self.index_location(hash_table, key, location);
+        Ok(old)
+    }
+
+    /// Deletes `key`, pointing the index at the tombstone as delete_prefix does, and returns the value it removed
+    /// A tombstone is written whatever the old value's state: a value that can't be read (a CRC failure, an I/O
+    /// error) is reported as RemovedValue::Unreadable rather than blocking the delete of a damaged key
+    pub fn delete_returning_old<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str) -> std::io::Result<RemovedValue> {
+        self.delete_with_reason_returning_old(hash_table, key, "")
+    }
+
+    /// delete_returning_old, recording why in the tombstone as delete_with_reason does
+    pub fn delete_with_reason_returning_old<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, reason: &str) -> std::io::Result<RemovedValue> {
+        let old = match self.read_current_value(hash_table, key) {
+            Ok(Some(value)) => RemovedValue::Value(value),
+            Ok(None) => RemovedValue::Absent,
+            Err(e) => RemovedValue::Unreadable(e.to_string()),
+        };
+        let location = self.delete_with_reason(key, reason)?;
+        self.index_tombstone(hash_table, key, location);
+        Ok(old)
+    }
+
+    /// Reads the live value of `key` through the index, None if it is missing or deleted
+    /// A binary value is returned with invalid UTF-8 replaced by U+FFFD rather than failing the write it precedes
+    fn current_value<T: HashTableTrait>(&mut self, hash_table: &T, key: &str) -> std::io::Result<Option<String>> {
+        self.read_current_value(hash_table, key).map_err(into_io_error)
+    }
+
+    /// current_value's lookup. If the key's entry points into a data file that no longer exists (the index went
+    /// stale across a merge), its latest record is looked up in the data files instead
+    fn read_current_value<T: HashTableTrait>(&mut self, hash_table: &T, key: &str) -> Result<Option<String>, StorageError> {
+        let Some(location) = hash_table.get(key) else {
+            return Ok(None);
+        };
+        let result = match self.read_location_bytes(location, key) {
+            Err(StorageError::FileMissing(_)) => match self.find_latest_location(key)? {
+                Some(location) => self.read_location_bytes(&location, key),
+                None => return Ok(None),
+            },
+            result => result,
+        };
+        match result {
+            Ok(value) => Ok(Some(String::from_utf8_lossy(&value).into_owned())),
+            Err(StorageError::KeyDeleted(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Scans every data file for the latest record of `key`, returning its location, or None if the key has no
+    /// record or its latest one is a tombstone. The newest timestamp wins, as in merge and rebuild_index
+    fn find_latest_location(&self, key: &str) -> std::io::Result<Option<FileLocation>> {
+        let mut latest: Option<(u64, Option<FileLocation>)> = None;
+        for record in self.iter_all_records() {
+            let record = record.map_err(into_io_error)?;
+            if record.key != key || latest.as_ref().is_some_and(|(timestamp, _)| *timestamp > record.timestamp) {
+                continue;
+            }
+            let location = (record.record_type == RecordType::Put).then_some(FileLocation {
+                filename: record.filename,
+                value_size: record.value_size,
+                value_offset: record.value_offset,
+                crc: record.crc,
+                timestamp: record.timestamp,
+            });
+            latest = Some((record.timestamp, location));
+        }
+        Ok(latest.and_then(|(_, location)| location))
+    }
+
+    /// Deletes every live key starting with `prefix`, writing a tombstone for each through delete and pointing the
+    /// index at it as a single delete does; keys already deleted are skipped. The matching keys are collected before
+    /// anything is written, since indexing a tombstone can move entries around the table. Stops at the first failure,
+    /// leaving the keys deleted before it deleted. Returns the number of keys deleted
+    pub fn delete_prefix<T: HashTableTrait>(&mut self, hash_table: &mut T, prefix: &str) -> std::io::Result<usize> {
+        self.check_writable()?;
+        let mut keys: Vec<String> = hash_table.keys()
+            .filter(|key| key.starts_with(prefix))
+            .map(str::to_string)
+            .collect();
+        keys.sort();
+
+        let mut deleted = 0;
+        for key in &keys {
+            if !self.is_live(hash_table, key).map_err(into_io_error)? {
+                continue;
+            }
+            let location = self.delete(key)?;
+            self.index_tombstone(hash_table, key, location);
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+
+    /// Whether the index points `key` at a record other than a tombstone
+    fn is_live<T: HashTableTrait>(&self, hash_table: &T, key: &str) -> Result<bool, StorageError> {
+        match hash_table.get(key) {
+            Some(location) => Ok(!self.is_tombstone(location, key)?),
+            None => Ok(false),
+        }
+    }
+
+    /// Looks up a key through the index and reads its current value
+    /// Returns Found(value), Deleted if the latest record is a tombstone, or Missing if the key is unknown
+    /// IO and corruption errors are still surfaced as Err
+    pub fn get_outcome<T: HashTableTrait>(&mut self, hash_table: &T, key: &str) -> Result<GetOutcome, StorageError> {
+        let location = match hash_table.get(key) {
+            Some(location) => location,
+            None => return Ok(GetOutcome::Missing),
+        };
+
+        match self.read_location(location, key) {
+            Ok(value) => Ok(GetOutcome::Found(value)),
+            Err(StorageError::KeyDeleted(_)) => Ok(GetOutcome::Deleted),
+            Err(e) => Err(e),
+        }
+    }
+    
+    /// Looks a key up like get_outcome, returning its value together with the index entry it was read through:
+    /// the file and value offset, the stored size and CRC, and the write timestamp. None if the key is missing or deleted
+    pub fn get_full<T: HashTableTrait>(&mut self, hash_table: &T, key: &str) -> Result<Option<(String, FileLocation)>, StorageError> {
+        let Some(location) = hash_table.get(key) else {
+            return Ok(None);
+        };
+        match self.read_location(location, key) {
+            Ok(value) => Ok(Some((value, location.clone()))),
+            Err(StorageError::KeyDeleted(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Looks up several keys at once, returning each one's value or error at the key's position in `keys`
+    /// Keys missing from the index yield KeyNotFound and deleted ones KeyDeleted. Values are read in file and
+    /// offset order rather than as listed, so each file is read front to back through its one shared read handle
+    pub fn get_many<T: HashTableTrait>(&mut self, hash_table: &T, keys: &[&str]) -> Vec<Result<String, StorageError>> {
+        let position = |&i: &usize| hash_table.get(keys[i]).map(|location| (location.filename.as_str(), location.value_offset));
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by(|a, b| position(a).cmp(&position(b)));
+
+        let mut results: Vec<Option<Result<String, StorageError>>> = keys.iter().map(|_| None).collect();
+        for i in order {
+            results[i] = Some(match hash_table.get(keys[i]) {
+                Some(location) => self.read_location(location, keys[i]),
+                None => Err(StorageError::KeyNotFound(keys[i].to_string())),
+            });
+        }
+        results.into_iter().map(|result| result.expect("every key was read")).collect()
+    }
+
+    /// Returns every live key starting with `prefix` and its value, sorted by key
+    /// The hash table has no key order, so this walks every key in it. Deleted keys are skipped,
+    /// as are keys whose value can't be read
+    pub fn scan_prefix<T: HashTableTrait>(&mut self, hash_table: &T, prefix: &str) -> Vec<(String, String)> {
+        let mut keys: Vec<&str> = hash_table.keys().filter(|key| key.starts_with(prefix)).collect();
+        keys.sort();
+
+        keys.into_iter()
+            .filter_map(|key| {
+                let location = hash_table.get(key)?;
+                let value = self.read_location(location, key).ok()?;
+                Some((key.to_string(), value))
+            })
+            .collect()
+    }
+    
+    /// Number of data files other than the active one, i.e. what merge_inactive_files would merge
+    pub fn inactive_file_count(&self) -> std::io::Result<usize> {
+        Ok(self.data_files()?.iter().filter(|filename| **filename != self.current_filename).count())
+    }
+
+    /// Points `key` at the value record at `location` in `hash_table`, moving the record's bytes into the live-bytes
+    /// count dead_space uses. Callers indexing a record they just wrote use this (or index_tombstone) rather than
+    /// inserting directly; operations that rewrite the index wholesale (rebuild, load, merge) recount instead
+    pub fn index_location<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, location: FileLocation) {
+        self.index_record(hash_table, key, location, false);
+    }
+
+    /// index_location for a tombstone just written by delete: the key stays indexed at it, counted by
+    /// indexed_tombstone_count rather than live_key_count until a merge drops it
+    pub fn index_tombstone<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, location: FileLocation) {
+        self.index_record(hash_table, key, location, true);
+    }
+
+    fn index_record<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, location: FileLocation, tombstone: bool) {
+        if let Some(old) = hash_table.peek(key) {
+            let old_size = self.location_record_size(key, old);
+            if let Some(live) = self.live_bytes.get_mut(&old.filename) {
+                *live = live.saturating_sub(old_size);
+            }
+        }
+        *self.live_bytes.entry(location.filename.clone()).or_default() += self.location_record_size(key, &location);
+        if tombstone {
+            self.indexed_tombstones.insert(key.to_string());
+        } else {
+            self.indexed_tombstones.remove(key);
+        }
+        hash_table.insert(key, location);
+    }
+
+    /// Recomputes the live-bytes count and the set of tombstoned keys from every entry in `hash_table`
+    /// Telling a tombstone apart reads its record's header, so this costs a read per indexed key
+    pub fn recount_index_stats<T: HashTableTrait>(&mut self, hash_table: &T) {
+        let mut live_bytes: HashMap<String, u64> = HashMap::new();
+        let mut indexed_tombstones = HashSet::new();
+        for key in hash_table.keys() {
+            if let Some(location) = hash_table.peek(key) {
+                *live_bytes.entry(location.filename.clone()).or_default() += self.location_record_size(key, location);
+                if self.is_tombstone(location, key).unwrap_or(false) {
+                    indexed_tombstones.insert(key.to_string());
+                }
+            }
+        }
+        self.live_bytes = live_bytes;
+        self.indexed_tombstones = indexed_tombstones;
+    }
+
+    /// Number of keys in `hash_table` whose entry points at a live value, i.e. not at a tombstone
+    /// O(1): the tombstoned keys are tracked as they are indexed (see index_tombstone)
+    pub fn live_key_count(&self, hash_table: &HashTable) -> usize {
+        hash_table.len().saturating_sub(self.indexed_tombstones.len())
+    }
+
+    /// Number of deleted keys still indexed at their tombstone, which the next merge or compaction drops
+    pub fn indexed_tombstone_count(&self) -> usize {
+        self.indexed_tombstones.len()
+    }
+
+    /// Whether `key` is indexed at a tombstone, i.e. deleted but not yet dropped by a merge
+    /// Answered from the tombstoned keys tracked in memory (see index_tombstone), without reading the record
+    pub fn is_indexed_tombstone(&self, key: &str) -> bool {
+        self.indexed_tombstones.contains(key)
+    }
+
+    /// Size of the whole record at `location`, header and checksum included, in the format of its file
+    fn location_record_size(&self, key: &str, location: &FileLocation) -> u64 {
+        let format_version = self.file_version(&location.filename);
+        record_header_size(format_version) + key.len() as u64 + location.value_size as u64 + record_crc_size(format_version)
+    }
+
+    /// Bytes in the inactive files no index entry points at, and the total size of those files: what a merge would
+    /// reclaim, give or take the tombstones it drops, which count as live here. The active file is left out, since
+    /// merge never rewrites it and its dead space would otherwise keep a dead-space merge coming back
+    pub fn dead_space(&self) -> std::io::Result<(u64, u64)> {
+        let mut total = 0;
+        let mut live = 0;
+        for filename in self.data_files()? {
+            if filename == self.current_filename {
+                continue;
+            }
+            total += std::fs::metadata(self.storage_dir.join(&filename))?.len();
+            live += self.live_bytes.get(&filename).copied().unwrap_or(0);
+        }
+        Ok((total.saturating_sub(live), total))
+    }
+
+    /// Share of the inactive files' bytes that is dead space (see dead_space), 0 when there are no inactive files
+    pub fn dead_ratio(&self) -> std::io::Result<f64> {
+        let (dead, total) = self.dead_space()?;
+        Ok(if total == 0 { 0.0 } else { dead as f64 / total as f64 })
+    }
+
+    /// Sets the dead_ratio past which dead_space_merge_due reports a merge worthwhile; None disables it
+    pub fn set_merge_dead_ratio(&mut self, ratio: Option<f64>) {
+        self.merge_dead_ratio = ratio;
+    }
+
+    /// dead_ratio at which dead_space_merge_due starts reporting a merge worthwhile, if set
+    pub fn merge_dead_ratio(&self) -> Option<f64> {
+        self.merge_dead_ratio
+    }
+
+    /// Whether dead_ratio has passed the ratio set by set_merge_dead_ratio
+    /// Never true for a read-only store, without a ratio set, or when the data files can't be listed
+    pub fn dead_space_merge_due(&self) -> bool {
+        !self.read_only && self.merge_dead_ratio.is_some_and(|threshold| self.dead_ratio().is_ok_and(|ratio| ratio > threshold))
+    }
+
+    /// Merges `store` from a background thread, independent of any event loop: whenever more than `file_threshold`
+    /// files have become inactive since the last merge, or every `interval` while any file is inactive (None
+    /// disables a trigger). Callers lock the same mutex around every operation on the storage and its index.
+    /// The thread stops when the returned handle is dropped
+    pub fn start_background_merge<T>(store: Arc<Mutex<(Storage, T)>>, interval: Option<Duration>, file_threshold: Option<usize>) -> BackgroundMerge
+    where
+        T: HashTableTrait + Send + 'static,
+    {
+        background::start(store, interval, file_threshold)
+    }
+
+    /// Lists every data file in the storage directory (including the active one), sorted oldest first
+    fn data_files(&self) -> std::io::Result<Vec<String>> {
+        let mut data_files = Vec::new();
+        for entry in read_dir(&self.storage_dir)? {
+            let entry = entry?;
+            let filename = entry.file_name().to_string_lossy().to_string();
+            if self.naming.replay_order(&filename).is_some() && !self.pending_deletes.contains(&filename) {
+                data_files.push(filename);
+            }
+        }
+        data_files.sort_by_key(|filename| self.naming.replay_order(filename));
+        Ok(data_files)
+    }
+
+    /// Name of the data file numbered `counter`: an existing file's name if there is one, which may use the
+    /// narrower counter of an earlier version, otherwise the name the naming scheme gives it
+    fn data_filename(&self, counter: u32) -> std::io::Result<String> {
+        for entry in read_dir(&self.storage_dir)? {
+            let filename = entry?.file_name().to_string_lossy().to_string();
+            if self.naming.data_counter(&filename) == Some(counter) {
+                return Ok(filename);
+            }
+        }
+        Ok(self.naming.data_file(counter))
+    }
+
+    /// The naming scheme of this store's data and merge files
+    pub fn file_naming(&self) -> &FileNaming {
+        &self.naming
+    }
+
+    /// Rebuilds the index by replaying every record in every data file, oldest first
+    /// The record with the newest timestamp wins for each key, as in merge, with file order breaking ties: live keys
+    /// are inserted, tombstoned keys are deleted
+    /// A record failing its CRC is skipped and listed in the report, and scanning resumes at the next intact record, as
+    /// merge does; scanning a file stops at a partial record, so an interrupted write is never indexed
+    pub fn rebuild_index<T: HashTableTrait>(&mut self, hash_table: &mut T) -> std::io::Result<RebuildReport> {
+        let mut report = RebuildReport::default();
+        let latest = self.latest_locations(false, &mut report)?;
+        report.live_keys = apply_latest_locations(hash_table, latest);
+        self.recount_index_stats(hash_table);
+        Ok(report)
+    }
+
+    /// Like rebuild_index, but reads a data file's .hint (written by merge) instead of its records when it has one,
+    /// scanning only what was appended after the hint. Files without a usable hint are scanned in full
+    pub fn rebuild_index_from_hints<T: HashTableTrait>(&mut self, hash_table: &mut T) -> std::io::Result<RebuildReport> {
+        let mut report = RebuildReport::default();
+        let latest = self.latest_locations(true, &mut report)?;
+        report.live_keys = apply_latest_locations(hash_table, latest);
+        self.recount_index_stats(hash_table);
+        Ok(report)
+    }
+
+    /// Saves `hash_table` to the store's index file (see HashTable::save_to) for load_index to pick up on the next start
+    /// Meant for a clean shutdown: the index only stays usable while the data files keep the lengths recorded with it
+    pub fn save_index(&self, hash_table: &HashTable) -> std::io::Result<()> {
+        self.check_writable()?;
+        hash_table.save_to(self.storage_dir.join(self.naming.store_file(INDEX_FILENAME)), &self.stats()?.per_file)
+    }
+
+    /// Replaces the contents of `hash_table` with the index saved by save_index, returning the number of keys loaded
+    /// Fails with NotFound if there is no saved index, and with InvalidData if it is corrupt or stale, i.e. any data
+    /// file was written, added or removed since it was saved; `hash_table` is left untouched then, ready for
+    /// rebuild_index_from_hints
+    pub fn load_index(&mut self, hash_table: &mut HashTable) -> std::io::Result<usize> {
+        let mut loaded = hash_table.empty_clone();
+        let saved_files = loaded.load_from(self.storage_dir.join(self.naming.store_file(INDEX_FILENAME)))?;
+        if saved_files != self.stats()?.per_file {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "stale index file: data files changed since it was saved"));
+        }
+        *hash_table = loaded;
+        self.recount_index_stats(hash_table);
+        Ok(hash_table.len())
+    }
+
+    /// Replays every data file oldest first and returns the latest location per key (newest timestamp, later in file
+    /// order on a tie), None when the latest record is a tombstone. Only keys and locations are held in memory, never
+    /// values. With `use_hints`, a file's hint stands in for the records it covers. Hints used and records skipped for
+    /// failing their CRC are counted in `report`
+    fn latest_locations(&self, use_hints: bool, report: &mut RebuildReport) -> std::io::Result<HashMap<String, Option<FileLocation>>> {
+        let mut latest: HashMap<String, (u64, Option<FileLocation>)> = HashMap::new();
+
+        for filename in self.data_files()? {
+            let mut file = File::open(self.storage_dir.join(&filename))?;
+            let file_len = file.metadata()?.len();
+            let mut position = 0u64;
+
+            // A hint covering more than the file holds is stale (e.g. the file lost an unsynced tail)
+            let hint = if use_hints { hints::load(&self.storage_dir, &filename)? } else { None };
+            if let Some((covered_len, entries)) = hint.filter(|(covered_len, _)| *covered_len <= file_len) {
+                for entry in entries {
+                    let key = String::from_utf8(entry.key)
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                    let location = (entry.record_type != RecordType::Tombstone).then(|| {
+                        let mut location = FileLocation::new(filename.clone(), entry.value_size, entry.value_offset, entry.crc);
+                        location.timestamp = entry.timestamp;
+                        location
+                    });
+                    keep_newest(&mut latest, key, entry.timestamp, location);
+                }
+                position = covered_len;
+                report.hinted_files += 1;
+            }
+
+            let format_version = self.file_version(&filename);
+            let skipped = &mut report.skipped;
+            let mut report_skip = |offset, next| skipped.push(SkippedRecord { filename: filename.clone(), offset, next });
+            while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, &mut report_skip)? {
+                position = raw.next_offset;
+                let key = String::from_utf8(raw.key)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+                let location = (raw.record_type != RecordType::Tombstone).then(|| {
+                    let mut location = FileLocation::new(filename.clone(), raw.value.len() as u32, raw.value_offset, value_crc(&raw.value));
+                    location.timestamp = raw.timestamp;
+                    location
+                });
+                keep_newest(&mut latest, key, raw.timestamp, location);
+            }
+        }
+        Ok(latest.into_iter().map(|(key, (_, location))| (key, location)).collect())
+    }
+
+    /// Writes the hint for a data file, covering every record currently in it; records failing their CRC are left out
+    fn write_hint(&self, filename: &str) -> std::io::Result<()> {
+        let mut file = File::open(self.storage_dir.join(filename))?;
+        let file_len = file.metadata()?.len();
+        let mut entries = Vec::new();
+        let mut position = 0u64;
+        while let Some(raw) = scan_past_damage(&mut file, position, file_len, self.file_version(filename), |_, _| {})? {
+            position = raw.next_offset;
+            entries.push(HintEntry {
+                record_type: raw.record_type,
+                value_size: raw.value.len() as u32,
+                value_offset: raw.value_offset,
+                crc: value_crc(&raw.value),
+                timestamp: raw.timestamp,
+                key: raw.key,
+            });
+        }
+        hints::save(&self.storage_dir, filename, position, &entries)
+    }
+
+    /// Returns the (oldest, newest) record timestamps across every data file, or None if the store is empty
+    /// Covers every record still on disk, superseded values and tombstones included; records failing their CRC are
+    /// skipped, as their timestamps can't be trusted
+    pub fn timestamp_range(&self) -> std::io::Result<Option<(u64, u64)>> {
+        let mut range: Option<(u64, u64)> = None;
+        for filename in self.data_files()? {
+            let mut file = File::open(self.storage_dir.join(&filename))?;
+            let file_len = file.metadata()?.len();
+            let mut position = 0u64;
+
+            while let Some(raw) = scan_past_damage(&mut file, position, file_len, self.file_version(&filename), |_, _| {})? {
+                position = raw.next_offset;
+                range = Some(match range {
+                    Some((oldest, newest)) => (oldest.min(raw.timestamp), newest.max(raw.timestamp)),
+                    None => (raw.timestamp, raw.timestamp),
+                });
+            }
+        }
+        Ok(range)
+    }
+
+    /// Returns every key with a tombstone still on disk and the timestamp of its latest one, oldest delete first
+    /// Deletes are found by each record's type flag, so a value that merely looks like a tombstone isn't one.
+    /// Keys written again after their delete are included, since the delete still happened; merge discards
+    /// tombstones, so this only reaches back to deletes made since the files holding them were last merged
+    pub fn list_tombstones(&self) -> std::io::Result<Vec<(String, u64)>> {
+        let mut latest: HashMap<String, u64> = HashMap::new();
+        for record in self.iter_all_records() {
+            let record = record.map_err(into_io_error)?;
+            if record.record_type == RecordType::Tombstone {
+                let timestamp = latest.entry(record.key).or_insert(record.timestamp);
+                *timestamp = (*timestamp).max(record.timestamp);
+            }
+        }
+        let mut tombstones: Vec<(String, u64)> = latest.into_iter().collect();
+        tombstones.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(tombstones)
+    }
+
+    /// Streams every live key-value pair to `out`, sorted by key, one record at a time
+    /// Live records are found by replaying the data files, so no index is needed; values are read and
+    /// written individually so memory stays bounded by the number of keys rather than the data size.
+    /// Records failing their CRC are skipped, as rebuild_index does, and listed in the report
+    pub fn export_to<W: Write>(&self, out: &mut W, format: ExportFormat) -> std::io::Result<ExportReport> {
+        let mut rebuild = RebuildReport::default();
+        let mut live: Vec<(String, FileLocation)> = self.latest_locations(false, &mut rebuild)?
+            .into_iter()
+            .filter_map(|(key, location)| location.map(|location| (key, location)))
+            .collect();
+        live.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (key, location) in &live {
+            let value = self.read_value_checked(&location.filename, location.value_offset, location.value_size, location.crc, key)
+                .map_err(into_io_error)?;
+            match format {
+                ExportFormat::JsonLines => writeln!(out, "{{\"key\":{},\"value\":{}}}", json_string(key), json_string(&value))?,
+                ExportFormat::Text => writeln!(out, "{}\t{}", key, value)?,
+            }
+        }
+        out.flush()?;
+        Ok(ExportReport { records: live.len(), skipped: rebuild.skipped })
+    }
+
+    /// Consolidates a single key without a full merge
+    /// Re-appends the key's latest record (value or tombstone) to the active file, points the index at it,
+    /// then rewrites every inactive file that held older versions of the key without them.
+    /// Index entries for other keys in a rewritten file are moved to their new offsets
+    pub fn compact_key<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str) -> std::io::Result<()> {
+        self.check_writable()?;
+        let location = match hash_table.get(key) {
+            Some(location) => location.clone(),
+            None => return Ok(()),
+        };
+
+        // Re-append the latest version so no inactive file is needed to serve it
+        match self.read_location(&location, key) {
+            Ok(value) => {
+                let latest = self.write(key, &value)?;
+                self.index_location(hash_table, key, latest);
+            }
+            Err(StorageError::KeyDeleted(_)) => {
+                let latest = self.delete(key)?;
+                self.index_tombstone(hash_table, key, latest);
+            }
+            Err(e) => return Err(into_io_error(e)),
+        }
+
+        for filename in self.data_files()? {
+            if filename != self.current_filename {
+                self.strip_key_from_file(hash_table, &filename, key)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrites an inactive file without any records for `key`, re-pointing index entries that moved
+    /// Leaves the file untouched if it has no records for the key, and removes it if nothing remains.
+    /// Fails with InvalidData, rewriting nothing, if the file has a record failing its CRC or can't be read to its
+    /// end: the rewrite would drop the damaged record and everything after it that can't be found
+    fn strip_key_from_file<T: HashTableTrait>(&mut self, hash_table: &mut T, filename: &str, key: &str) -> std::io::Result<()> {
+        let file_path = self.storage_dir.join(filename);
+        let mut file = File::open(&file_path)?;
+        let file_len = file.metadata()?.len();
+
+        let mut kept = Vec::new();
+        let mut stripped = 0;
+        let mut position = 0u64;
+        let mut skipped = None;
+        let format_version = self.file_version(filename);
+        let mut report_skip = |offset, next| {
+            skipped.get_or_insert(SkippedRecord { filename: filename.to_string(), offset, next });
+        };
+        while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, &mut report_skip)? {
+            position = raw.next_offset;
+            if raw.key == key.as_bytes() {
+                stripped += 1;
+            } else {
+                kept.push(raw);
+            }
+        }
+        drop(file);
+
+        if stripped == 0 {
+            return Ok(());
+        }
+        if let Some(skipped) = skipped {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}; not rewriting it", skipped)));
+        }
+        if position != file_len {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!(
+                "'{}' can't be read past offset {}; not rewriting it", filename, position
+            )));
+        }
+        if kept.is_empty() {
+            self.read_handles.evict(filename);
+            remove_file(&file_path)?;
+            return self.forget_file(filename);
+        }
+
+        let moved = self.rewrite_file(filename, &kept)?;
+        self.forget_file(filename)?;
+
+        // Only entries still pointing at the old offset in this file are re-pointed
+        for (raw, new_value_offset) in moved {
+            let other_key = String::from_utf8_lossy(&raw.key);
+            let updated = match hash_table.get(&other_key) {
+                Some(location) if location.filename == filename && location.value_offset == raw.value_offset => {
+                    let mut location = location.clone();
+                    location.value_offset = new_value_offset;
+                    location
+                }
+                _ => continue,
+            };
+            hash_table.insert(&other_key, updated);
+        }
+        Ok(())
+    }
+
+    /// Replaces a data file's contents with `records` via a temp file and rename
+    /// Returns each record with its new value offset
+    fn rewrite_file<'a>(&self, filename: &str, records: &'a [RawEntry]) -> std::io::Result<Vec<(&'a RawEntry, u64)>> {
+        self.read_handles.evict(filename);
+        let file_path = self.storage_dir.join(filename);
+        let temp_path = self.storage_dir.join(format!("{}.compact", filename));
+        let mut temp = File::create(&temp_path)?;
+        let mut moved = Vec::new();
+        let mut new_position = 0u64;
+        for raw in records {
+            let record = encode_record(raw.record_type, raw.compression, raw.timestamp, &raw.key, &raw.value);
+            temp.write_all(&record)?;
+            moved.push((raw, new_position + RECORD_HEADER_SIZE + raw.key.len() as u64));
+            new_position += record.len() as u64;
+        }
+        temp.sync_all()?;
+        drop(temp);
+        std::fs::rename(&temp_path, &file_path)?;
+        Ok(moved)
+    }
+
+    /// Rolls the store back to its state at `timestamp` (nanoseconds since the Unix epoch)
+    /// Every record written after the cutoff is discarded: files are truncated at the first later record,
+    /// rewritten when earlier records (e.g. merged ones) follow it, and removed when nothing earlier remains.
+    /// The last remaining file becomes active and the index is rebuilt from disk. This cannot be undone.
+    /// Fails with InvalidData, changing nothing, if a file that would be rewritten or removed can't be read to its end
+    /// Only versions still on disk can come back. A merge keeps just the newest version of each key, so a key
+    /// rewritten after the cutoff and merged since then disappears instead of reverting to the value it had at `timestamp`
+    pub fn truncate_to<T: HashTableTrait>(&mut self, timestamp: u64, hash_table: &mut T) -> std::io::Result<TruncateReport> {
+        self.check_writable()?;
+        let mut report = TruncateReport::default();
+        let mut discarded_keys = Vec::new();
+
+        // Every file is scanned before any is changed, so one that can't be rewritten safely stops the rollback
+        // while the store is still whole
+        let mut plans = Vec::new();
+        for filename in self.data_files()? {
+            let file_path = self.storage_dir.join(&filename);
+            let mut file = File::open(&file_path)?;
+            let file_len = file.metadata()?.len();
+
+            let mut kept = Vec::new();
+            let mut boundary = None;
+            let mut kept_after_boundary = false;
+            let mut position = 0u64;
+            let format_version = self.file_version(&filename);
+            let skipped = &mut report.skipped;
+            let mut report_skip = |offset, next| skipped.push(SkippedRecord { filename: filename.clone(), offset, next });
+            while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, &mut report_skip)? {
+                let record_start = raw.record_offset(format_version);
+                position = raw.next_offset;
+                if raw.timestamp > timestamp {
+                    boundary.get_or_insert(record_start);
+                    report.records_discarded += 1;
+                    discarded_keys.push(String::from_utf8_lossy(&raw.key).to_string());
+                } else {
+                    kept_after_boundary |= boundary.is_some();
+                    kept.push(raw);
+                }
+            }
+
+            let boundary = match boundary {
+                Some(boundary) => boundary,
+                None => continue,
+            };
+            // Removing or rewriting the file would also drop whatever the scan couldn't read past, which is only
+            // safe if that is the uncommitted tail of a crashed write
+            let rewrites = kept.is_empty() || kept_after_boundary;
+            if rewrites && position != file_len && !is_uncommitted_tail(&mut file, position, file_len, format_version)? {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!(
+                    "'{}' can't be read past offset {}; nothing was rolled back", filename, position
+                )));
+            }
+            plans.push((filename, file_len, format_version, kept, boundary, kept_after_boundary));
+        }
+
+        for (filename, file_len, format_version, kept, boundary, kept_after_boundary) in plans {
+            let file_path = self.storage_dir.join(&filename);
+            if kept.is_empty() {
+                self.read_handles.evict(&filename);
+                remove_file(&file_path)?;
+                self.forget_file(&filename)?;
+                report.files_removed.push(filename);
+            } else if kept_after_boundary {
+                // Counted in the file's own format: the rewrite re-encodes what is kept in the current one
+                let kept_len: u64 = kept.iter().map(|raw| raw.next_offset - raw.record_offset(format_version)).sum();
+                self.rewrite_file(&filename, &kept)?;
+                self.forget_file(&filename)?;
+                report.files_truncated.push((filename, file_len - kept_len));
+            } else {
+                // Cutting the tail leaves the earlier records, and so the file's format version, as they were
+                OpenOptions::new().write(true).open(&file_path)?.set_len(boundary)?;
+                hints::remove(&self.storage_dir, &filename)?;
+                report.files_truncated.push((filename, file_len - boundary));
+            }
+        }
+
+        // Truncated files can reuse locations for different values, so nothing cached can be trusted
+        if let Some(cache) = self.value_cache.as_mut() {
+            cache.clear();
+        }
+
+        // The active file may have been removed or shortened, so reopen the newest survivor
+        let file_counter = self.data_files()?
+            .last()
+            .and_then(|name| self.naming.data_counter(name))
+            .unwrap_or(0);
+        self.open_active_file(file_counter)?;
+
+        // Keys that only existed after the cutoff must leave the index; rebuild restores the rest
+        for key in &discarded_keys {
+            hash_table.delete(key);
+        }
+        report.live_keys = self.rebuild_index(hash_table)?.live_keys;
+        Ok(report)
+    }
+
+    /// Reports what merge_inactive_files would do right now without modifying anything
+    /// The newest record per key across the inactive files is kept unless it is a tombstone; everything else is dropped
+    pub fn merge_dry_run(&self) -> std::io::Result<MergeReport> {
+        let preview = self.merge_preview()?;
+        Ok(MergeReport {
+            bytes_read: preview.bytes_retained + preview.bytes_removed,
+            bytes_rewritten: preview.bytes_retained,
+            records_kept: preview.live_keys,
+            records_dropped: preview.records - preview.live_keys,
+            files_merged: preview.files,
+            files_pending_delete: Vec::new(),
+            skipped: Vec::new(),
+        })
+    }
+
+    /// Scans the inactive files the way merge_inactive_files does and reports how many records, keys and
+    /// tombstones they hold and how many bytes a merge would reclaim, without modifying or deleting anything.
+    /// Only the inactive files are consulted, so a key overwritten or deleted in the active file since still counts
+    /// as live here, although a merge given the index skips it. Retained bytes are the records as stored now;
+    /// records in older format versions grow slightly when the merge rewrites them
+    pub fn merge_preview(&self) -> std::io::Result<MergePreview> {
+        let mut preview = MergePreview::default();
+        let mut latest: HashMap<Vec<u8>, (RecordType, u64, u64)> = HashMap::new();
+        let mut bytes_read = 0;
+
+        for filename in self.data_files()? {
+            if filename == self.current_filename {
+                continue;
+            }
+            let mut file = File::open(self.storage_dir.join(&filename))?;
+            let file_len = file.metadata()?.len();
+            let mut position = 0u64;
+            let format_version = self.file_version(&filename);
+            // Damaged records are skipped as merge_files skips them, so the records after one still count
+            let damaged = &mut preview.damaged_records;
+            while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, |_, _| *damaged += 1)? {
+                if raw.record_type == RecordType::Tombstone {
+                    preview.tombstones += 1;
+                }
+                if latest.get(&raw.key).is_none_or(|newest| newest.2 <= raw.timestamp) {
+                    let record_len = raw.next_offset - raw.record_offset(format_version);
+                    latest.insert(raw.key, (raw.record_type, record_len, raw.timestamp));
+                }
+                preview.records += 1;
+                position = raw.next_offset;
+            }
+            bytes_read += file_len;
+            preview.files.push(filename);
+        }
+
+        preview.unique_keys = latest.len();
+        for (record_type, record_len, _) in latest.values() {
+            if *record_type == RecordType::Put {
+                preview.live_keys += 1;
+                preview.bytes_retained += record_len;
+            }
+        }
+        preview.bytes_removed = bytes_read - preview.bytes_retained;
+        Ok(preview)
+    }
+
+    /// Estimates how long merge_inactive_files would take, without modifying anything
+    /// Combines merge_dry_run with a quick sample of sequential read throughput over the inactive files
+    /// (at most `sample_bytes`); writes are assumed to run at the same rate
+    pub fn estimate_merge(&self, sample_bytes: u64) -> std::io::Result<MergeEstimate> {
+        let report = self.merge_dry_run()?;
+
+        let mut sampled = 0u64;
+        let start = Instant::now();
+        let mut buffer = vec![0u8; 64 * 1024];
+        for filename in &report.files_merged {
+            let mut file = File::open(self.storage_dir.join(filename))?;
+            while sampled < sample_bytes {
+                let read = file.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                sampled += read as u64;
+            }
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+
+        let read_bytes_per_sec = if sampled == 0 || elapsed == 0.0 { 0.0 } else { sampled as f64 / elapsed };
+        let estimated_duration = if read_bytes_per_sec == 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((report.bytes_read + report.bytes_rewritten) as f64 / read_bytes_per_sec)
+        };
+        Ok(MergeEstimate { report, read_bytes_per_sec, estimated_duration })
+    }
+
+    /// Deletes inactive files that hold nothing worth keeping, without rewriting anything
+    /// A file is dead when every Put in it is superseded (the index points elsewhere) and every tombstone is
+    /// either superseded or stale: no older file holds a Put it would otherwise have to keep hidden.
+    /// A file with a record failing its CRC, or one that can't be read to its end, is never dead: what the
+    /// damage hides can't be judged. Records after the damage are still read, so their keys count as older Puts.
+    /// Deleted keys whose only tombstone is removed leave the index. Returns the number of files removed
+    pub fn prune_dead_files<T: HashTableTrait>(&mut self, hash_table: &mut T) -> std::io::Result<usize> {
+        self.check_writable()?;
+        self.retry_pending_deletes()?;
+
+        let mut older_puts: HashSet<String> = HashSet::new();
+        let mut dead_files = Vec::new();
+        for filename in self.data_files()? {
+            if filename == self.current_filename {
+                continue;
+            }
+            let mut file = File::open(self.storage_dir.join(&filename))?;
+            let file_len = file.metadata()?.len();
+            let mut position = 0u64;
+            let mut damaged = false;
+            let mut live = false;
+            let mut puts = Vec::new();
+            let mut indexed_tombstones = Vec::new();
+
+            let format_version = self.file_version(&filename);
+            while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, |_, _| damaged = true)? {
+                position = raw.next_offset;
+                let indexed = is_indexed(hash_table, &filename, &raw);
+                let key = String::from_utf8_lossy(&raw.key).to_string();
+                if raw.record_type == RecordType::Put {
+                    live |= indexed;
+                    puts.push(key);
+                } else {
+                    let superseded = !indexed && hash_table.get(&key).is_some();
+                    if !superseded && older_puts.contains(&key) {
+                        live = true;
+                    } else if indexed {
+                        indexed_tombstones.push(key);
+                    }
+                }
+            }
+
+            if !live && !damaged && position == file_len {
+                dead_files.push((filename, indexed_tombstones));
+            }
+            older_puts.extend(puts);
+        }
+
+        for (filename, indexed_tombstones) in &dead_files {
+            self.remove_data_file(filename)?;
+            for key in indexed_tombstones {
+                hash_table.delete(key);
+            }
+        }
+        self.recount_index_stats(hash_table);
+        Ok(dead_files.len())
+    }
+
+    /// Incrementally merges inactive files, deadest first, capping the bytes rewritten per call
+    /// Files are ordered by dead-byte ratio (bytes not referenced by the index / file size) and merged one at a time;
+    /// merging stops before the file whose live bytes would push the total rewrite past `max_bytes_to_rewrite`.
+    /// Tombstones still referenced by the index count as live so deleted keys can't resurface
+    pub fn merge_budget<T: HashTableTrait>(&mut self, hash_table: &mut T, max_bytes_to_rewrite: u64) -> std::io::Result<MergeReport> {
+        self.check_writable()?;
+        self.retry_pending_deletes()?;
+        let mut candidates = Vec::new();
+        for filename in self.data_files()? {
+            if filename == self.current_filename {
+                continue;
+            }
+            let (total_bytes, live_bytes) = self.file_liveness(hash_table, &filename)?;
+            let dead_ratio = if total_bytes == 0 { 1.0 } else { (total_bytes - live_bytes) as f64 / total_bytes as f64 };
+            candidates.push((filename, live_bytes, dead_ratio));
+        }
+        candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
 
-        let value_size = value_size as usize;
+        let mut report = MergeReport::default();
+        for (filename, live_bytes, _) in candidates {
+            if report.bytes_rewritten + live_bytes > max_bytes_to_rewrite {
+                break;
+            }
+            self.merge_single_file(hash_table, &filename, &mut report)?;
+        }
+        self.recount_index_stats(hash_table);
+        Ok(report)
+    }
 
-        // Read value
-        let mut value_buf = vec![0u8; value_size];
-        file.read_exact(&mut value_buf)?;
-        
-        // Verify CRC before converting to string
-        const X25: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_SDLC);
-        let calculated_crc = X25.checksum(value_buf.as_slice());
-        if calculated_crc != expected_crc {
-            return Err(StorageError::CorruptedData(format!(
-                "CRC mismatch for key '{}': expected {}, got {}", 
-                key, expected_crc, calculated_crc
-            )));
+    /// Returns (file size, bytes of records the index still points at) for a data file
+    fn file_liveness<T: HashTableTrait>(&self, hash_table: &T, filename: &str) -> std::io::Result<(u64, u64)> {
+        let mut file = File::open(self.storage_dir.join(filename))?;
+        let file_len = file.metadata()?.len();
+        let mut live_bytes = 0u64;
+        let mut position = 0u64;
+
+        let format_version = self.file_version(filename);
+        while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, |_, _| {})? {
+            if is_indexed(hash_table, filename, &raw) {
+                live_bytes += raw.next_offset - raw.record_offset(format_version);
+            }
+            position = raw.next_offset;
         }
-        
-        let value = String::from_utf8(value_buf)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        
-        // Check if this is a tombstone (deleted key)
-        if value == TOMBSTONE_MARKER {
-            return Err(StorageError::KeyDeleted(key.parse().unwrap()));
+        Ok((file_len, live_bytes))
+    }
+
+    /// Re-appends every indexed record of one inactive file, updates the index, then removes the file
+    /// A record failing its CRC is skipped and added to the report, as merge_inactive_files does
+    fn merge_single_file<T: HashTableTrait>(&mut self, hash_table: &mut T, filename: &str, report: &mut MergeReport) -> std::io::Result<()> {
+        let file_path = self.storage_dir.join(filename);
+        let mut file = File::open(&file_path)?;
+        let file_len = file.metadata()?.len();
+        let mut position = 0u64;
+
+        let format_version = self.file_version(filename);
+        let skipped = &mut report.skipped;
+        let mut report_skip = |offset, next| skipped.push(SkippedRecord { filename: filename.to_string(), offset, next });
+        while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, &mut report_skip)? {
+            let record_len = raw.next_offset - raw.record_offset(format_version);
+            position = raw.next_offset;
+            if !is_indexed(hash_table, filename, &raw) {
+                report.records_dropped += 1;
+                continue;
+            }
+
+            let key = String::from_utf8(raw.key)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            let location = self.append_record(&key, &raw.value, raw.record_type, raw.compression, raw.timestamp)?;
+            hash_table.insert(&key, location);
+
+            report.records_kept += 1;
+            report.bytes_rewritten += record_len;
         }
-        
-        Ok(value)
+        drop(file);
+
+        if !self.remove_data_file(filename)? {
+            report.files_pending_delete.push(filename.to_string());
+        }
+        report.bytes_read += file_len;
+        report.files_merged.push(filename.to_string());
+        Ok(())
     }
-    
+
+    /// Compacts every live record into a store in `dest`, for migrating to new storage without downtime
+    /// A record is live if the index points at it and it isn't a tombstone; records keep their timestamps.
+    /// Each written record is read back and CRC-checked before returning, and hint files are written for the
+    /// new store. This store and the index are left untouched, so the caller decides when to switch over
+    /// (index locations hold only filenames, so rebuild the index after opening `dest`). files_merged lists the
+    /// files read, none of which are removed. Records failing their CRC can't be copied; they are listed in skipped
+    /// and reading resumes after them. Fails without writing anything if `dest` already holds data files
+    pub fn merge_into_dir<T: HashTableTrait>(&self, dest: &Path, hash_table: &T) -> std::io::Result<MergeReport> {
+        self.prepare_empty_dir(dest)?;
+
+        let mut target = Storage::new_with_config(dest, self.max_file_size, DurabilityMode::None, self.compression, self.naming.clone()).map_err(into_io_error)?;
+        let mut report = MergeReport::default();
+        let mut written = Vec::new();
+
+        for filename in self.data_files()? {
+            let mut file = File::open(self.storage_dir.join(&filename))?;
+            let file_len = file.metadata()?.len();
+            let mut position = 0u64;
+
+            let format_version = self.file_version(&filename);
+            let skipped = &mut report.skipped;
+            let mut report_skip = |offset, next| skipped.push(SkippedRecord { filename: filename.clone(), offset, next });
+            while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, &mut report_skip)? {
+                let record_len = raw.next_offset - raw.record_offset(format_version);
+                position = raw.next_offset;
+                if raw.record_type != RecordType::Put || !is_indexed(hash_table, &filename, &raw) {
+                    report.records_dropped += 1;
+                    continue;
+                }
+
+                let key = String::from_utf8(raw.key)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                written.push((target.append_record(&key, &raw.value, RecordType::Put, raw.compression, raw.timestamp)?, key));
+                report.records_kept += 1;
+                report.bytes_rewritten += record_len;
+            }
+            report.bytes_read += file_len;
+            report.files_merged.push(filename);
+        }
+        target.sync()?;
+
+        // The checksum in each location was computed from the source value, so a read that passes proves the copy
+        for (location, key) in &written {
+            target.read_checked_bytes(&location.filename, location.value_offset, location.value_size, location.crc, key)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("verification of '{}' in {} failed: {}", key, dest.display(), e),
+                ))?;
+        }
+
+        for filename in target.data_files()? {
+            target.write_hint(&filename)?;
+        }
+        Ok(report)
+    }
+
+    /// Compare-and-set on value bytes: writes `new` only if the key's current value equals `expected`
+    /// Missing or deleted keys never match. Returns whether the swap happened; nothing is written otherwise.
+    /// Atomic with respect to other callers because Storage is borrowed mutably for the whole read-then-write
+    pub fn cas_value<T: HashTableTrait>(&mut self, hash_table: &mut T, key: &str, expected: &str, new: &str) -> Result<bool, StorageError> {
+        match self.get_outcome(hash_table, key)? {
+            GetOutcome::Found(current) if current.as_bytes() == expected.as_bytes() => {
+                let location = self.write(key, new)?;
+                self.index_location(hash_table, key, location);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
     /// Merges all inactive storage files, keeping only the latest value for each key
-    /// Removes old entries and tombstones, compacting the storage into the current active file
+    /// Removes old entries and tombstones, compacting the survivors into new merge files that are
+    /// synced before the inactive files are removed; the active file is never written to. Merge files replay
+    /// before every data file, so records written after them still win when the index is rebuilt.
+    /// The survivor for each key is the record with the newest timestamp, whatever file it was read from;
+    /// records with equal timestamps fall back to file and offset order. A record failing its CRC is reported and
+    /// skipped, and reading resumes at the next intact record; what it held is dropped with the merged files.
     /// Also cleans up the hash table by removing entries for deleted keys
     /// This operation helps reclaim space and improve read performance
-    pub fn merge_inactive_files<T>(&mut self, mut hash_table: Option<&mut T>) -> std::io::Result<()> 
+    /// Progress lines (files read, records skipped, files written and removed) are printed to stdout
+    pub fn merge_inactive_files<T>(&mut self, hash_table: Option<&mut T>) -> std::io::Result<()> 
     where 
         T: HashTableTrait,
     {
-        // Collect all data files except the current active one
-        let mut data_files = Vec::new();
-        
-        for entry in read_dir(&self.storage_dir)? {
-            let entry = entry?;
-            let filename = entry.file_name();
-            let filename_str = filename.to_string_lossy();
-            
-            if filename_str.starts_with("data_") && filename_str.ends_with(".dat") {
-                if filename_str != self.current_filename {
-                    data_files.push(filename_str.to_string());
-                }
-            }
+        self.merge_inactive_files_with_progress(hash_table, &mut std::io::stdout())
+    }
+
+    /// merge_inactive_files, writing its progress lines to `progress` instead of stdout, e.g. to the client that
+    /// asked for the merge
+    pub fn merge_inactive_files_with_progress<T: HashTableTrait>(&mut self, mut hash_table: Option<&mut T>, progress: &mut dyn Write) -> std::io::Result<()> {
+        let start = Instant::now();
+        let result = self.merge_files(hash_table.as_deref_mut(), progress);
+        self.report_if_slow("merge", start, None);
+        if let Some(hash_table) = hash_table {
+            self.recount_index_stats(hash_table);
+        }
+        result
+    }
+
+    /// Merges every data file, the active one included, reclaiming the stale versions and tombstones it holds
+    /// The active file is rotated out first, so writes continue in a new, empty active file
+    pub fn compact_all<T: HashTableTrait>(&mut self, hash_table: &mut T) -> std::io::Result<()> {
+        self.compact_all_with_progress(hash_table, &mut std::io::stdout())
+    }
+
+    /// compact_all, writing the merge's progress lines to `progress` as merge_inactive_files_with_progress does
+    pub fn compact_all_with_progress<T: HashTableTrait>(&mut self, hash_table: &mut T, progress: &mut dyn Write) -> std::io::Result<()> {
+        // An empty active file has nothing to reclaim, and rotating would only leave it behind for the merge
+        if self.current_file_size > 0 {
+            self.rotate_file()?;
         }
+        self.merge_inactive_files_with_progress(Some(hash_table), progress)
+    }
+
+    /// Performs the merge described in merge_inactive_files, writing its progress lines to `progress`
+    fn merge_files<T: HashTableTrait>(&mut self, mut hash_table: Option<&mut T>, progress: &mut dyn Write) -> std::io::Result<()> {
+        self.check_writable()?;
+        // Files left over from an earlier merge get another chance to go away first
+        let retried = self.retry_pending_deletes()?;
+        if retried > 0 {
+            let _ = writeln!(progress, "  Removed {} file(s) left pending by an earlier merge", retried);
+        }
+
+        // Collect all data files except the current active one, in order
+        let current_filename = self.current_filename.clone();
+        let data_files: Vec<String> = self.data_files()?
+            .into_iter()
+            .filter(|filename| *filename != current_filename)
+            .collect();
         
         if data_files.is_empty() {
-            println!("  No inactive files to merge");
+            let _ = writeln!(progress, "  No inactive files to merge");
             return Ok(());
         }
         
-        // Sort files to process them in order
-        data_files.sort();
-        
-        // Read all entries from inactive files and track the latest value for each key
-        let mut latest_entries: HashMap<String, String> = HashMap::new();
+        // Read all entries from inactive files and track the latest record for each key
+        let mut latest_entries: HashMap<String, (RecordType, Compression, u64, Vec<u8>)> = HashMap::new();
         let mut total_entries_read = 0;
         let mut tombstones_found = 0;
+        let mut out_of_order = 0;
         
         for filename in &data_files {
-            println!("  Processing inactive file: {}", filename);
+            let _ = writeln!(progress, "  Processing inactive file: {}", filename);
             let file_path = self.storage_dir.join(filename);
             let mut file = File::open(&file_path)?;
+            let file_len = file.metadata()?.len();
+            let format_version = self.file_version(filename);
             let mut position = 0u64;
             
-            while position < file.metadata()?.len() {
-                file.seek(SeekFrom::Start(position))?;
-                
-                // Read entry header
-                let mut size_buf = [0u8; 4];
-                if file.read_exact(&mut size_buf).is_err() {
-                    break; // End of file or corrupted entry
-                }
-                let key_size = u32::from_le_bytes(size_buf) as usize;
-                
-                if file.read_exact(&mut size_buf).is_err() {
-                    break; // End of file or corrupted entry
-                }
-                let value_size = u32::from_le_bytes(size_buf) as usize;
-                
-                // Read key
-                let mut key_buf = vec![0u8; key_size];
-                if file.read_exact(&mut key_buf).is_err() {
-                    break;
-                }
-                let key = String::from_utf8(key_buf).map_err(|e| {
-                    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
-                })?;
-                
-                // Read value
-                let mut value_buf = vec![0u8; value_size];
-                if file.read_exact(&mut value_buf).is_err() {
-                    break;
-                }
-                let value = String::from_utf8(value_buf).map_err(|e| {
+            // Stops at the end of the file or at a partial/undecodable entry; one failing its CRC is skipped
+            let mut report_skip = |offset, next| {
+                let _ = writeln!(progress, "  ✗ {}", SkippedRecord { filename: filename.clone(), offset, next });
+            };
+            while let Some(raw) = scan_past_damage(&mut file, position, file_len, format_version, &mut report_skip)? {
+                let key = String::from_utf8(raw.key).map_err(|e| {
                     std::io::Error::new(std::io::ErrorKind::InvalidData, e)
                 })?;
+                let value = raw.value;
                 
                 total_entries_read += 1;
                 
-                // Track latest value (including tombstones)
-                if value == TOMBSTONE_MARKER {
+                // Track latest record (including tombstones) by timestamp; on a tie the one read later wins
+                if raw.record_type == RecordType::Tombstone {
                     tombstones_found += 1;
-                    latest_entries.insert(key, value); // Keep tombstone as latest
+                }
+                if latest_entries.get(&key).is_none_or(|latest| latest.2 <= raw.timestamp) {
+                    latest_entries.insert(key, (raw.record_type, raw.compression, raw.timestamp, value));
                 } else {
-                    latest_entries.insert(key, value);
+                    out_of_order += 1;
                 }
                 
                 // Move to next entry
-                position += 8 + key_size as u64 + value_size as u64;
+                position = raw.next_offset;
             }
         }
         
-        println!("  Read {} total entries from {} inactive files", total_entries_read, data_files.len());
-        println!("  Found {} unique keys ({} tombstones)", latest_entries.len(), tombstones_found);
+        let _ = writeln!(progress, "  Read {} total entries from {} inactive files", total_entries_read, data_files.len());
+        let _ = writeln!(progress, "  Found {} unique keys ({} tombstones)", latest_entries.len(), tombstones_found);
+        if out_of_order > 0 {
+            let _ = writeln!(progress, "  Ignored {} records older than a version read before them", out_of_order);
+        }
+
         
-        // Write non-deleted entries to current active file and update hash table
+        // Write non-deleted entries to fresh merge files and update hash table
+        // With an index, a key whose entry points outside the merged files was overwritten or deleted in the
+        // active file since; its merged record is stale and the index must keep pointing at the newer one
+        let merged: HashSet<&str> = data_files.iter().map(String::as_str).collect();
+        let mut output = MergeOutput::new(&self.storage_dir, self.max_file_size, self.naming.clone())?;
         let mut entries_written = 0;
         let mut tombstones_skipped = 0;
+        let mut superseded_skipped = 0;
         let mut hash_table_deletions = 0;
         
-        for (key, value) in latest_entries {
-            if value == TOMBSTONE_MARKER {
+        for (key, (record_type, compression, timestamp, value)) in latest_entries {
+            let superseded = hash_table.as_ref()
+                .and_then(|ht| ht.get(&key))
+                .is_some_and(|location| !merged.contains(location.filename.as_str()));
+
+            if record_type == RecordType::Tombstone {
                 tombstones_skipped += 1;
                 // Remove deleted key from hash table if provided
-                if let Some(ref mut ht) = hash_table {
-                    if ht.delete(&key) {
-                        hash_table_deletions += 1;
-                    }
+                if !superseded
+                    && let Some(ref mut ht) = hash_table
+                    && ht.delete(&key)
+                {
+                    hash_table_deletions += 1;
                 }
                 // Skip tombstones - they represent deleted keys
                 continue;
             }
+            if superseded {
+                superseded_skipped += 1;
+                continue;
+            }
             
-            // Write the latest value to current active file
-            // filename, value_offset, value_size, crc
-            let (filename, value_offset, value_size, crc) = self.write(&key, &value)?;
+            // Write the latest value to the merge output as stored, keeping its original timestamp
+            let location = output.append(&key, &value, compression, timestamp)?;
             
             // Update hash table with new location if provided
             if let Some(ref mut ht) = hash_table {
-                ht.insert(&key, FileLocation::new(filename, value_size, value_offset, crc));
+                ht.insert(&key, location);
             }
             
             entries_written += 1;
         }
+        let output_files = output.finish()?;
         
-        println!("  Wrote {} active entries to current file, skipped {} deleted entries", 
-            entries_written, tombstones_skipped);
+        let _ = writeln!(progress, "  Wrote {} active entries to {} merge file(s), skipped {} deleted entries",
+            entries_written, output_files.len(), tombstones_skipped);
+        if superseded_skipped > 0 {
+            let _ = writeln!(progress, "  Skipped {} entries superseded in the active file", superseded_skipped);
+        }
         
         if hash_table.is_some() {
-            println!("  Removed {} deleted keys from hash table", hash_table_deletions);
+            let _ = writeln!(progress, "  Removed {} deleted keys from hash table", hash_table_deletions);
+        }
+
+        // Hints let the next startup index the merged output without reading its values
+        for filename in &output_files {
+            self.write_hint(filename)?;
+            let _ = writeln!(progress, "  Wrote hint file: {}", hints::hint_filename(filename));
         }
         
-        // Remove the old inactive files
+        // Remove the old inactive files; locked ones are queued rather than failing the merge
         for filename in &data_files {
-            let file_path = self.storage_dir.join(filename);
-            remove_file(file_path)?;
-            println!("  Removed old file: {}", filename);
+            if self.remove_data_file(filename)? {
+                let _ = writeln!(progress, "  Removed old file: {}", filename);
+            } else {
+                let _ = writeln!(progress, "  Could not remove {}; queued for deletion on the next merge or open", filename);
+            }
         }
         
-        println!("  Merge completed successfully");
+        let _ = writeln!(progress, "  Merge completed successfully");
         Ok(())
     }
     
-    /// Returns statistics about the storage files
-    pub fn get_storage_stats(&self) -> std::io::Result<()> {
-        let mut file_count = 0;
-        let mut total_size = 0u64;
-        
+    /// Runs a write/read/delete/merge/recover cycle in a scratch store under the storage directory and reports each step
+    /// Never touches this store's own data files; see selftest::run
+    pub fn self_test(&self) -> SelfTestReport {
+        // The scratch store is a subdirectory, which a read-only store must not create
+        if let Err(e) = self.check_writable() {
+            return selftest::refused(e.to_string());
+        }
+        selftest::run(&self.storage_dir, &self.naming)
+    }
+
+    /// Sizes of the data files on disk, including any still pending deletion, in replay order
+    pub fn stats(&self) -> std::io::Result<StorageStats> {
+        let mut per_file = Vec::new();
         for entry in read_dir(&self.storage_dir)? {
             let entry = entry?;
-            let filename = entry.file_name();
-            let filename_str = filename.to_string_lossy();
-            
-            if filename_str.starts_with("data_") && filename_str.ends_with(".dat") {
-                let metadata = entry.metadata()?;
-                let size = metadata.len();
-                total_size += size;
-                file_count += 1;
-                
-                let status = if filename_str == self.current_filename { " (ACTIVE)" } else { "" };
-                println!("    {}: {} bytes{}", filename_str, size, status);
+            let filename = entry.file_name().to_string_lossy().to_string();
+            if self.naming.replay_order(&filename).is_some() {
+                per_file.push((filename, entry.metadata()?.len()));
             }
         }
-        
-        println!("  Total: {} files, {} bytes", file_count, total_size);
+        per_file.sort_by_key(|(filename, _)| self.naming.replay_order(filename));
+
+        Ok(StorageStats {
+            file_count: per_file.len(),
+            total_bytes: per_file.iter().map(|(_, size)| size).sum(),
+            active_file: self.current_filename.clone(),
+            per_file,
+        })
+    }
+
+    /// Prints statistics about the storage files
+    pub fn get_storage_stats(&self) -> std::io::Result<()> {
+        print!("{}", self.stats()?);
+        Ok(())
+    }
+}
+/// Records `location` as the latest for `key` in latest_locations unless a record with a newer timestamp was
+/// already seen; on a tie the one read later wins, as in merge
+fn keep_newest(latest: &mut HashMap<String, (u64, Option<FileLocation>)>, key: String, timestamp: u64, location: Option<FileLocation>) {
+    if latest.get(&key).is_none_or(|(seen, _)| *seen <= timestamp) {
+        latest.insert(key, (timestamp, location));
+    }
+}
+
+/// Applies the result of latest_locations to the index, returning the number of live keys
+fn apply_latest_locations<T: HashTableTrait>(hash_table: &mut T, latest: HashMap<String, Option<FileLocation>>) -> usize {
+    let mut live_keys = 0;
+    for (key, location) in latest {
+        match location {
+            Some(location) => {
+                hash_table.insert(&key, location);
+                live_keys += 1;
+            }
+            None => {
+                hash_table.delete(&key);
+            }
+        }
+    }
+    live_keys
+}
+
+/// Whether the index still points at this record
+fn is_indexed<T: HashTableTrait>(hash_table: &T, filename: &str, raw: &RawEntry) -> bool {
+    let key = String::from_utf8_lossy(&raw.key);
+    hash_table.get(&key)
+        .is_some_and(|location| location.filename == filename && location.value_offset == raw.value_offset)
+}
+
+/// Computes the value checksum stored in FileLocation
+pub(crate) fn value_crc(value: &[u8]) -> u16 {
+    const X25: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_SDLC);
+    X25.checksum(value)
+}
+
+/// Converts value bytes to a String, reporting invalid UTF-8 as an InvalidData IO error
+fn decode_utf8(bytes: Vec<u8>) -> Result<String, StorageError> {
+    String::from_utf8(bytes)
+        .map_err(|e| StorageError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+}
+
+/// Quotes and escapes a string as a JSON string literal
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Cuts an uncommitted trailing record off the end of the active file, returning the number of bytes removed:
+/// a partial one, or one whose CRC doesn't match, with no intact record after it (see is_uncommitted_tail).
+/// A record that can't be decoded, or a partial or failing one with intact records after it, is left in place for
+/// repair::scan_and_fix to deal with, since cutting there would drop the committed records that follow
+fn truncate_partial_tail(file: &mut File) -> std::io::Result<u64> {
+    let file_len = file.metadata()?.len();
+    let mut position = 0u64;
+    loop {
+        match scan_record(file, position, file_len, meta::FORMAT_VERSION)? {
+            RecordScan::Entry(raw) => position = raw.next_offset,
+            RecordScan::Partial | RecordScan::BadChecksum if is_uncommitted_tail(file, position, file_len, meta::FORMAT_VERSION)? => break,
+            RecordScan::End | RecordScan::Corrupt | RecordScan::Partial | RecordScan::BadChecksum => return Ok(0),
+        }
+    }
+    file.set_len(position)?;
+    file.sync_all()?;
+    Ok(file_len - position)
+}
+
+/// Whether the record at `position`, which failed its CRC or runs past the end of the file, is the uncommitted tail
+/// of a write cut short by a crash rather than damage to committed data. The CRC is the record's commit marker: it is
+/// written last, so a record whose end never reached the disk (e.g. the file grew but its last blocks are still zeros)
+/// can't match it. Damage to a committed record, including a length field pointing past the end, has intact records
+/// after it; an uncommitted one is always the last thing in its file.
+/// Records before format version 5 have no CRC to find intact records by, so a partial one is always taken as a tail
+pub(crate) fn is_uncommitted_tail<R: Read + Seek>(file: &mut R, position: u64, file_len: u64, format_version: u32) -> std::io::Result<bool> {
+    if record_crc_size(format_version) == 0 {
+        return Ok(true);
+    }
+    Ok(next_intact_record(file, position + 1, file_len, format_version)?.is_none())
+}
+
+/// Finds the first position from `from` on where an intact record starts, to skip past one that failed its CRC
+/// without trusting its length fields. A candidate has to decode and match its own CRC, so landing inside another
+/// record by chance is as unlikely as a CRC collision. Returns None if no intact record follows
+fn next_intact_record<R: Read + Seek>(file: &mut R, from: u64, file_len: u64, format_version: u32) -> std::io::Result<Option<u64>> {
+    // Every offset is tried, so the reads go through a window rather than a seek and read per offset
+    let mut reader = WindowedReader::new(file);
+    for position in from..file_len {
+        if let RecordScan::Entry(_) = scan_record(&mut reader, position, file_len, format_version)? {
+            return Ok(Some(position));
+        }
+    }
+    Ok(None)
+}
+
+/// Bytes next_intact_record reads from its file at a time
+const SCAN_WINDOW_SIZE: usize = 64 * 1024;
+
+/// Read + Seek over `inner` serving reads from an in-memory window of up to SCAN_WINDOW_SIZE bytes, refilled only when
+/// a read falls outside it. Unlike BufReader, seeking doesn't discard the window, so the many short, overlapping reads
+/// of scanning every offset cost about one read of the file per window
+struct WindowedReader<'a, R> {
+    inner: &'a mut R,
+    window: Vec<u8>,
+    window_start: u64,
+    position: u64,
+}
+
+impl<'a, R: Read + Seek> WindowedReader<'a, R> {
+    fn new(inner: &'a mut R) -> Self {
+        WindowedReader { inner, window: Vec::new(), window_start: 0, position: 0 }
+    }
+
+    /// Reloads the window to start at the current position, returning how many bytes it now holds
+    fn refill(&mut self) -> std::io::Result<usize> {
+        self.inner.seek(SeekFrom::Start(self.position))?;
+        self.window.clear();
+        (&mut *self.inner).take(SCAN_WINDOW_SIZE as u64).read_to_end(&mut self.window)?;
+        self.window_start = self.position;
+        Ok(self.window.len())
+    }
+}
+
+impl<R: Read + Seek> Read for WindowedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let in_window = self.position.checked_sub(self.window_start)
+            .filter(|offset| *offset < self.window.len() as u64);
+        let offset = match in_window {
+            Some(offset) => offset as usize,
+            // A read as large as the window gains nothing from it
+            None if buf.len() >= SCAN_WINDOW_SIZE => {
+                self.inner.seek(SeekFrom::Start(self.position))?;
+                let read = self.inner.read(buf)?;
+                self.position += read as u64;
+                return Ok(read);
+            }
+            None => {
+                if self.refill()? == 0 {
+                    return Ok(0);
+                }
+                0
+            }
+        };
+        let available = &self.window[offset..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for WindowedReader<'_, R> {
+    fn seek(&mut self, target: SeekFrom) -> std::io::Result<u64> {
+        self.position = match target {
+            SeekFrom::Start(position) => position,
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before the start of the file"))?,
+            SeekFrom::End(_) => self.inner.seek(target)?,
+        };
+        Ok(self.position)
+    }
+}
+
+/// Decodes the record at `position` as scan_record does, but skips a record failing its CRC to the next intact one
+/// (see next_intact_record), as merge does. Each skip is passed to `skipped` with the damaged record's offset and the
+/// offset reading resumes at, None if no intact record follows. Returns None at the end of what can be read, which
+/// includes a record running past the end of the file even when its length field was damaged and records follow it
+fn scan_past_damage<R: Read + Seek>(file: &mut R, mut position: u64, file_len: u64, format_version: u32, mut skipped: impl FnMut(u64, Option<u64>)) -> std::io::Result<Option<RawEntry>> {
+    loop {
+        match scan_record(file, position, file_len, format_version)? {
+            RecordScan::Entry(raw) => return Ok(Some(raw)),
+            RecordScan::BadChecksum => {
+                let next = next_intact_record(file, position + 1, file_len, format_version)?;
+                skipped(position, next);
+                match next {
+                    Some(next) => position = next,
+                    None => return Ok(None),
+                }
+            }
+            RecordScan::End | RecordScan::Partial | RecordScan::Corrupt => return Ok(None),
+        }
+    }
+}
+
+/// Reports a record that failed its CRC check
+fn bad_checksum_error(filename: &str, record_offset: u64) -> StorageError {
+    StorageError::CorruptedData(format!("Record at offset {} in '{}' fails its checksum", record_offset, filename))
+}
+
+/// Reports a data file that doesn't exist as FileMissing rather than a raw NotFound
+fn missing_file_error(error: std::io::Error, filename: &str) -> StorageError {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => StorageError::FileMissing(filename.to_string()),
+        _ => StorageError::Io(error),
+    }
+}
+
+/// Renames a file, falling back to copy and delete when the destination is on another filesystem
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            std::fs::copy(from, to)?;
+            File::open(to)?.sync_all()?;
+            remove_file(from)
+        }
+        result => result,
+    }
+}
+
+/// Undoes part of a relocate by moving `filenames` from `from` back to `to`, returning `error` (the failure that
+/// stopped the relocate). If a file can't be moved back, the store is left split and the error says where
+fn move_back(from: &Path, to: &Path, filenames: &[String], error: std::io::Error) -> std::io::Error {
+    for filename in filenames.iter().rev() {
+        if let Err(e) = move_file(&from.join(filename), &to.join(filename)) {
+            return std::io::Error::new(error.kind(), format!(
+                "{}; rolling back also failed ({}), {} is left in {}", error, e, filename, from.display()
+            ));
+        }
+    }
+    error
+}
+
+/// Current time in nanoseconds since the Unix epoch, as stored in record headers
+fn now_nanos() -> u64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+    since_epoch.as_secs() * 1_000_000_000 + since_epoch.subsec_nanos() as u64
+}
+
+/// Size of the fixed record header in a format version: 8 bytes of lengths, plus the record-type byte
+/// from version 2 and the timestamp from version 3
+pub(crate) fn record_header_size(format_version: u32) -> u64 {
+    match format_version {
+        1 => 8,
+        2 => 9,
+        _ => RECORD_HEADER_SIZE,
+    }
+}
+
+/// Size of the checksum after a record's value in a format version: none before version 5
+pub(crate) fn record_crc_size(format_version: u32) -> u64 {
+    if format_version >= 5 { RECORD_CRC_SIZE } else { 0 }
+}
+
+/// Size of a record encode_record writes for a key and value of these lengths
+pub(crate) fn record_size(key_len: usize, value_len: usize) -> u64 {
+    RECORD_HEADER_SIZE + key_len as u64 + value_len as u64 + RECORD_CRC_SIZE
+}
+
+/// Computes the CRC-32 ending a record over its parts (header, key and value) in order
+fn record_crc(parts: &[&[u8]]) -> u32 {
+    const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    let mut digest = CRC32.digest();
+    for part in parts {
+        digest.update(part);
+    }
+    digest.finalize()
+}
+
+/// Encodes a record as [key_size:4][value_size:4][flags:1][timestamp:8][key][value][crc:4]
+/// The flags byte holds the record type in its low four bits and the value's compression in the high four;
+/// the CRC-32 covers everything before it, so a damaged length field or key is caught as well as a damaged value.
+/// The CRC doubles as the record's commit marker: the record goes out in one write rather than the body being synced
+/// before the trailer, which would cost a second fsync per record. A record cut short by a crash can't match its
+/// CRC, so recovery treats a failing record with nothing intact after it as never written (see is_uncommitted_tail)
+pub(crate) fn encode_record(record_type: RecordType, compression: Compression, timestamp: u64, key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(record_size(key.len(), value.len()) as usize);
+    record.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    record.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    record.push(record_type as u8 | (compression as u8) << 4);
+    record.extend_from_slice(&timestamp.to_le_bytes());
+    record.extend_from_slice(key);
+    record.extend_from_slice(value);
+    let crc = record_crc(&[&record]);
+    record.extend_from_slice(&crc.to_le_bytes());
+    record
+}
+
+/// Splits a record's flags byte (format version 2 onwards) into its record type and compression
+/// Versions 2 and 3 have no compression bits, so anything but a bare record type is invalid there
+fn decode_flags(flags: u8, format_version: u32) -> Option<(RecordType, Compression)> {
+    match format_version {
+        2 | 3 => Some((RecordType::from_byte(flags)?, Compression::None)),
+        _ => Some((RecordType::from_byte(flags & 0x0f)?, Compression::from_byte(flags >> 4)?)),
+    }
+}
+
+/// Converts a storage error into an io::Error for APIs that only report IO failures
+fn into_io_error(error: StorageError) -> std::io::Error {
+    match error {
+        StorageError::Io(e) => e,
+        other => std::io::Error::new(std::io::ErrorKind::InvalidData, other.to_string()),
+    }
+}
+
+/// The dedicated files merge_files writes survivors into, rotating at max_file_size like the active file
+struct MergeOutput {
+    storage_dir: PathBuf,
+    max_file_size: u64,
+    /// Names the files written
+    naming: FileNaming,
+    /// Counter of the next file to open
+    next_counter: u32,
+    /// File being written: (filename, file, bytes written)
+    current: Option<(String, File, u64)>,
+    /// Every file opened so far, oldest first
+    filenames: Vec<String>,
+}
+
+impl MergeOutput {
+    /// Numbers files after every merge file already in `storage_dir`, including ones still pending deletion
+    fn new(storage_dir: &Path, max_file_size: u64, naming: FileNaming) -> std::io::Result<MergeOutput> {
+        let mut next_counter = 0;
+        for entry in read_dir(storage_dir)? {
+            if let Some(counter) = naming.merge_counter(&entry?.file_name().to_string_lossy()) {
+                next_counter = next_counter.max(counter + 1);
+            }
+        }
+        Ok(MergeOutput { storage_dir: storage_dir.to_path_buf(), max_file_size, naming, next_counter, current: None, filenames: Vec::new() })
+    }
+
+    /// Appends a Put record, moving on to a new file first if it would exceed max_file_size
+    /// An empty file takes the record even if it exceeds max_file_size, as in Storage::append_record
+    fn append(&mut self, key: &str, value: &[u8], compression: Compression, timestamp: u64) -> std::io::Result<FileLocation> {
+        let record = encode_record(RecordType::Put, compression, timestamp, key.as_bytes(), value);
+        if self.current.as_ref().is_none_or(|(_, _, size)| *size > 0 && size + record.len() as u64 > self.max_file_size) {
+            self.open_next()?;
+        }
+        let Some((filename, file, size)) = self.current.as_mut() else {
+            unreachable!("open_next sets the current file");
+        };
+
+        let value_offset = *size + RECORD_HEADER_SIZE + key.len() as u64;
+        file.write_all(&record)?;
+        *size += record.len() as u64;
+
+        let mut location = FileLocation::new(filename.clone(), value.len() as u32, value_offset, value_crc(value));
+        location.timestamp = timestamp;
+        Ok(location)
+    }
+
+    /// Syncs the file being written and starts the next one
+    fn open_next(&mut self) -> std::io::Result<()> {
+        if let Some((_, file, _)) = self.current.take() {
+            file.sync_all()?;
+        }
+        let filename = self.naming.merge_file(self.next_counter);
+        let file = OpenOptions::new().write(true).create_new(true).open(self.storage_dir.join(&filename))?;
+        self.next_counter += 1;
+        self.filenames.push(filename.clone());
+        self.current = Some((filename, file, 0));
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Syncs the last file and returns the names of every file written, oldest first
+    fn finish(mut self) -> std::io::Result<Vec<String>> {
+        if let Some((_, file, _)) = self.current.take() {
+            file.sync_all()?;
+        }
+        Ok(self.filenames)
+    }
+}
+
+/// A record's position and metadata as yielded by Storage::iter_all_records
+/// value_offset, value_size and crc are exactly what read_value and FileLocation expect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawRecord {
+    pub filename: String,
+    /// Offset of the record header within the file
+    pub record_offset: u64,
+    pub key: String,
+    pub value_offset: u64,
+    pub value_size: u32,
+    pub crc: u16,
+    /// Write time in nanoseconds since the Unix epoch (0 for older-format files)
+    pub timestamp: u64,
+    pub record_type: RecordType,
+    /// How the value is stored; value_size and crc describe the stored bytes
+    pub compression: Compression,
+}
+
+/// Walks the data files one record at a time for Storage::iter_all_records
+struct RecordIter<'a> {
+    storage: &'a Storage,
+    files: std::vec::IntoIter<String>,
+    /// File being read: (filename, file, length when opened, position of the next record)
+    current: Option<(String, File, u64, u64)>,
+    /// Error listing the data files, reported once
+    error: Option<StorageError>,
+}
+
+impl Iterator for RecordIter<'_> {
+    type Item = Result<RawRecord, StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+        loop {
+            let (filename, file, file_len, position) = match self.current.as_mut() {
+                Some(current) => current,
+                None => {
+                    let filename = self.files.next()?;
+                    let opened = self.storage.open_for_read(&filename)
+                        .and_then(|file| Ok((file.metadata()?.len(), file)));
+                    match opened {
+                        Ok((file_len, file)) => self.current = Some((filename, file, file_len, 0)),
+                        Err(e) => return Some(Err(e)),
+                    }
+                    continue;
+                }
+            };
+
+            let format_version = self.storage.file_version(filename);
+            let raw = match scan_record(file, *position, *file_len, format_version) {
+                Ok(RecordScan::Entry(raw)) => raw,
+                Ok(RecordScan::End | RecordScan::Partial) => {
+                    self.current = None;
+                    continue;
+                }
+                Ok(RecordScan::Corrupt) => {
+                    let error = StorageError::CorruptedData(format!("Undecodable record at offset {} in '{}'", position, filename));
+                    self.current = None;
+                    return Some(Err(error));
+                }
+                Ok(RecordScan::BadChecksum) => {
+                    // An uncommitted write at the end of the file was never part of the store, so it ends the file
+                    // as a partial record does; only damage with intact records after it is reported
+                    let error = match is_uncommitted_tail(file, *position, *file_len, format_version) {
+                        Ok(true) => None,
+                        Ok(false) => Some(bad_checksum_error(filename, *position)),
+                        Err(e) => Some(StorageError::Io(e)),
+                    };
+                    self.current = None;
+                    match error {
+                        Some(error) => return Some(Err(error)),
+                        None => continue,
+                    }
+                }
+                Err(e) => {
+                    self.current = None;
+                    return Some(Err(StorageError::Io(e)));
+                }
+            };
+
+            let record_offset = *position;
+            *position = raw.next_offset;
+            let key = match String::from_utf8(raw.key) {
+                Ok(key) => key,
+                Err(_) => {
+                    return Some(Err(StorageError::CorruptedData(format!(
+                        "Record at offset {} in '{}' has a key that is not valid UTF-8", record_offset, filename
+                    ))));
+                }
+            };
+            return Some(Ok(RawRecord {
+                filename: filename.clone(),
+                record_offset,
+                key,
+                value_offset: raw.value_offset,
+                value_size: raw.value.len() as u32,
+                crc: value_crc(&raw.value),
+                timestamp: raw.timestamp,
+                record_type: raw.record_type,
+                compression: raw.compression,
+            }));
+        }
+    }
+}
+
+/// A single record decoded from a data file, with its byte positions
+pub(crate) struct RawEntry {
+    pub record_type: RecordType,
+    /// How `value` is compressed; it holds the stored bytes, not the original value
+    pub compression: Compression,
+    /// Write time in nanoseconds since the Unix epoch
+    pub timestamp: u64,
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    pub value_offset: u64,
+    pub next_offset: u64,
+}
+
+impl RawEntry {
+    /// Offset the record starts at in its file, whose format version fixes the header size
+    pub fn record_offset(&self, format_version: u32) -> u64 {
+        self.value_offset - record_header_size(format_version) - self.key.len() as u64
+    }
+}
+
+/// Result of decoding the record starting at a given position
+pub(crate) enum RecordScan {
+    /// A complete record
+    Entry(RawEntry),
+    /// Header or body runs past the end of the file: an interrupted write, or a committed record whose length field
+    /// was damaged. From format version 5 on, is_uncommitted_tail tells the two apart
+    Partial,
+    /// Position is exactly at the end of the file
+    End,
+    /// Complete header with unknown record flags, so the rest of the file can't be trusted
+    Corrupt,
+    /// Complete record whose CRC doesn't match its contents (format version 5 onwards); its length fields
+    /// can't be trusted either, so neither can the position of the next record. With no intact record after it,
+    /// this is a write that never committed rather than damage, see is_uncommitted_tail
+    BadChecksum,
+}
+
+/// Decodes the record at `position`, checking its length fields against the file length
+/// before allocating so a damaged header can't trigger a huge read
+/// Files in older format versions (see meta::OLDEST_READABLE_VERSION) decode with that version's layout;
+/// their records report timestamp 0, and version 1 tombstones are recognized by LEGACY_TOMBSTONE_MARKER.
+/// Records from version 5 on are checked against their CRC before anything in them is believed
+pub(crate) fn scan_record<R: Read + Seek>(file: &mut R, position: u64, file_len: u64, format_version: u32) -> std::io::Result<RecordScan> {
+    let header_size = record_header_size(format_version);
+    if position == file_len {
+        return Ok(RecordScan::End);
+    }
+    if position + header_size > file_len {
+        return Ok(RecordScan::Partial);
+    }
+
+    file.seek(SeekFrom::Start(position))?;
+    let mut header = vec![0u8; header_size as usize];
+    file.read_exact(&mut header)?;
+    let key_size = u32::from_le_bytes(header[0..4].try_into().unwrap()) as u64;
+    let value_size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as u64;
+    let (mut record_type, compression) = match format_version {
+        1 => (RecordType::Put, Compression::None),
+        _ => match decode_flags(header[8], format_version) {
+            Some(flags) => flags,
+            None => return Ok(RecordScan::Corrupt),
+        },
+    };
+    let timestamp = match format_version {
+        1 | 2 => 0,
+        _ => u64::from_le_bytes(header[9..17].try_into().unwrap()),
+    };
+
+    let crc_size = record_crc_size(format_version);
+    let next_offset = position + header_size + key_size + value_size + crc_size;
+    if next_offset > file_len {
+        return Ok(RecordScan::Partial);
+    }
+
+    let mut key = vec![0u8; key_size as usize];
+    file.read_exact(&mut key)?;
+    let mut value = vec![0u8; value_size as usize];
+    file.read_exact(&mut value)?;
+    if crc_size > 0 {
+        let mut stored_crc = [0u8; RECORD_CRC_SIZE as usize];
+        file.read_exact(&mut stored_crc)?;
+        if record_crc(&[&header, &key, &value]) != u32::from_le_bytes(stored_crc) {
+            return Ok(RecordScan::BadChecksum);
+        }
+    }
+    if format_version == 1 && value == LEGACY_TOMBSTONE_MARKER.as_bytes() {
+        record_type = RecordType::Tombstone;
+    }
+
+    Ok(RecordScan::Entry(RawEntry {
+        record_type,
+        compression,
+        timestamp,
+        key,
+        value,
+        value_offset: position + header_size + key_size,
+        next_offset,
+    }))
+}
@@ -119,7 +119,7 @@ test_basic_operations() {
     
     # Test help command
     send_command "help"
-    check_output_contains "Commands\|insert\|get\|delete" "Help Command"
+    check_output_contains "Commands\|insert\|get\|delete\|exit" "Help Command"
     
     # Test stats command
     send_command "stats"
@@ -147,8 +147,7 @@ test_edge_cases() {
     
     # Empty key/value tests
     send_command "insert \"\" empty_key_value"
-    wait_for_output 2
-    log_test_result "Empty Key Handling" "PASS"
+    check_output_contains "Key must not be empty" "Empty Key Handling"
     
     send_command "insert empty_value_key \"\""
     check_output_contains "Inserted\|inserted" "Empty Value Handling"
@@ -284,6 +283,2169 @@ test_error_handling() {
     log_test_result "Missing Key Handling" "PASS"
 }
 
+test_rehash() {
+    echo -e "${BLUE}=== Testing Rehash ===${NC}"
+    
+    send_command "rehash"
+    check_output_contains "Before: max probe length" "Rehash Reports Probe Stats Before"
+    check_output_contains "After:  max probe length" "Rehash Reports Probe Stats After"
+    
+    # Data must still be reachable after the table is rebuilt
+    send_command "get rotation_key_5"
+    check_output_contains "rotation_key_5:" "Data Integrity After Rehash"
+}
+
+test_simulate_crash() {
+    echo -e "${BLUE}=== Testing Simulated Crash Recovery ===${NC}"
+    
+    send_command "insert crash_key crash_value"
+    send_command "simulate-crash" 1
+    check_output_contains "Recovered [0-9]* keys" "Index Recovered After Simulated Crash"
+    
+    send_command "get crash_key"
+    check_output_contains "crash_key: crash_value" "Data Integrity After Simulated Crash"
+    
+    send_command "get test_key"
+    check_output_contains "not found\|deleted" "Deleted Key Stays Deleted After Recovery"
+}
+
+test_simulate_crash_dropped_entries() {
+    echo -e "${BLUE}=== Testing Simulated Crash Drops Every Index Entry Once ===${NC}"
+    
+    # The index is cleared in place: an updated key counts once, whatever the collision method
+    local app_bin="$(pwd)/$APP_PATH"
+    local method
+    for method in chaining linear_probing quadratic_probing double_hashing robin_hood; do
+        local exec_dir=$(mktemp -d)
+        sed -e "s/^collision_method = .*/collision_method = \"$method\"/" config.toml > "$exec_dir/config.toml"
+        local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert a 1" --exec "insert b 2" --exec "insert c 3" \
+            --exec "insert d 4" --exec "insert a 5" --exec "simulate-crash" --exec "get a" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        
+        check_exec_output_contains "$output" "dropping 4 in-memory index entries" "Index Entries Dropped ($method)"
+        check_exec_output_contains "$output" "✓ a: 5$" "Latest Value After Drop ($method)"
+    done
+}
+
+# Runs the app non-interactively with the given arguments in a scratch directory,
+# so it doesn't share storage with the running app, and prints its output
+run_exec() {
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" "$@" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+}
+
+check_exec_output_contains() {
+    local output="$1"
+    local expected="$2"
+    local test_name="$3"
+    
+    if echo "$output" | grep -q -- "$expected"; then
+        log_test_result "$test_name" "PASS"
+    else
+        log_test_result "$test_name" "FAIL" "Expected '$expected' not found in output"
+    fi
+}
+
+test_exec_mode() {
+    echo -e "${BLUE}=== Testing Non-interactive Exec Mode ===${NC}"
+    
+    local output=$(run_exec --exec "insert a 1" --exec "get a")
+    check_exec_output_contains "$output" "a: 1" "Exec Mode Runs Commands"
+    
+    # Insert, get and delete through the command handler, end to end
+    output=$(run_exec --exec "insert rt one" --exec "get rt" --exec "delete rt" --exec "get rt")
+    check_exec_output_contains "$output" "Inserted rt: one (file: data_0000000000.dat, value_offset: 19)" "Handler Insert Reports Location"
+    check_exec_output_contains "$output" "✓ rt: one$" "Handler Get Reads Value"
+    check_exec_output_contains "$output" "Deleted rt (tombstone: file data_0000000000.dat" "Handler Delete Writes Tombstone"
+    check_exec_output_contains "$output" "Key 'rt' has been deleted$" "Handler Get After Delete"
+    
+    # A script piped on stdin runs to its end; an empty one exits rather than going interactive
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    output=$(cd "$exec_dir" && printf 'insert sc_a 1\n# a comment\n\nget sc_a\n' | timeout 10 "$app_bin" --script - 2>&1)
+    local empty=$(cd "$exec_dir" && : > empty.txt && timeout 10 "$app_bin" --script empty.txt < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "Running 2 commands; merges only run when a command asks for one" "Script Skips Comments And Blank Lines"
+    check_exec_output_contains "$output" "✓ sc_a: 1$" "Script Read From Stdin"
+    if echo "$empty" | grep -q "Running 0 commands" && ! echo "$empty" | grep -q "Interactive"; then
+        log_test_result "Empty Script Exits" "PASS"
+    else
+        log_test_result "Empty Script Exits" "FAIL"
+    fi
+}
+
+test_hexdump() {
+    echo -e "${BLUE}=== Testing Hexdump ===${NC}"
+    
+    local output=$(run_exec --exec "insert hx abc" --exec "hexdump data_0000000000.dat 0")
+    check_exec_output_contains "$output" "00000000: 0200 0000 0300 0000 00" "Hexdump Hex Layout"
+    check_exec_output_contains "$output" "^00000010: ..68 7861 6263 .*hxabc....$" "Hexdump ASCII Gutter"
+    
+    output=$(run_exec --exec "hexdump data_0000000999.dat 0")
+    check_exec_output_contains "$output" "Data file 'data_0000000999.dat' does not exist" "Hexdump Reports Missing File"
+}
+
+test_tombstone_marker_value() {
+    echo -e "${BLUE}=== Testing Tombstone Marker As A Value ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local args=()
+    for i in {10..30}; do
+        args+=(--exec "insert key_$i value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec 'insert marker \DELETED\' --exec "get marker" "${args[@]}" --exec "merge" --exec "get marker" < /dev/null 2>&1)
+    if [ "$(echo "$output" | grep -c '✓ marker: \\DELETED\\$')" = "2" ]; then
+        log_test_result "Marker Value Readable Before And After Merge" "PASS"
+    else
+        log_test_result "Marker Value Readable Before And After Merge" "FAIL"
+    fi
+    
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get marker" < /dev/null 2>&1)
+    check_exec_output_contains "$output" '✓ marker: \\DELETED\\$' "Marker Value Survives Restart"
+    rm -rf "$exec_dir"
+}
+
+test_binary_values() {
+    echo -e "${BLUE}=== Testing Binary Values ===${NC}"
+    
+    local args=()
+    for i in {10..30}; do
+        args+=(--exec "insert key_$i value_$i")
+    done
+    local output=$(run_exec --exec "insert-hex bin ff00fe80" --exec "get-hex bin" --exec "get bin" "${args[@]}" --exec "merge" --exec "get-hex bin" \
+        --exec "delete bin" --exec "get-hex bin" --exec "insert-hex odd abc")
+    check_exec_output_contains "$output" "✓ Inserted bin: 4 bytes" "Binary Value Inserted"
+    check_exec_output_contains "$output" "✓ bin: ff00fe80$" "Binary Value Read Back"
+    check_exec_output_contains "$output" "Error reading bin: .*invalid utf-8" "String Read Rejects Binary Value"
+    if [ "$(echo "$output" | grep -c "✓ bin: ff00fe80$")" = "2" ]; then
+        log_test_result "Binary Value Survives Merge" "PASS"
+    else
+        log_test_result "Binary Value Survives Merge" "FAIL"
+    fi
+    check_exec_output_contains "$output" "Key 'bin' has been deleted$" "Binary Value Deleted"
+    check_exec_output_contains "$output" "Invalid hex bytes: abc" "Odd Hex Rejected"
+}
+
+test_delete_reason() {
+    echo -e "${BLUE}=== Testing Delete With Reason ===${NC}"
+    
+    local output=$(run_exec --exec "insert audited 1" --exec "delete audited gdpr request" --exec "get audited")
+    check_exec_output_contains "$output" "Key 'audited' has been deleted (reason: gdpr request)" "Delete Reason Readable"
+    
+    output=$(run_exec --exec "insert plain 1" --exec "delete plain" --exec "get plain")
+    check_exec_output_contains "$output" "Key 'plain' has been deleted$" "Delete Without Reason"
+}
+
+test_truncate_to() {
+    echo -e "${BLUE}=== Testing Point-in-time Truncate ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert early 1" --exec "timestamp" < /dev/null 2>&1)
+    local ts=$(echo "$output" | grep -o "timestamp: [0-9]*" | grep -o "[0-9]*$")
+    
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert late 2" --exec "truncate-to $ts" --exec "get late" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "Re-run as 'truncate-to $ts confirm'" "Truncate Requires Confirmation"
+    check_exec_output_contains "$output" "late: 2" "Truncate Not Applied Without Confirmation"
+    
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "truncate-to $ts confirm" --exec "get early" --exec "get late" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "early: 1" "Earlier Key Survives Truncate"
+    check_exec_output_contains "$output" "Key 'late' not found" "Later Key Discarded By Truncate"
+    rm -rf "$exec_dir"
+    
+    # td_a, td_b and td_c are 26 bytes each; td_b is damaged at its value, td_d comes after the cutoff
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert td_a 1" --exec "insert td_b 2" --exec "insert td_c 3" --exec "timestamp" < /dev/null 2>&1)
+    ts=$(echo "$output" | grep -o "timestamp: [0-9]*" | grep -o "[0-9]*$")
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert td_d 4" < /dev/null > /dev/null 2>&1)
+    printf 'x' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=47 conv=notrunc 2>/dev/null
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "truncate-to $ts confirm" --exec "get td_c" --exec "get td_d" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    if echo "$output" | grep -A1 "✗ Data corruption: Record at offset 26 in 'data_0000000000.dat' fails its checksum" \
+        | grep -q "✓ Rolled back to $ts: discarded 1 records"; then
+        log_test_result "Truncate Reports Damaged Record" "PASS"
+    else
+        log_test_result "Truncate Reports Damaged Record" "FAIL"
+    fi
+    check_exec_output_contains "$output" "✓ td_c: 3$" "Record After Damage Kept By Truncate"
+    check_exec_output_contains "$output" "Key 'td_d' not found" "Later Record After Damage Discarded"
+    
+    # A format version 4 file, whose records are 22 bytes without a CRC: tv_b is after the cutoff of 2ns, tv_c before it.
+    # Without tv_c the file is cut short and stays in version 4; with it the file is rewritten in the current version
+    local record_a='\x04\x00\x00\x00\x01\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00tv_a1'
+    local record_b='\x04\x00\x00\x00\x01\x00\x00\x00\x00\x03\x00\x00\x00\x00\x00\x00\x00tv_b2'
+    local record_c='\x04\x00\x00\x00\x01\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00tv_c3'
+    local mode
+    for mode in cut rewritten; do
+        exec_dir=$(mktemp -d)
+        cp config.toml "$exec_dir/"
+        mkdir -p "$exec_dir/storage"
+        case "$mode" in
+            cut) printf "$record_a$record_b" > "$exec_dir/storage/data_0000000000.dat" ;;
+            rewritten) printf "$record_a$record_b$record_c" > "$exec_dir/storage/data_0000000000.dat" ;;
+        esac
+        printf 'format_version = 5\nendianness = "little"\nchecksum = "crc16-ibm-sdlc"\nrecord_types = true\ntimestamps = true\n\n[file_versions]\n"data_0000000000.dat" = 4\n' \
+            > "$exec_dir/storage/.meta"
+        output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "truncate-to 2 confirm" < /dev/null 2>&1)
+        local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get tv_a" --exec "get tv_b" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        check_exec_output_contains "$output" "Truncated data_0000000000.dat by 22 bytes" "Version 4 Bytes Cut Counted ($mode)"
+        check_exec_output_contains "$restarted" "✓ tv_a: 1$" "Version 4 Record Readable After Truncate ($mode)"
+        check_exec_output_contains "$restarted" "Key 'tv_b' not found" "Version 4 Later Record Gone ($mode)"
+    done
+}
+
+test_prune_dead_files() {
+    echo -e "${BLUE}=== Testing Dead File Pruning ===${NC}"
+    
+    # data_0000000000.dat fills with 14 keys that are all overwritten in data_0000000001.dat; data_0000000002.dat becomes active
+    local args=()
+    for i in {10..23}; do
+        args+=(--exec "insert key_$i value_$i")
+    done
+    for i in {10..23}; do
+        args+=(--exec "insert key_$i new_$i")
+    done
+    for i in {10..25}; do
+        args+=(--exec "insert more_$i v")
+    done
+    local output=$(run_exec "${args[@]}" --exec "delete key_10" --exec "prune" --exec "layout" --exec "get key_11" --exec "get key_10" --exec "get more_20")
+    check_exec_output_contains "$output" "✓ Pruned 1 dead files" "Prune Removes Superseded File"
+    check_exec_output_contains "$output" "Logical layout (2 files)" "Prune Keeps Files With Live Data"
+    if echo "$output" | grep -q "data_0000000000.dat: start"; then
+        log_test_result "Superseded File Gone After Prune" "FAIL"
+    else
+        log_test_result "Superseded File Gone After Prune" "PASS"
+    fi
+    check_exec_output_contains "$output" "✓ key_11: new_11$" "Latest Values Survive Prune"
+    check_exec_output_contains "$output" "Key 'key_10' has been deleted$" "Deletes Survive Prune"
+    check_exec_output_contains "$output" "✓ more_20: v$" "Active File Untouched By Prune"
+    
+    # data_0000000000.dat holds pd_a (overwritten), pd_b (overwritten, then damaged at its value) and pd_c, still live,
+    # 26 bytes each; the padding value doesn't fit after them, so it and the overwrites go to data_0000000001.dat
+    local app_bin="$(pwd)/$APP_PATH"
+    local pad=$(printf 'p%.0s' {1..450})
+    local mode
+    for mode in prune merge-budget; do
+        local exec_dir=$(mktemp -d)
+        cp config.toml "$exec_dir/"
+        (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert pd_a 1" --exec "insert pd_b 2" --exec "insert pd_c 3" \
+            --exec "insert pd_pad $pad" --exec "insert pd_a 4" --exec "insert pd_b 5" < /dev/null > /dev/null 2>&1)
+        printf 'x' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=47 conv=notrunc 2>/dev/null
+        case "$mode" in
+            prune) output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "prune" --exec "get pd_c" < /dev/null 2>&1) ;;
+            merge-budget) output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "merge-budget 1000000" --exec "get pd_c" < /dev/null 2>&1) ;;
+        esac
+        local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get pd_c" --exec "get pd_b" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        case "$mode" in
+            prune) check_exec_output_contains "$output" "✓ Pruned 0 dead files" "Damaged File Not Pruned" ;;
+            merge-budget)
+                # Opening the store reports the damage too, so look for it right before the merge result
+                if echo "$output" | grep -A1 "✗ Data corruption: Record at offset 26 in 'data_0000000000.dat' fails its checksum; skipped 26 bytes to the next intact record" \
+                    | grep -q "✓ Merged 2 files"; then
+                    log_test_result "Budget Merge Reports Damaged Record" "PASS"
+                else
+                    log_test_result "Budget Merge Reports Damaged Record" "FAIL"
+                fi ;;
+        esac
+        check_exec_output_contains "$output" "✓ pd_c: 3$" "Record After Damage Kept ($mode)"
+        check_exec_output_contains "$restarted" "✓ pd_c: 3$" "Record After Damage Survives Restart ($mode)"
+        check_exec_output_contains "$restarted" "✓ pd_b: 5$" "Overwrite Of Damaged Record Intact ($mode)"
+    done
+}
+
+test_logical_layout() {
+    echo -e "${BLUE}=== Testing Logical Layout ===${NC}"
+    
+    # Every record is 35 bytes (17 header + 6 key + 8 value + 4 CRC), so 14 fit in each 512-byte file
+    local args=()
+    for i in {10..50}; do
+        args+=(--exec "insert key_$i value_$i")
+    done
+    local output=$(run_exec "${args[@]}" --exec "layout" --exec "read-logical 525" --exec "read-logical 99999")
+    check_exec_output_contains "$output" "Logical layout (3 files)" "Layout Lists Every File"
+    check_exec_output_contains "$output" "data_0000000001.dat: start 490, length 490" "Layout Places Second File After First"
+    check_exec_output_contains "$output" "Logical offset 525 is data_0000000001.dat:35" "Logical Offset Resolves Into Second File"
+    check_exec_output_contains "$output" "✓ key_25: value_25$" "Logical Read Decodes Record"
+    check_exec_output_contains "$output" "Logical offset 99999 is past the end of the store" "Logical Offset Past End Rejected"
+}
+
+test_age() {
+    echo -e "${BLUE}=== Testing Record Age ===${NC}"
+    
+    local output=$(run_exec --exec "age")
+    check_exec_output_contains "$output" "✓ Store is empty" "Age Of Empty Store"
+    
+    output=$(run_exec --exec "timestamp" --exec "insert age_a 1" --exec "insert age_b 2" --exec "delete age_a" --exec "timestamp" --exec "age")
+    local before=$(echo "$output" | grep -o "timestamp: [0-9]*" | head -1 | grep -o "[0-9]*$")
+    local after=$(echo "$output" | grep -o "timestamp: [0-9]*" | tail -1 | grep -o "[0-9]*$")
+    local oldest=$(echo "$output" | grep -o "Oldest record: [0-9]*" | grep -o "[0-9]*$")
+    local newest=$(echo "$output" | grep -o "Newest record: [0-9]*" | grep -o "[0-9]*$")
+    if [ -n "$oldest" ] && [ -n "$newest" ] && [ "$before" -le "$oldest" ] && [ "$oldest" -lt "$newest" ] && [ "$newest" -le "$after" ]; then
+        log_test_result "Age Reports Oldest And Newest" "PASS"
+    else
+        log_test_result "Age Reports Oldest And Newest" "FAIL" "$before <= $oldest < $newest <= $after"
+    fi
+    check_exec_output_contains "$output" "Span: " "Age Reports Span"
+}
+
+test_export() {
+    echo -e "${BLUE}=== Testing Export ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert ex_b 2" --exec "insert ex_a 1" --exec "insert ex_b 3" \
+        --exec "insert ex_gone x" --exec "delete ex_gone" --exec "export out.jsonl" --exec "export out.txt text" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "Exported 2 records to out.jsonl" "Export Counts Live Records"
+    
+    if [ "$(cat "$exec_dir/out.jsonl")" = $'{"key":"ex_a","value":"1"}\n{"key":"ex_b","value":"3"}' ]; then
+        log_test_result "Export JSON Lines Sorted And Latest" "PASS"
+    else
+        log_test_result "Export JSON Lines Sorted And Latest" "FAIL"
+    fi
+    if [ "$(cat "$exec_dir/out.txt")" = $'ex_a\t1\nex_b\t3' ]; then
+        log_test_result "Export Text Format" "PASS"
+    else
+        log_test_result "Export Text Format" "FAIL"
+    fi
+    rm -rf "$exec_dir"
+}
+
+test_locked_merge_files() {
+    echo -e "${BLUE}=== Testing Deferred Deletion Of Locked Merge Files ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local args=()
+    for i in {1..30}; do
+        args+=(--exec "insert lock_key_$i lock_value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "simulate-locked-files on" --exec "merge" \
+        --exec "stats" --exec "get lock_key_3" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "Could not remove data_0000000000.dat; queued for deletion" "Locked File Queued For Deletion"
+    check_exec_output_contains "$output" "✓ Merge completed successfully" "Merge Not Aborted By Locked File"
+    check_exec_output_contains "$output" "Files pending deletion: data_0000000000.dat" "Pending Deletion Reported In Stats"
+    check_exec_output_contains "$output" "✓ lock_key_3: lock_value_3$" "Data Readable With Deletion Pending"
+    
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get lock_key_3" < /dev/null 2>&1)
+    if [ ! -e "$exec_dir/storage/data_0000000000.dat" ] && [ ! -e "$exec_dir/storage/.pending_deletes" ]; then
+        log_test_result "Pending Deletion Retried On Open" "PASS"
+    else
+        log_test_result "Pending Deletion Retried On Open" "FAIL"
+    fi
+    check_exec_output_contains "$output" "✓ lock_key_3: lock_value_3$" "Data Intact After Deferred Deletion"
+    rm -rf "$exec_dir"
+}
+
+test_bench_read() {
+    echo -e "${BLUE}=== Testing Read Benchmark ===${NC}"
+    
+    local output=$(run_exec --exec "insert b1 one" --exec "insert b2 two" --exec "insert b3 three" --exec "bench read 50")
+    check_exec_output_contains "$output" "Read 50 random keys (3 distinct).*0 errors" "Bench Read Runs"
+    
+    local min=$(echo "$output" | grep -o "min: [0-9]*" | grep -o "[0-9]*$")
+    local p50=$(echo "$output" | grep -o "p50: [0-9]*" | grep -o "[0-9]*$")
+    local p95=$(echo "$output" | grep -o "p95: [0-9]*" | grep -o "[0-9]*$")
+    local p99=$(echo "$output" | grep -o "p99: [0-9]*" | grep -o "[0-9]*$")
+    local max=$(echo "$output" | grep -o "max: [0-9]*" | grep -o "[0-9]*$")
+    if [ -n "$min" ] && [ -n "$max" ] && [ "$min" -le "$p50" ] && [ "$p50" -le "$p95" ] && [ "$p95" -le "$p99" ] && [ "$p99" -le "$max" ]; then
+        log_test_result "Bench Percentiles Ordered Within Bounds" "PASS"
+    else
+        log_test_result "Bench Percentiles Ordered Within Bounds" "FAIL" "min=$min p50=$p50 p95=$p95 p99=$p99 max=$max"
+    fi
+}
+
+test_bench_merge() {
+    echo -e "${BLUE}=== Testing Merge Estimate ===${NC}"
+    
+    local args=()
+    for i in {10..30}; do
+        args+=(--exec "insert key_$i value_$i")
+    done
+    local output=$(run_exec "${args[@]}" --exec "layout" --exec "bench merge" --exec "layout" --exec "merge")
+    local inactive_len=$(echo "$output" | grep -o "data_0000000000.dat: start 0, length [0-9]*" | head -1 | grep -o "[0-9]*$")
+    local would_read=$(echo "$output" | grep -o "Would read: [0-9]*" | grep -o "[0-9]*$")
+    local would_write=$(echo "$output" | grep -o "Would write: [0-9]*" | grep -o "[0-9]*$")
+    local kept=$(echo "$output" | grep -o "Records kept: [0-9]*" | grep -o "[0-9]*$")
+    local written=$(echo "$output" | grep -o "Wrote [0-9]* active entries" | grep -o "[0-9]*")
+    
+    if [ -n "$would_read" ] && [ "$would_read" -gt 0 ] && [ "$would_read" = "$inactive_len" ]; then
+        log_test_result "Merge Estimate Read Bytes" "PASS"
+    else
+        log_test_result "Merge Estimate Read Bytes" "FAIL" "would read $would_read, inactive file is $inactive_len"
+    fi
+    if [ -n "$would_write" ] && [ "$would_write" -gt 0 ] && [ "$kept" = "$written" ]; then
+        log_test_result "Merge Estimate Matches Merge" "PASS"
+    else
+        log_test_result "Merge Estimate Matches Merge" "FAIL" "would write $would_write, kept $kept, merge wrote $written"
+    fi
+    check_exec_output_contains "$output" "Estimated duration: " "Merge Estimate Reports Duration"
+    if [ "$(echo "$output" | grep -c "data_0000000000.dat: start 0, length $inactive_len")" = "2" ]; then
+        log_test_result "Merge Estimate Modifies Nothing" "PASS"
+    else
+        log_test_result "Merge Estimate Modifies Nothing" "FAIL"
+    fi
+}
+
+test_cache_policy() {
+    echo -e "${BLUE}=== Testing Value Cache Eviction Policy ===${NC}"
+    
+    # Capacity 2: re-reading 'ca' saves it under LRU but not under FIFO, which evicts in insertion order
+    local reads=(--exec "insert ca 1" --exec "insert cb 2" --exec "insert cc 3" --exec "get ca" --exec "get cb" --exec "get ca" --exec "get cc" --exec "cache")
+    local output=$(run_exec --exec "cache 2 fifo" "${reads[@]}")
+    check_exec_output_contains "$output" "Cached keys: cb, cc" "FIFO Evicts In Insertion Order"
+    check_exec_output_contains "$output" "1 hits, 3 misses, 1 evictions" "FIFO Cache Counters"
+    
+    output=$(run_exec --exec "cache 2" "${reads[@]}")
+    check_exec_output_contains "$output" "Cached keys: ca, cc" "LRU Evicts Least Recently Used"
+}
+
+test_cache_pinning() {
+    echo -e "${BLUE}=== Testing Pinned Cache Keys ===${NC}"
+    
+    # Capacity 3: four other keys flood the cache, but the pinned key is still served from it
+    local output=$(run_exec --exec "cache 3" --exec "insert hot H" --exec "insert pa 1" --exec "insert pb 2" --exec "insert pc 3" --exec "insert pd 4" \
+        --exec "pin hot" --exec "get pa" --exec "get pb" --exec "get pc" --exec "get pd" --exec "get hot" --exec "cache")
+    check_exec_output_contains "$output" "✓ Pinned hot$" "Pin Cached Key"
+    check_exec_output_contains "$output" "Cached keys: hot, pc, pd$" "Pinned Key Survives Eviction"
+    check_exec_output_contains "$output" "1 hits, 5 misses, 2 evictions" "Pinned Key Served From Cache"
+    check_exec_output_contains "$output" "Pinned keys: hot (1/65536 bytes)" "Pinned Bytes Reported"
+    
+    output=$(run_exec --exec "cache 3" --exec "insert big 0123456789" --exec "cache pin-budget 8" --exec "pin big" --exec "unpin big")
+    check_exec_output_contains "$output" "Cannot pin big: 'big' needs 10 bytes but only 8 pinned bytes are left" "Pin Budget Enforced"
+    check_exec_output_contains "$output" "✗ big is not pinned" "Unpin Unknown Pin"
+}
+
+test_recent_deletes() {
+    echo -e "${BLUE}=== Testing Recent Deletes ===${NC}"
+    
+    local deletes=()
+    for i in {1..22}; do
+        deletes+=(--exec "delete rd_$i")
+    done
+    local output=$(run_exec "${deletes[@]}" --exec "recent-deletes 3" --exec "recent-deletes 100")
+    local newest=$(echo "$output" | grep -A3 "Last 3 of" | grep -o "rd_[0-9]*" | tr '\n' ' ')
+    if [ "$newest" = "rd_22 rd_21 rd_20 " ]; then
+        log_test_result "Recent Deletes Newest First" "PASS"
+    else
+        log_test_result "Recent Deletes Newest First" "FAIL" "Got '$newest'"
+    fi
+    check_exec_output_contains "$output" "Last 20 of 20 recent deletes" "Recent Deletes Capped At Capacity"
+    if echo "$output" | grep -A20 "Last 20 of" | grep -q "rd_2 at"; then
+        log_test_result "Oldest Deletes Dropped" "FAIL" "rd_2 still listed"
+    else
+        log_test_result "Oldest Deletes Dropped" "PASS"
+    fi
+}
+
+test_write_location() {
+    echo -e "${BLUE}=== Testing Write Locations ===${NC}"
+    
+    # 17-byte header + 3-byte key puts the first value at offset 20; after it and the 4-byte CRC the next record starts at 27
+    local output=$(run_exec --exec "insert loc abc" --exec "insert lo2 xy" --exec "get loc" --exec "get lo2")
+    check_exec_output_contains "$output" "Inserted loc: abc (file: data_0000000000.dat, value_offset: 20)" "First Write Location"
+    check_exec_output_contains "$output" "Inserted lo2: xy (file: data_0000000000.dat, value_offset: 47)" "Second Write Location"
+    check_exec_output_contains "$output" "✓ loc: abc$" "Read Back Through Returned Location"
+    check_exec_output_contains "$output" "✓ lo2: xy$" "CRC Of Returned Location Validates"
+}
+
+test_scan_pagination() {
+    echo -e "${BLUE}=== Testing Paginated Scan ===${NC}"
+    
+    local inserts=()
+    for i in {1..10}; do
+        inserts+=(--exec "insert page_key_$i v$i")
+    done
+    local output=$(run_exec "${inserts[@]}" --exec "scan 3")
+    check_exec_output_contains "$output" "Page 4 (1 keys)" "Scan Pages Of 3"
+    
+    local scanned=$(echo "$output" | grep "^Page" | grep -o "page_key_[0-9]*" | sort)
+    local expected=$(for i in {1..10}; do echo "page_key_$i"; done | sort)
+    if [ "$scanned" = "$expected" ]; then
+        log_test_result "Scan Covers All Keys Without Duplicates" "PASS"
+    else
+        log_test_result "Scan Covers All Keys Without Duplicates" "FAIL" "Got: $(echo $scanned)"
+    fi
+    
+    output=$(run_exec "${inserts[@]}" --exec "scan 3 0:0")
+    check_exec_output_contains "$output" "Next cursor: [0-9]*:[0-9]*" "Scan Returns Resume Cursor"
+}
+
+test_scan_prefix() {
+    echo -e "${BLUE}=== Testing Prefix Scan ===${NC}"
+    
+    local output=$(run_exec --exec "insert user:2 bob" --exec "insert user:1 alice" --exec "insert order:1 book" \
+        --exec "insert user:3 carol" --exec "delete user:3" --exec "insert user:1 alicia" --exec "scan prefix user:")
+    check_exec_output_contains "$output" "2 keys starting with 'user:'" "Prefix Scan Skips Deleted Keys"
+    check_exec_output_contains "$output" "^  user:1: alicia$" "Prefix Scan Returns Latest Value"
+    local scanned=$(echo "$output" | grep "^  [a-z]*:[0-9]*: " | cut -d: -f1-2 | tr -d ' ' | tr '\n' ' ')
+    if [ "$scanned" = "user:1 user:2 " ]; then
+        log_test_result "Prefix Scan Sorted And Filtered" "PASS"
+    else
+        log_test_result "Prefix Scan Sorted And Filtered" "FAIL" "Got: $scanned"
+    fi
+}
+
+test_hash_table_config() {
+    echo -e "${BLUE}=== Testing Hash Table Config ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local method
+    for method in linear_probing quadratic_probing double_hashing robin_hood chaining Linear_Probing bogus; do
+        local exec_dir=$(mktemp -d)
+        sed "s/^collision_method = .*/collision_method = \"$method\"/" config.toml > "$exec_dir/config.toml"
+        local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert cfg_key v" --exec "get cfg_key" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        
+        case "$method" in
+            bogus)
+                check_exec_output_contains "$output" "unknown collision method 'bogus'" "Invalid Collision Method Rejected"
+                ;;
+            *)
+                local expected=$(echo "$method" | tr 'A-Z' 'a-z' | sed -r 's/(^|_)([a-z])/\U\2/g')
+                check_exec_output_contains "$output" "Hash table: 127 buckets, $expected" "Collision Method $method"
+                check_exec_output_contains "$output" "✓ cfg_key: v$" "Reads Work With $method"
+                ;;
+        esac
+    done
+}
+
+test_hash_table_growth() {
+    echo -e "${BLUE}=== Testing Hash Table Growth ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local method
+    for method in linear_probing quadratic_probing double_hashing robin_hood chaining; do
+        local exec_dir=$(mktemp -d)
+        sed -e "s/^size = .*/size = 5/" -e "s/^collision_method = .*/collision_method = \"$method\"/" config.toml > "$exec_dir/config.toml"
+        local args=()
+        for i in {1..40}; do
+            args+=(--exec "insert grow_$i v$i")
+        done
+        local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "get grow_1" --exec "get grow_40" --exec "stats" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        
+        check_exec_output_contains "$output" "✓ grow_1: v1$" "Early Key Survives Growth ($method)"
+        check_exec_output_contains "$output" "✓ grow_40: v40$" "Late Key Inserted After Growth ($method)"
+        check_exec_output_contains "$output" "Index: 40 live keys and 0 tombstones in 97 buckets (load factor 0.41)" "Table Grew Past Load Factor ($method)"
+    done
+}
+
+test_list_keys() {
+    echo -e "${BLUE}=== Testing Key Listing ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local method
+    for method in linear_probing chaining; do
+        local exec_dir=$(mktemp -d)
+        sed -e "s/^size = .*/size = 5/" -e "s/^collision_method = .*/collision_method = \"$method\"/" config.toml > "$exec_dir/config.toml"
+        local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert list_b 2" --exec "insert list_a 1" --exec "insert list_c 3" \
+            --exec "insert list_a 4" --exec "list" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        
+        check_exec_output_contains "$output" "✓ 3 keys in the index:" "List Counts Keys ($method)"
+        if echo "$output" | grep -A3 "keys in the index" | tail -3 | tr -d ' ' | paste -sd, | grep -q "^list_a,list_b,list_c$"; then
+            log_test_result "List Shows Every Key Once, Sorted ($method)" "PASS"
+        else
+            log_test_result "List Shows Every Key Once, Sorted ($method)" "FAIL"
+        fi
+    done
+}
+
+test_index_len() {
+    echo -e "${BLUE}=== Testing Index Key Count ===${NC}"
+    
+    # 'a', 'l' and 'w' share a home bucket, so deleting them from the index exercises the cluster rehash
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e "s/^size = .*/size = 5/" -e "s/^collision_method = .*/collision_method = \"linear_probing\"/" config.toml > "$exec_dir/config.toml"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert a 1" --exec "insert b 2" --exec "timestamp" < /dev/null 2>&1)
+    local ts=$(echo "$output" | grep -o "timestamp: [0-9]*" | grep -o "[0-9]*$")
+    
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert l 3" --exec "insert w 4" --exec "insert m 5" --exec "stats" \
+        --exec "truncate-to $ts confirm" --exec "stats" --exec "get a" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "Index: 5 live keys and 0 tombstones in 11 buckets" "Index Counts Inserted Keys"
+    check_exec_output_contains "$output" "Index: 2 live keys and 0 tombstones in 11 buckets" "Index Count Survives Deletes"
+    check_exec_output_contains "$output" "✓ a: 1$" "Remaining Key Readable After Deletes"
+}
+
+test_open_addressing_tombstones() {
+    echo -e "${BLUE}=== Testing Open Addressing Tombstones ===${NC}"
+    
+    # Merge drops keys whose latest record is a delete, so the filler pushes the deletes into an inactive file first.
+    # 40 keys grow the table from 31 to 67 buckets, so compaction kicks in past 16 tombstones
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e "s/^size = .*/size = 31/" -e "s/^collision_method = .*/collision_method = \"quadratic_probing\"/" config.toml > "$exec_dir/config.toml"
+    local inserts=()
+    for i in {10..29}; do
+        inserts+=(--exec "insert tk_$i v$i")
+    done
+    local filler=()
+    for i in {1..20}; do
+        filler+=(--exec "insert filler_$i x")
+    done
+    
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${inserts[@]}" --exec "delete tk_10" --exec "delete tk_11" --exec "delete tk_12" "${filler[@]}" \
+        --exec "merge" --exec "stats" --exec "get tk_10" --exec "get tk_29" --exec "insert tk_10 back" --exec "stats" --exec "duplicates" < /dev/null 2>&1)
+    rm -rf "$exec_dir/storage"
+    check_exec_output_contains "$output" "Deleted slots awaiting compaction: 3$" "Deletes Leave Tombstones"
+    check_exec_output_contains "$output" "Key 'tk_10' not found" "Deleted Key Not Found"
+    check_exec_output_contains "$output" "✓ tk_29: v29$" "Lookups Probe Past Tombstones"
+    check_exec_output_contains "$output" "Deleted slots awaiting compaction: 2$" "Insert Reuses Tombstone"
+    check_exec_output_contains "$output" "No duplicate keys in the index" "No Duplicates After Reuse"
+    
+    local deletes=()
+    for i in {10..26}; do
+        deletes+=(--exec "delete tk_$i")
+    done
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${inserts[@]}" "${deletes[@]}" "${filler[@]}" --exec "merge" --exec "stats" --exec "get tk_27" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "Removed 17 deleted keys from hash table" "Seventeen Keys Deleted"
+    if echo "$output" | grep -q "Deleted slots awaiting compaction"; then
+        log_test_result "Tombstones Compacted Past Threshold" "FAIL" "tombstones still reported"
+    else
+        log_test_result "Tombstones Compacted Past Threshold" "PASS"
+    fi
+    check_exec_output_contains "$output" "✓ tk_27: v27$" "Keys Readable After Compaction"
+}
+
+test_hash_function() {
+    echo -e "${BLUE}=== Testing Configurable Hash Function ===${NC}"
+    
+    # 'a', 'l' and 'w' share a home bucket under the polynomial hash with 11 buckets; FNV-1a spreads them out
+    local app_bin="$(pwd)/$APP_PATH"
+    local hash
+    for hash in polynomial fnv1a bogus; do
+        local exec_dir=$(mktemp -d)
+        sed -e "s/^size = .*/size = 11/" -e "s/^collision_method = .*/collision_method = \"double_hashing\"/" \
+            -e "s/^hash_function = .*/hash_function = \"$hash\"/" config.toml > "$exec_dir/config.toml"
+        local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert a 1" --exec "insert l 2" --exec "insert w 3" --exec "rehash" --exec "get w" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        
+        case "$hash" in
+            polynomial)
+                check_exec_output_contains "$output" "After:  max probe length 2, avg probe length 1.67" "Polynomial Hash Clusters Keys"
+                check_exec_output_contains "$output" "✓ w: 3$" "Lookup With Polynomial Hash"
+                ;;
+            fnv1a)
+                check_exec_output_contains "$output" "fnv1a hash" "FNV-1a Hash Configured"
+                check_exec_output_contains "$output" "After:  max probe length 1, avg probe length 1.00" "FNV-1a Hash Spreads Keys"
+                check_exec_output_contains "$output" "✓ w: 3$" "Lookup With FNV-1a Hash"
+                ;;
+            bogus) check_exec_output_contains "$output" "unknown hash function 'bogus'" "Invalid Hash Function Rejected" ;;
+        esac
+    done
+}
+
+test_grow_recommended() {
+    echo -e "${BLUE}=== Testing Grow Recommendation ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e "s/^size = .*/size = 10/" config.toml > "$exec_dir/config.toml"
+    local inserts=()
+    for i in {1..6}; do
+        inserts+=(--exec "insert grow_$i v$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${inserts[@]}" --exec "stats" < /dev/null 2>&1)
+    if echo "$output" | grep -q "Grow recommended"; then
+        log_test_result "No Grow Recommendation Below Limit" "FAIL" "6 keys in 10 buckets should not recommend growing"
+    else
+        log_test_result "No Grow Recommendation Below Limit" "PASS"
+    fi
+    
+    # 7 keys reach the configured 0.7 limit, so the next new key would grow the table; 11 buckets keep them under it
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert grow_7 v7" --exec "stats" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "Index: 7 live keys and 0 tombstones in 10 buckets" "Table Not Yet Grown"
+    check_exec_output_contains "$output" "Grow recommended: load factor at the 0.70 limit; 11 buckets would bring it back under" "Grow Recommendation Capacity"
+    
+    # The recommendation follows the configured limit
+    sed -i "s/^max_load_factor = .*/max_load_factor = 0.9/" "$exec_dir/config.toml"
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "stats" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    if echo "$output" | grep -q "Grow recommended"; then
+        log_test_result "Grow Recommendation Uses Configured Limit" "FAIL" "7 keys in 10 buckets are under a 0.9 limit"
+    else
+        log_test_result "Grow Recommendation Uses Configured Limit" "PASS"
+    fi
+}
+
+test_sync_only_when_dirty() {
+    echo -e "${BLUE}=== Testing Sync Dirty Tracking ===${NC}"
+    
+    local output=$(run_exec --exec "sync" --exec "insert sync_key v" --exec "sync" --exec "sync" --exec "stats")
+    check_exec_output_contains "$output" "Fsyncs performed: 1" "Redundant Syncs Skipped"
+    
+    output=$(run_exec --exec "insert sync_key v" --exec "sync" --exec "insert sync_key w" --exec "sync" --exec "stats")
+    check_exec_output_contains "$output" "Fsyncs performed: 2" "Sync After Each Write"
+}
+
+test_durability_mode() {
+    echo -e "${BLUE}=== Testing Durability Modes ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local mode
+    for mode in none every_write interval:60000 bogus; do
+        local exec_dir=$(mktemp -d)
+        sed "s/^durability = .*/durability = \"$mode\"/" config.toml > "$exec_dir/config.toml"
+        local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert d1 1" --exec "insert d2 2" --exec "insert d3 3" --exec "stats" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        
+        case "$mode" in
+            none) check_exec_output_contains "$output" "Fsyncs performed: 0" "No Fsync Per Write ($mode)" ;;
+            every_write) check_exec_output_contains "$output" "Fsyncs performed: 3" "Fsync Every Write ($mode)" ;;
+            interval:60000) check_exec_output_contains "$output" "Fsyncs performed: 0" "No Fsync Before Interval ($mode)" ;;
+            bogus) check_exec_output_contains "$output" "unknown durability mode 'bogus'" "Invalid Durability Mode Rejected" ;;
+        esac
+    done
+    
+    # The first write comes after the interval has passed and syncs; the second follows immediately and doesn't
+    local exec_dir=$(mktemp -d)
+    sed "s/^durability = .*/durability = \"interval:50\"/" config.toml > "$exec_dir/config.toml"
+    local output=$(cd "$exec_dir" && (sleep 0.3; echo "insert d1 1"; echo "insert d2 2"; echo "stats"; echo "exit") | timeout 10 "$app_bin" 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "Fsyncs performed: 1$" "Fsync Once Interval Elapsed"
+    
+    # 14 records of 35 bytes fill data_0000000000.dat; the 15th rotates, which syncs the outgoing file once
+    local inserts=()
+    for i in {10..24}; do
+        inserts+=(--exec "insert key_$i value_$i")
+    done
+    for mode in none interval:60000; do
+        exec_dir=$(mktemp -d)
+        sed "s/^durability = .*/durability = \"$mode\"/" config.toml > "$exec_dir/config.toml"
+        output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${inserts[@]}" --exec "stats" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        check_exec_output_contains "$output" "data_0000000001.dat: 35 bytes (ACTIVE)" "Inserts Rotate Once ($mode)"
+        check_exec_output_contains "$output" "Fsyncs performed: 1$" "Rotation Syncs Outgoing File ($mode)"
+    done
+    
+    # A pause longer than poll_timeout_ms syncs the pending insert, unless sync_on_idle is off
+    local setting
+    for setting in true false; do
+        exec_dir=$(mktemp -d)
+        sed -e "s/^sync_on_idle = .*/sync_on_idle = $setting/" -e "s/^poll_timeout_ms = .*/poll_timeout_ms = 50/" config.toml > "$exec_dir/config.toml"
+        output=$(cd "$exec_dir" && (echo "insert d1 1"; sleep 0.3; echo "stats"; echo "exit") | timeout 10 "$app_bin" 2>&1)
+        rm -rf "$exec_dir"
+        case "$setting" in
+            true) check_exec_output_contains "$output" "Fsyncs performed: 1$" "Idle Sync Flushes Pending Write" ;;
+            false)
+                check_exec_output_contains "$output" "Idle sync: disabled" "Idle Sync Setting Reported"
+                check_exec_output_contains "$output" "Fsyncs performed: 0$" "Idle Sync Disabled By Config" ;;
+        esac
+    done
+}
+
+test_legacy_format() {
+    echo -e "${BLUE}=== Testing Legacy Format Files ===${NC}"
+    
+    # A version 1 store: 8-byte headers, no .meta, and a tombstone written as the marker value
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/config.toml"
+    mkdir -p "$exec_dir/storage"
+    printf '\x03\x00\x00\x00\x02\x00\x00\x00oldv1' > "$exec_dir/storage/data_000.dat"
+    printf '\x04\x00\x00\x00\x01\x00\x00\x00gonex' >> "$exec_dir/storage/data_000.dat"
+    printf '\x04\x00\x00\x00\x09\x00\x00\x00gone\\DELETED\\' >> "$exec_dir/storage/data_000.dat"
+    
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get old" --exec "get gone" --exec "insert new v3" --exec "stats" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "✓ old: v1$" "Version 1 Record Readable"
+    check_exec_output_contains "$output" "Key 'gone' not found" "Version 1 Tombstone Honoured"
+    check_exec_output_contains "$output" "data_0000000001.dat: .*(ACTIVE)" "New Writes Go To A Fresh File"
+    check_exec_output_contains "$(cat "$exec_dir/storage/.meta")" "\"data_000.dat\" = 1" "Legacy File Recorded In Meta"
+    
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "merge" --exec "get old" --exec "get new" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "✓ old: v1$" "Legacy Record Survives Merge"
+    check_exec_output_contains "$output" "✓ new: v3$" "Current Record Survives Merge"
+    
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get old" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "✓ old: v1$" "Merged Legacy Record Readable After Restart"
+    if grep -q "data_000.dat" "$exec_dir/storage/.meta"; then
+        log_test_result "Merged Legacy File Dropped From Meta" "FAIL" "data_000.dat still listed in .meta"
+    else
+        log_test_result "Merged Legacy File Dropped From Meta" "PASS"
+    fi
+    rm -rf "$exec_dir"
+}
+
+test_relocate() {
+    echo -e "${BLUE}=== Testing Store Relocation ===${NC}"
+    
+    local output=$(run_exec --exec "insert mv_a 1" --exec "insert mv_b 2" --exec "relocate moved_store" --exec "get mv_a" --exec "insert mv_c 3" --exec "get mv_c" --exec "stats")
+    check_exec_output_contains "$output" "Store relocated to 'moved_store/'" "Relocate Succeeds"
+    check_exec_output_contains "$output" "✓ mv_a: 1$" "Reads Continue After Relocate"
+    check_exec_output_contains "$output" "✓ mv_c: 3$" "Writes Continue After Relocate"
+    
+    output=$(run_exec --exec "insert mv_a 1" --exec "relocate moved_store" --exec "relocate moved_store")
+    check_exec_output_contains "$output" "already contains data files" "Relocate Refuses Populated Directory"
+    
+    # A directory named .meta in the target makes the last move fail after the data files have moved
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    mkdir -p "$exec_dir/moved_store/.meta"
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert mv_a 1" --exec "relocate moved_store" --exec "get mv_a" --exec "insert mv_b 2" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "✗ Failed to relocate to moved_store" "Failed Relocate Reported"
+    check_exec_output_contains "$output" "✓ mv_a: 1$" "Reads Continue After Failed Relocate"
+    if [ -e "$exec_dir/storage/data_0000000000.dat" ] && [ -f "$exec_dir/storage/.meta" ] && [ -z "$(ls "$exec_dir/moved_store")" ]; then
+        log_test_result "Failed Relocate Rolled Back" "PASS"
+    else
+        log_test_result "Failed Relocate Rolled Back" "FAIL" "files left in moved_store: $(ls "$exec_dir/moved_store")"
+    fi
+    rm -rf "$exec_dir"
+    
+    # A merged file still pending deletion moves along with the list naming it, so the new directory finishes the deletion
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local args=()
+    for i in {1..30}; do
+        args+=(--exec "insert mv_key_$i mv_value_$i")
+    done
+    (cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "simulate-locked-files on" --exec "merge" --exec "relocate moved_store" < /dev/null > /dev/null 2>&1)
+    if [ -e "$exec_dir/moved_store/data_0000000000.dat" ] && [ -e "$exec_dir/moved_store/.pending_deletes" ] && [ ! -e "$exec_dir/storage/data_0000000000.dat" ]; then
+        log_test_result "Pending Deletes Relocated" "PASS"
+    else
+        log_test_result "Pending Deletes Relocated" "FAIL"
+    fi
+    sed -i 's/^directory = .*/directory = "moved_store"/' "$exec_dir/config.toml"
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get mv_key_3" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "✓ mv_key_3: mv_value_3$" "Relocated Store Reopens"
+    if [ ! -e "$exec_dir/moved_store/data_0000000000.dat" ] && [ ! -e "$exec_dir/moved_store/.pending_deletes" ]; then
+        log_test_result "Pending Deletion Finished In New Directory" "PASS"
+    else
+        log_test_result "Pending Deletion Finished In New Directory" "FAIL"
+    fi
+    rm -rf "$exec_dir"
+}
+
+test_index_key_mismatch() {
+    echo -e "${BLUE}=== Testing Index Key Mismatch Detection ===${NC}"
+    
+    # 'c' has the same value length as 'a', so only the key check stops its value being returned for 'a'
+    local output=$(run_exec --exec "insert a 1" --exec "insert bb 2" --exec "insert c 3" --exec "simulate-misdirect a c" --exec "get a" \
+        --exec "simulate-misdirect c bb" --exec "get c" --exec "get bb")
+    check_exec_output_contains "$output" "Index entry for 'a' does not point at a record for that key" "Misdirected Read Detected"
+    if echo "$output" | grep -q "✓ a: 3"; then
+        log_test_result "Wrong Value Not Returned" "FAIL" "got c's value for a"
+    else
+        log_test_result "Wrong Value Not Returned" "PASS"
+    fi
+    check_exec_output_contains "$output" "Index entry for 'c' does not point at a record for that key" "Mismatched Key Length Detected"
+    check_exec_output_contains "$output" "✓ bb: 2$" "Correct Entries Unaffected"
+}
+
+test_self_test() {
+    echo -e "${BLUE}=== Testing Self-Test Command ===${NC}"
+    
+    local output=$(run_exec --exec "insert real_key real_value" --exec "selftest" --exec "list" --exec "get real_key")
+    check_exec_output_contains "$output" "✓ merge: live keys kept, deleted key dropped" "Self-Test Merge Step Passes"
+    check_exec_output_contains "$output" "✓ Self-test passed (7 steps)" "Self-Test All Steps Pass"
+    check_exec_output_contains "$output" "✓ 1 keys in the index" "Self-Test Leaves Index Alone"
+    check_exec_output_contains "$output" "✓ real_key: real_value$" "Self-Test Leaves Data Alone"
+}
+
+test_read_only_mode() {
+    echo -e "${BLUE}=== Testing Read-Only Mode ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert ro_a 1" --exec "insert ro_b 2" --exec "delete ro_b" < /dev/null > /dev/null 2>&1)
+    local before=$(cd "$exec_dir" && ls -A storage && md5sum storage/*)
+    
+    sed -i "s/^read_only = .*/read_only = true/" "$exec_dir/config.toml"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get ro_a" --exec "insert ro_c 3" --exec "delete ro_a" \
+        --exec "merge" --exec "truncate-to 0 confirm" --exec "selftest" < /dev/null 2>&1)
+    local after=$(cd "$exec_dir" && ls -A storage && md5sum storage/*)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Read-only: writes, deletes and merges are rejected" "Read-Only Mode Reported"
+    check_exec_output_contains "$output" "✓ ro_a: 1$" "Reads Work Read-Only"
+    check_exec_output_contains "$output" "Failed to insert ro_c: Storage was opened read-only" "Write Rejected Read-Only"
+    check_exec_output_contains "$output" "Failed to delete ro_a: Storage was opened read-only" "Delete Rejected Read-Only"
+    check_exec_output_contains "$output" "Merge failed: Storage was opened read-only" "Merge Rejected Read-Only"
+    check_exec_output_contains "$output" "✗ setup: Storage was opened read-only" "Self-Test Refused Read-Only"
+    if [ "$before" = "$after" ]; then
+        log_test_result "Directory Unchanged Read-Only" "PASS"
+    else
+        log_test_result "Directory Unchanged Read-Only" "FAIL" "files changed"
+    fi
+}
+
+test_merge_into_dir() {
+    echo -e "${BLUE}=== Testing Merge Into Another Directory ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert mi_a 1" --exec "insert mi_b 2" --exec "insert mi_a 3" \
+        --exec "delete mi_b" --exec "insert mi_c 4" < /dev/null > /dev/null 2>&1)
+    local before=$(cd "$exec_dir" && ls -A storage && md5sum storage/*)
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "merge-into migrated" --exec "merge-into migrated" < /dev/null 2>&1)
+    local after=$(cd "$exec_dir" && ls -A storage && md5sum storage/*)
+    
+    sed -i "s/^directory = .*/directory = \"migrated\"/" "$exec_dir/config.toml"
+    local migrated=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "records" --exec "get mi_a" --exec "get mi_b" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Merged 2 live records from 1 files into 'migrated/', all verified" "Live Records Merged Into Directory"
+    check_exec_output_contains "$output" "migrated already contains data files" "Non-Empty Destination Rejected"
+    if [ "$before" = "$after" ]; then
+        log_test_result "Source Unchanged By Merge Into" "PASS"
+    else
+        log_test_result "Source Unchanged By Merge Into" "FAIL" "source files changed"
+    fi
+    check_exec_output_contains "$migrated" "(1 read from hint files)" "Destination Has Hint Files"
+    check_exec_output_contains "$migrated" "✓ 2 records on disk" "One Record Per Live Key"
+    check_exec_output_contains "$migrated" "✓ mi_a: 3$" "Latest Value Migrated"
+    check_exec_output_contains "$migrated" "mi_b' not found" "Deleted Key Not Migrated"
+    
+    # md_a, md_b and md_c are 26 bytes each; md_b is damaged at its value
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert md_a 1" --exec "insert md_b 2" --exec "insert md_c 3" < /dev/null > /dev/null 2>&1)
+    printf 'x' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=47 conv=notrunc 2>/dev/null
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "merge-into migrated" < /dev/null 2>&1)
+    sed -i "s/^directory = .*/directory = \"migrated\"/" "$exec_dir/config.toml"
+    migrated=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get md_c" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Merged 2 live records from 1 files into 'migrated/', all verified; 1 damaged records left out" "Merge Into Reports Damaged Record"
+    check_exec_output_contains "$migrated" "✓ md_c: 3$" "Record After Damage Migrated"
+}
+
+test_reopen_file_counter() {
+    echo -e "${BLUE}=== Testing File Counter On Reopen ===${NC}"
+    
+    # Files numbered 003 and 041 in the narrower names of earlier versions, with nothing in between:
+    # appends must continue in 041 and rotate to 042, named with the current ten-digit counter
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert fc_old 1" < /dev/null > /dev/null 2>&1 \
+        && mv storage/data_0000000000.dat storage/data_041.dat && cp storage/data_041.dat storage/data_003.dat)
+    local value=$(printf 'v%.0s' {1..40})
+    local inserts=()
+    for i in {1..10}; do
+        inserts+=(--exec "insert fc_key_$i $value")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "stats" "${inserts[@]}" --exec "get fc_old" < /dev/null 2>&1)
+    local files=$(cd "$exec_dir" && ls storage | grep "\.dat$" | tr '\n' ' ')
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "data_041.dat: 28 bytes (ACTIVE)" "Highest Numbered File Reopened As Active"
+    check_exec_output_contains "$output" "Inserted fc_key_1: .*file: data_041.dat, value_offset: 53)" "Appends Continue After Existing Records"
+    check_exec_output_contains "$output" "Inserted fc_key_10: .*file: data_0000000042.dat" "Rotation Picks Next Number"
+    if [ "$files" = "data_0000000042.dat data_003.dat data_041.dat " ]; then
+        log_test_result "No Out-Of-Order File Created" "PASS"
+    else
+        log_test_result "No Out-Of-Order File Created" "FAIL" "Files: $files"
+    fi
+    check_exec_output_contains "$output" "✓ fc_old: 1$" "Reopened File Still Read"
+}
+
+test_verify_command() {
+    echo -e "${BLUE}=== Testing Verified Reads ===${NC}"
+    
+    local output=$(run_exec --exec "cache 10" --exec "insert va 1" --exec "insert vc 3" --exec "insert vd 4" --exec "delete vd" \
+        --exec "get va" --exec "verify va" --exec "verify vd" --exec "simulate-misdirect va vc" --exec "verify va" --exec "verify vz")
+    check_exec_output_contains "$output" "✓ va: 1 (record verified on disk in data_0000000000.dat at value offset" "Verified Read Returns Value"
+    check_exec_output_contains "$output" "Key 'vd' is deleted (tombstone verified on disk" "Verified Read Reports Tombstone"
+    check_exec_output_contains "$output" "Verification of va failed: .*Index entry for 'va' does not point at a record for that key" "Verified Read Detects Wrong Record"
+    check_exec_output_contains "$output" "Key 'vz' not found" "Verified Read Of Missing Key"
+}
+
+test_background_merge() {
+    echo -e "${BLUE}=== Testing Background Merge ===${NC}"
+    
+    # Commands arrive without pause, so only the background thread can merge before the stats
+    local app_bin="$(pwd)/$APP_PATH"
+    local value=$(printf 'v%.0s' {1..40})
+    local trigger
+    for trigger in files seconds; do
+        local exec_dir=$(mktemp -d)
+        sed -e "s/^background_merge_$trigger = .*/background_merge_$trigger = 2/" config.toml > "$exec_dir/config.toml"
+        local output=$(cd "$exec_dir" && (for i in {1..40}; do echo "insert bg_key_$((i % 5)) $value"; done; sleep 3; echo "stats"; echo "get bg_key_3"; echo "exit") \
+            | timeout 15 "$app_bin" 2>&1)
+        rm -rf "$exec_dir"
+        
+        case "$trigger" in
+            files) check_exec_output_contains "$output" "Background merge: past 2 new inactive files" "Background Merge Configured ($trigger)" ;;
+            seconds) check_exec_output_contains "$output" "Background merge: every 2 seconds" "Background Merge Configured ($trigger)" ;;
+        esac
+        check_exec_output_contains "$output" "Background merge of [0-9]* inactive files" "Background Merge Ran ($trigger)"
+        check_exec_output_contains "$output" "Total: [12] files" "Inactive Files Merged Away ($trigger)"
+        check_exec_output_contains "$output" "✓ bg_key_3: $value$" "Data Intact After Background Merge ($trigger)"
+    done
+}
+
+test_size_limits() {
+    echo -e "${BLUE}=== Testing Key And Value Size Limits ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e "s/^max_key_size = .*/max_key_size = 4/" -e "s/^max_value_size = .*/max_value_size = 8/" config.toml > "$exec_dir/config.toml"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert abcd 12345678" --exec "insert abcde 1" --exec "insert k 123456789" \
+        --exec "delete abcd 12345678" --exec "delete k 123456789" --exec "records" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Max key size: 4 bytes, max value size: 8 bytes" "Size Limits Configured"
+    check_exec_output_contains "$output" "✓ Inserted abcd: 12345678" "Key And Value At Limit Accepted"
+    check_exec_output_contains "$output" "Failed to insert abcde: Key is 5 bytes, over the 4 byte limit" "Key One Byte Over Rejected"
+    check_exec_output_contains "$output" "Failed to insert k: Value is 9 bytes, over the 8 byte limit" "Value One Byte Over Rejected"
+    check_exec_output_contains "$output" "✓ Deleted abcd" "Delete Reason At Limit Accepted"
+    check_exec_output_contains "$output" "Failed to delete k: Value is 9 bytes, over the 8 byte limit" "Delete Reason Over Limit Rejected"
+    check_exec_output_contains "$output" "✓ 2 records on disk" "Rejected Writes Leave Nothing On Disk"
+    
+    local limit
+    for limit in max_key_size max_value_size; do
+        exec_dir=$(mktemp -d)
+        sed -e "s/^$limit = .*/$limit = 4294967296/" config.toml > "$exec_dir/config.toml"
+        output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "stats" < /dev/null 2>&1)
+        rm -rf "$exec_dir"
+        check_exec_output_contains "$output" "Invalid \[storage\] size limits in config.toml: $limit 4294967296 is over the 4294967295 bytes a record can hold" "Oversized $limit Rejected"
+    done
+}
+
+test_merge_output_files() {
+    echo -e "${BLUE}=== Testing Merge Into Dedicated Files ===${NC}"
+    
+    # data_0000000000.dat holds mo_key_1..13; mo_key_1 is then overwritten and mo_key_2 deleted in the active data_0000000001.dat
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local args=()
+    for i in {1..20}; do
+        args+=(--exec "insert mo_key_$i value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "insert mo_key_1 updated" --exec "delete mo_key_2" \
+        --exec "stats" --exec "merge" --exec "stats" --exec "get mo_key_1" --exec "get mo_key_2" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "Wrote 11 active entries to 1 merge file(s)" "Survivors Written To Merge File"
+    check_exec_output_contains "$output" "Skipped 2 entries superseded in the active file" "Superseded Entries Skipped"
+    if [ "$(echo "$output" | grep -c "data_0000000001.dat: 331 bytes (ACTIVE)")" = "2" ]; then
+        log_test_result "Active File Untouched By Merge" "PASS"
+    else
+        log_test_result "Active File Untouched By Merge" "FAIL"
+    fi
+    check_exec_output_contains "$output" "merge_0000000000.dat: [0-9]* bytes$" "Merge File Listed In Stats"
+    check_exec_output_contains "$output" "✓ mo_key_1: updated$" "Newer Value Kept After Merge"
+    check_exec_output_contains "$output" "Key 'mo_key_2' has been deleted" "Newer Delete Kept After Merge"
+    
+    # On restart the merge file replays before the active file; a second merge replaces it with the next numbers
+    args=()
+    for i in {21..40}; do
+        args+=(--exec "insert mo_key_$i value_$i")
+    done
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get mo_key_1" --exec "get mo_key_2" "${args[@]}" --exec "merge" \
+        --exec "get mo_key_1" --exec "get mo_key_3" < /dev/null 2>&1)
+    local files=$(ls "$exec_dir/storage" | grep "\.dat$" | tr '\n' ' ')
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "✓ mo_key_1: updated$" "Newer Value Kept After Restart"
+    check_exec_output_contains "$output" "Key 'mo_key_2' not found" "Newer Delete Kept After Restart"
+    check_exec_output_contains "$output" "Removed old file: merge_0000000000.dat" "Earlier Merge File Merged Again"
+    check_exec_output_contains "$output" "✓ mo_key_3: value_3$" "Merged Key Readable After Second Merge"
+    if echo "$files" | grep -q "^data_[0-9]*.dat merge_0000000001.dat merge_0000000002.dat merge_0000000003.dat $"; then
+        log_test_result "Second Merge Continues Merge File Numbers" "PASS"
+    else
+        log_test_result "Second Merge Continues Merge File Numbers" "FAIL" "files: $files"
+    fi
+}
+
+test_partial_tail_recovery() {
+    echo -e "${BLUE}=== Testing Partial Trailing Record Recovery ===${NC}"
+    
+    # A crash after writing 10 of a record's 17 header bytes leaves them at the end of the active file
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert pt_a one" < /dev/null > /dev/null 2>&1)
+    local valid_len=$(wc -c < "$exec_dir/storage/data_0000000000.dat")
+    printf '\x04\x00\x00\x00\x03\x00\x00\x00\x01\x00' >> "$exec_dir/storage/data_0000000000.dat"
+    
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert pt_b two" --exec "get pt_a" --exec "get pt_b" < /dev/null 2>&1)
+    local final_len=$(wc -c < "$exec_dir/storage/data_0000000000.dat")
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get pt_b" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Truncated a 10-byte uncommitted record left by an interrupted write from data_0000000000.dat" "Partial Record Truncated On Open"
+    check_exec_output_contains "$output" "Inserted pt_b: two (file: data_0000000000.dat, value_offset: $((valid_len + 21)))" "Next Write Lands After Last Complete Record"
+    check_exec_output_contains "$output" "✓ pt_a: one$" "Complete Record Kept"
+    if [ "$final_len" = "$((valid_len + 28))" ] && echo "$restarted" | grep -q "✓ pt_b: two$"; then
+        log_test_result "Appended Record Readable After Restart" "PASS"
+    else
+        log_test_result "Appended Record Readable After Restart" "FAIL" "file is $final_len bytes, expected $((valid_len + 28))"
+    fi
+    
+    # The file grew by a whole 28-byte record for pt_z but its last blocks never reached the disk, so its CRC is zeros
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert pt_a one" < /dev/null > /dev/null 2>&1)
+    printf '\x04\x00\x00\x00\x03\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00pt_zzzz\x00\x00\x00\x00' >> "$exec_dir/storage/data_0000000000.dat"
+    local read_only=$(cd "$exec_dir" && sed -i 's/^read_only = .*/read_only = true/' config.toml && timeout 10 "$app_bin" --exec "records" --exec "get pt_z" < /dev/null 2>&1)
+    sed -i 's/^read_only = .*/read_only = false/' "$exec_dir/config.toml"
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert pt_b two" --exec "get pt_z" --exec "get pt_b" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$read_only" "✓ 1 records on disk:" "Uncommitted Record Ignored By Record Scan"
+    check_exec_output_contains "$read_only" "✗ Key 'pt_z' not found" "Uncommitted Record Not Recovered"
+    check_exec_output_contains "$output" "Truncated a 28-byte uncommitted record left by an interrupted write from data_0000000000.dat" "Uncommitted Record Truncated On Open"
+    check_exec_output_contains "$output" "Inserted pt_b: two (file: data_0000000000.dat, value_offset: $((valid_len + 21)))" "Next Write Replaces Uncommitted Record"
+    check_exec_output_contains "$output" "✓ pt_b: two$" "Write After Uncommitted Record Readable"
+    
+    # A damaged value length makes pt_b run past the end of the file, but pt_c follows it intact: that's damage, not a torn tail
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert pt_a one" --exec "insert pt_b two" --exec "insert pt_c six" < /dev/null > /dev/null 2>&1)
+    valid_len=$(wc -c < "$exec_dir/storage/data_0000000000.dat")
+    printf '\xff' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=32 conv=notrunc 2>/dev/null
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get pt_a" < /dev/null 2>&1)
+    final_len=$(wc -c < "$exec_dir/storage/data_0000000000.dat")
+    rm -rf "$exec_dir"
+    
+    if echo "$output" | grep -q "Truncated a" || [ "$final_len" != "$valid_len" ]; then
+        log_test_result "Damaged Length Mid-File Not Truncated" "FAIL" "file is $final_len bytes, expected $valid_len"
+    else
+        log_test_result "Damaged Length Mid-File Not Truncated" "PASS"
+    fi
+    check_exec_output_contains "$output" "✓ pt_a: one$" "Record Before Damaged Length Readable"
+}
+
+test_insert_batch() {
+    echo -e "${BLUE}=== Testing Batch Inserts ===${NC}"
+    
+    # Under every_write, a batch spanning two files costs one fsync per file instead of one per key
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed "s/^durability = .*/durability = \"every_write\"/" config.toml > "$exec_dir/config.toml"
+    local pairs=""
+    local singles=()
+    for i in {1..20}; do
+        pairs+=" bk_$i=value_$i"
+        singles+=(--exec "insert bk_$i value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert-batch$pairs" --exec "stats" < /dev/null 2>&1)
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get bk_1" --exec "get bk_20" < /dev/null 2>&1)
+    rm -rf "$exec_dir/storage"
+    local single_output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${singles[@]}" --exec "stats" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ Wrote 20 keys in one batch" "Batch Written"
+    check_exec_output_contains "$output" "Inserted bk_16: value_16 (file: data_0000000001.dat, value_offset: 22)" "Batch Rotates Per Record"
+    check_exec_output_contains "$output" "Fsyncs performed: 2$" "One Fsync Per File In Batch"
+    check_exec_output_contains "$single_output" "Fsyncs performed: 20$" "One Fsync Per Single Insert"
+    if [ "$(echo "$output" | grep "data_000000000[0-9].dat: ")" = "$(echo "$single_output" | grep "data_000000000[0-9].dat: ")" ]; then
+        log_test_result "Batch Files Match Single Inserts" "PASS"
+    else
+        log_test_result "Batch Files Match Single Inserts" "FAIL"
+    fi
+    check_exec_output_contains "$restarted" "✓ bk_1: value_1$" "First Batch Key Recovered"
+    check_exec_output_contains "$restarted" "✓ bk_20: value_20$" "Last Batch Key Recovered"
+    
+    output=$(run_exec --exec "insert-batch ok_1=a ok_2" --exec "get ok_1")
+    check_exec_output_contains "$output" "Expected <key>=<value>, got 'ok_2'" "Malformed Pair Rejected"
+    check_exec_output_contains "$output" "Key 'ok_1' not found" "Rejected Batch Writes Nothing"
+}
+
+test_value_compression() {
+    echo -e "${BLUE}=== Testing Value Compression ===${NC}"
+    
+    # Small files put each record in its own file, so the final merge rewrites records from both earlier runs
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    local value=$(printf 'abc%.0s' {1..100})
+    local compression
+    local output=""
+    for compression in lz4 zstd none; do
+        sed -e "s/^compression = .*/compression = \"$compression\"/" -e "s/^max_file_size = .*/max_file_size = 64/" config.toml > "$exec_dir/config.toml"
+        case "$compression" in
+            lz4) output+=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert cz_a $value" --exec "insert cz_short xy" --exec "records" < /dev/null 2>&1) ;;
+            zstd) output+=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert cz_b $value" --exec "get cz_a" < /dev/null 2>&1) ;;
+            none) output+=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert cz_c $value" --exec "merge" --exec "records" \
+                --exec "get cz_a" --exec "get cz_b" --exec "get cz_c" < /dev/null 2>&1) ;;
+        esac
+        output+=$'\n'
+    done
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Compression: Lz4" "Compression Configured"
+    check_exec_output_contains "$output" "put cz_a = $value \[Lz4, [0-9]* bytes stored\]$" "Value Stored With Lz4"
+    check_exec_output_contains "$output" "put cz_short = xy$" "Incompressible Value Stored As Is"
+    check_exec_output_contains "$output" "put cz_b = $value \[Zstd, [0-9]* bytes stored\]$" "Merge Keeps Zstd Value Compressed"
+    check_exec_output_contains "$output" "put cz_c = $value$" "Uncompressed Value After Disabling"
+    if [ "$(echo "$output" | grep -c "✓ cz_a: $value$")" = "2" ] && echo "$output" | grep -q "✓ cz_b: $value$" \
+        && echo "$output" | grep -q "✓ cz_c: $value$"; then
+        log_test_result "Mixed Compression Readable Across Restarts And Merge" "PASS"
+    else
+        log_test_result "Mixed Compression Readable Across Restarts And Merge" "FAIL"
+    fi
+}
+
+test_exists_command() {
+    echo -e "${BLUE}=== Testing Exists Command ===${NC}"
+    
+    # Quadratic probing leaves a deleted slot once merge drops ex_a from the index; probing must skip it
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed "s/^collision_method = .*/collision_method = \"quadratic_probing\"/" config.toml > "$exec_dir/config.toml"
+    local fillers=()
+    for i in {1..20}; do
+        fillers+=(--exec "insert ex_fill_$i value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert ex_a 1" --exec "insert ex_b 2" --exec "exists ex_a" \
+        --exec "delete ex_a" --exec "exists ex_a" --exec "exists ex_missing" "${fillers[@]}" --exec "merge" --exec "exists ex_a" --exec "exists ex_b" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ 'ex_a' is present" "Inserted Key Exists"
+    check_exec_output_contains "$output" "✗ 'ex_a' is absent (deleted)" "Deleted Key Absent Before Merge"
+    check_exec_output_contains "$output" "✗ 'ex_missing' is absent$" "Unknown Key Absent"
+    check_exec_output_contains "$output" "Removed 1 deleted keys from hash table" "Merge Drops Deleted Key From Index"
+    if [ "$(echo "$output" | grep -c "✗ 'ex_a' is absent$")" = "1" ] && echo "$output" | tail -3 | grep -q "✓ 'ex_b' is present"; then
+        log_test_result "Deleted Slot Skipped After Merge" "PASS"
+    else
+        log_test_result "Deleted Slot Skipped After Merge" "FAIL"
+    fi
+}
+
+test_hash_table_metrics() {
+    echo -e "${BLUE}=== Testing Hash Table Metrics ===${NC}"
+    
+    # A single bucket with a high load factor threshold puts every key in one chain
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e "s/^size = .*/size = 1/" -e "s/^max_load_factor = .*/max_load_factor = 100.0/" config.toml > "$exec_dir/config.toml"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert hm_1 a" --exec "insert hm_2 b" --exec "insert hm_3 c" \
+        --exec "get hm_3" --exec "htstats" --exec "htstats reset" --exec "htstats" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Inserts: 3, lookups: 1" "Inserts And Lookups Counted"
+    check_exec_output_contains "$output" "Total probes: 9 (2.25 per operation)" "Chain Probes Counted"
+    check_exec_output_contains "$output" "Collisions: 5" "Collisions Counted"
+    check_exec_output_contains "$output" "Longest probe sequence: 3" "Longest Probe Recorded"
+    check_exec_output_contains "$output" "Average chain length: 3.00" "Average Chain Length"
+    if echo "$output" | tail -6 | grep -q "Inserts: 0, lookups: 0"; then
+        log_test_result "Metrics Reset" "PASS"
+    else
+        log_test_result "Metrics Reset" "FAIL"
+    fi
+}
+
+test_double_hashing_step() {
+    echo -e "${BLUE}=== Testing Double Hashing Step Size ===${NC}"
+    
+    # 'b', 'i', 'p' and 'w' all hash to bucket 0 of 7; a step of 7 would revisit it forever and force a grow,
+    # while steps derived from 5, the largest prime below 7, reach free buckets
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e "s/^size = .*/size = 7/" -e "s/^collision_method = .*/collision_method = \"double_hashing\"/" config.toml > "$exec_dir/config.toml"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert b 1" --exec "insert i 2" --exec "insert p 3" --exec "insert w 4" \
+        --exec "htstats" --exec "get i" < /dev/null 2>&1)
+    sed -i "s/^size = .*/size = 0/" "$exec_dir/config.toml"
+    local zero_size=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "stats" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "(7 buckets, 4 entries)" "Colliding Keys Fit Without Growing"
+    check_exec_output_contains "$output" "Longest probe sequence: 2" "Step Reaches Free Bucket"
+    check_exec_output_contains "$output" "✓ i: 2$" "Lookup Follows Double Hashing Step"
+    check_exec_output_contains "$zero_size" "size in config.toml: must be at least 1" "Zero Table Size Rejected"
+}
+
+test_compact_command() {
+    echo -e "${BLUE}=== Testing Compaction Including The Active File ===${NC}"
+    
+    # Everything lives in the active file, which merge leaves alone and compact reclaims
+    local output=$(run_exec --exec "insert cp_a 1" --exec "insert cp_a 2" --exec "insert cp_b 3" --exec "delete cp_b" \
+        --exec "merge" --exec "compact" --exec "get cp_a" --exec "get cp_b")
+    check_exec_output_contains "$output" "No inactive files to merge" "Merge Skips The Active File"
+    check_exec_output_contains "$output" "Processing inactive file: data_0000000000.dat" "Compact Merges The Active File"
+    check_exec_output_contains "$output" "Removed 1 deleted keys from hash table" "Compact Drops Deleted Key"
+    check_exec_output_contains "$output" "Compaction completed; new writes go to data_0000000001.dat" "Writes Continue In New Active File"
+    check_exec_output_contains "$output" "✓ cp_a: 2$" "Latest Value Survives Compaction"
+    check_exec_output_contains "$output" "Key 'cp_b' not found" "Deleted Key Gone After Compaction"
+}
+
+test_transaction_command() {
+    echo -e "${BLUE}=== Testing Transactions ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e "s/^max_value_size = .*/max_value_size = 400/" config.toml > "$exec_dir/config.toml"
+    local filler=$(printf 'f%.0s' {1..370})
+    # The second transaction doesn't fit after the filler, so all of it moves to the next file
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert tx_c old" --exec "transaction put:tx_a=1 put:tx_b=2 del:tx_c" \
+        --exec "insert tx_fill $filler" --exec "transaction put:tx_d=4 put:tx_e=5 put:tx_f=6 put:tx_g=7 put:tx_h=8" \
+        --exec "transaction put:tx_a=9 put:tx_big=$filler$filler" --exec "transaction put:tx_a" < /dev/null 2>&1)
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get tx_a" --exec "get tx_c" --exec "get tx_h" --exec "get tx_big" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ Deleted tx_c (tombstone: file data_0000000000.dat" "Transaction Writes Tombstones"
+    check_exec_output_contains "$output" "Committed transaction of 3 operations" "Transaction Committed"
+    if [ "$(echo "$output" | grep -c "✓ Inserted tx_[d-h]: [4-8] (file: data_0000000001.dat")" = "5" ]; then
+        log_test_result "Transaction Kept In One File" "PASS"
+    else
+        log_test_result "Transaction Kept In One File" "FAIL"
+    fi
+    check_exec_output_contains "$output" "✗ Transaction failed: Value is 740 bytes, over the 400 byte limit" "Oversized Transaction Rejected"
+    check_exec_output_contains "$output" "Expected put:<key>=<value> or del:<key>, got 'put:tx_a'" "Malformed Operation Rejected"
+    check_exec_output_contains "$restarted" "✓ tx_a: 1$" "Rejected Transaction Left No Writes"
+    check_exec_output_contains "$restarted" "Key 'tx_c' \(has been deleted\|not found\)" "Transaction Delete Survives Restart"
+    check_exec_output_contains "$restarted" "✓ tx_h: 8$" "Transaction Put Survives Restart"
+    check_exec_output_contains "$restarted" "Key 'tx_big' not found" "Rejected Put Absent After Restart"
+}
+
+test_merge_timestamp_order() {
+    echo -e "${BLUE}=== Testing Merge Keeps The Newest Timestamp ===${NC}"
+    
+    # Each filler record rotates to a new file, leaving the two versions of mt_key in data_000 and data_002;
+    # swapping those files makes file order disagree with write order
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local filler=$(printf 'f%.0s' {1..500})
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert mt_key old" --exec "insert mt_fill_1 $filler" \
+        --exec "insert mt_key new" --exec "insert mt_fill_2 $filler" < /dev/null > /dev/null 2>&1)
+    mv "$exec_dir/storage/data_0000000000.dat" "$exec_dir/storage/swap.tmp"
+    mv "$exec_dir/storage/data_0000000002.dat" "$exec_dir/storage/data_0000000000.dat"
+    mv "$exec_dir/storage/swap.tmp" "$exec_dir/storage/data_0000000002.dat"
+    # The saved index still matches every file's length, so drop it to make the restart rebuild from the records
+    rm -f "$exec_dir/storage/.index"
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get mt_key" < /dev/null 2>&1)
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "merge" --exec "get mt_key" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$restarted" "✓ mt_key: new$" "Newest Timestamp Wins On Rebuild"
+    check_exec_output_contains "$output" "Ignored 1 records older than a version read before them" "Older Record Read Later Ignored"
+    check_exec_output_contains "$output" "✓ mt_key: new$" "Newest Timestamp Survives Merge"
+}
+
+test_count_command() {
+    echo -e "${BLUE}=== Testing Count Command ===${NC}"
+    
+    # A deleted key stays indexed at its tombstone until compaction drops it; it counts as a tombstone, not a live key
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert cn_a 1" --exec "insert cn_b 2" --exec "insert cn_c 3" \
+        --exec "delete cn_a" --exec "count tombstones" --exec "stats" --exec "compact" --exec "count tombstones" --exec "count bogus" < /dev/null 2>&1)
+    # An interactive session saves the index on exit, tombstone entries included, for the next start to load
+    (cd "$exec_dir" && (printf 'delete cn_b\n'; sleep 0.5) | timeout 10 "$app_bin" > /dev/null 2>&1)
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "count tombstones" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ 2 live keys in the index$" "Count Leaves Out Deleted Keys"
+    check_exec_output_contains "$output" "Tombstones pending compaction: 1$" "Count Reports Pending Tombstones"
+    check_exec_output_contains "$output" "Index: 2 live keys and 1 tombstones in" "Stats Shows Live And Tombstone Counts"
+    check_exec_output_contains "$output" "Tombstones pending compaction: 0$" "Compaction Clears Tombstones"
+    check_exec_output_contains "$output" "Usage: count \[tombstones\]" "Count Rejects Unknown Option"
+    check_exec_output_contains "$restarted" "✓ 1 live keys in the index$" "Live Count Survives Restart"
+    check_exec_output_contains "$restarted" "Tombstones pending compaction: 1$" "Tombstone Count Survives Restart"
+}
+
+test_quoted_arguments() {
+    echo -e "${BLUE}=== Testing Quoted Command Arguments ===${NC}"
+    
+    local output=$(run_exec --exec 'insert "full name" "John Doe"' --exec 'get "full name"' --exec 'insert plain two words' \
+        --exec 'insert quip "she said \"hi\" \\o/"' --exec 'get quip' --exec 'insert broken "no end' --exec 'delete "full name"' \
+        --exec 'get "full name"')
+    check_exec_output_contains "$output" "✓ Inserted full name: John Doe (file:" "Quoted Key And Value"
+    check_exec_output_contains "$output" "✓ full name: John Doe$" "Get With Quoted Key"
+    check_exec_output_contains "$output" "✓ Inserted plain: two words (file:" "Unquoted Words Still Joined"
+    check_exec_output_contains "$output" '✓ quip: she said "hi" \\o/$' "Escaped Quotes In Value"
+    check_exec_output_contains "$output" "Invalid command: unterminated quote" "Unterminated Quote Rejected"
+    check_exec_output_contains "$output" "Key 'full name' has been deleted" "Delete With Quoted Key"
+    
+    # Only a quote starting a word opens a quoted word; one inside a word is kept as typed
+    output=$(run_exec --exec 'insert size 5"' --exec 'get size' --exec 'insert quote say"hi" to"' --exec 'get quote')
+    check_exec_output_contains "$output" '✓ size: 5"$' "Trailing Quote In Value Kept"
+    check_exec_output_contains "$output" '✓ quote: say"hi" to"$' "Quotes Inside Words Kept"
+}
+
+test_conditional_insert() {
+    echo -e "${BLUE}=== Testing Conditional Inserts ===${NC}"
+    
+    local output=$(run_exec --exec "setxx nx_key first" --exec "setnx nx_key first" --exec "setnx nx_key second" --exec "get nx_key" \
+        --exec "setxx nx_key third" --exec "get nx_key" --exec "delete nx_key" --exec "setxx nx_key fourth" --exec "setnx nx_key fifth" \
+        --exec "get nx_key")
+    check_exec_output_contains "$output" "✗ 'nx_key' does not exist, nothing replaced" "Setxx Skips Missing Key"
+    check_exec_output_contains "$output" "✓ Inserted nx_key: first$" "Setnx Inserts Missing Key"
+    check_exec_output_contains "$output" "✗ 'nx_key' already exists, not overwritten" "Setnx Keeps Existing Value"
+    check_exec_output_contains "$output" "✓ nx_key: first$" "Existing Value Unchanged"
+    check_exec_output_contains "$output" "✓ nx_key: third$" "Setxx Replaces Existing Key"
+    if [ "$(echo "$output" | grep -c "does not exist, nothing replaced")" = "2" ] && echo "$output" | tail -1 | grep -q "✓ nx_key: fifth$"; then
+        log_test_result "Deleted Key Counts As Absent" "PASS"
+    else
+        log_test_result "Deleted Key Counts As Absent" "FAIL"
+    fi
+}
+
+test_file_naming() {
+    echo -e "${BLUE}=== Testing Configurable File Names ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e 's/^file_prefix = .*/file_prefix = "kv_"/' -e 's/^merge_file_prefix = .*/merge_file_prefix = "kvm_"/' \
+        -e 's/^file_extension = .*/file_extension = "log"/' config.toml > "$exec_dir/config.toml"
+    local value=$(printf 'n%.0s' {1..40})
+    local inserts=()
+    for i in {1..12}; do
+        inserts+=(--exec "insert fn_key_$i $value")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${inserts[@]}" --exec "merge" --exec "stats" < /dev/null 2>&1)
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get fn_key_1" --exec "get fn_key_12" < /dev/null 2>&1)
+    local files=$(ls "$exec_dir/storage" | tr '\n' ' ')
+    sed -i 's/^merge_file_prefix = .*/merge_file_prefix = "kv_merge_"/' "$exec_dir/config.toml"
+    local clashing=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "stats" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Data files: kv_N.log, merge files: kvm_N.log" "Custom Naming Reported"
+    check_exec_output_contains "$output" "Inserted fn_key_1: .*file: kv_0000000000.log" "Data File Uses Prefix And Extension"
+    check_exec_output_contains "$output" "kvm_0000000000.log: [0-9]* bytes$" "Merge File Uses Prefix And Extension"
+    check_exec_output_contains "$output" "kv_000000000[0-9].log: [0-9]* bytes (ACTIVE)$" "Active File Listed In Stats"
+    check_exec_output_contains "$restarted" "✓ fn_key_1: $value$" "Merged Key Readable After Restart"
+    check_exec_output_contains "$restarted" "✓ fn_key_12: $value$" "Active Key Readable After Restart"
+    if echo "$files" | grep -q "kvm_0000000000.hint" && ! echo "$files" | grep -q "\.dat"; then
+        log_test_result "Only Configured Names Written" "PASS"
+    else
+        log_test_result "Only Configured Names Written" "FAIL" "Files: $files"
+    fi
+    check_exec_output_contains "$clashing" "must not start with one another" "Overlapping Prefixes Rejected"
+}
+
+test_missing_data_file() {
+    echo -e "${BLUE}=== Testing Reads From A Removed Data File ===${NC}"
+    
+    # The file is removed behind the running store's back, leaving its index entry pointing nowhere
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local output=$(cd "$exec_dir" && (echo "insert fm_key v"; sleep 0.5; rm storage/data_0000000000.dat; echo "get fm_key"; echo "exit") \
+        | timeout 10 "$app_bin" 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✗ Can't read fm_key: its index entry points into data_0000000000.dat, which no longer exists" "Missing File Reported Distinctly"
+}
+
+test_backup_command() {
+    echo -e "${BLUE}=== Testing Snapshot Backups ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local value=$(printf 'b%.0s' {1..40})
+    local inserts=()
+    for i in {1..12}; do
+        inserts+=(--exec "insert bu_key_$i $value")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${inserts[@]}" --exec "merge" --exec "delete bu_key_2" --exec "backup snap" \
+        --exec "insert bu_after later" --exec "backup snap" < /dev/null 2>&1)
+    local copied=$(ls -A "$exec_dir/snap" | tr '\n' ' ')
+    sed -i 's/^directory = .*/directory = "snap"/' "$exec_dir/config.toml"
+    local restored=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get bu_key_1" --exec "get bu_key_2" --exec "get bu_key_12" \
+        --exec "get bu_after" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ Snapshot of [0-9]* files written to 'snap/'" "Snapshot Written"
+    check_exec_output_contains "$output" "^  merge_0000000000.hint$" "Hint Files Copied"
+    check_exec_output_contains "$output" "^  .meta$" "Meta Copied"
+    check_exec_output_contains "$output" "✗ Failed to back up to snap: .*already contains data files" "Backup Into Used Directory Refused"
+    if echo "$copied" | grep -q "\.tmp"; then
+        log_test_result "No Temporary Files Left" "FAIL" "Files: $copied"
+    else
+        log_test_result "No Temporary Files Left" "PASS"
+    fi
+    check_exec_output_contains "$restored" "✓ bu_key_1: $value$" "Merged Key Restored"
+    check_exec_output_contains "$restored" "✓ bu_key_12: $value$" "Active File Key Restored"
+    check_exec_output_contains "$restored" "Key 'bu_key_2' \(has been deleted\|not found\)" "Delete Before Snapshot Restored"
+    check_exec_output_contains "$restored" "Key 'bu_after' not found" "Write After Snapshot Excluded"
+}
+
+test_robin_hood() {
+    echo -e "${BLUE}=== Testing Robin Hood Hashing ===${NC}"
+    
+    # 30 keys in 37 buckets, then every third deleted; compaction drops the deleted keys from the index, and
+    # the backward shifts that fill their slots must leave every survivor reachable
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed -e "s/^size = .*/size = 37/" -e "s/^collision_method = .*/collision_method = \"robin_hood\"/" -e "s/^max_load_factor = .*/max_load_factor = 0.9/" \
+        config.toml > "$exec_dir/config.toml"
+    local args=()
+    for i in {1..30}; do
+        args+=(--exec "insert rh_$i v$i")
+    done
+    for i in {3..30..3}; do
+        args+=(--exec "delete rh_$i")
+    done
+    args+=(--exec "compact" --exec "count tombstones")
+    for i in {1..30}; do
+        args+=(--exec "get rh_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "simulate-crash" --exec "get rh_29" --exec "duplicates" \
+        --exec "bench probing 1009" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    local found=$(echo "$output" | grep -c "✓ rh_[0-9]*: v[0-9]*$")
+    local deleted=$(echo "$output" | grep -c "Key 'rh_[0-9]*' \(has been deleted\|not found\)")
+    if [ "$found" = "21" ] && [ "$deleted" = "10" ]; then
+        log_test_result "Survivors Reachable After Deletes" "PASS"
+    else
+        log_test_result "Survivors Reachable After Deletes" "FAIL" "$found found, $deleted deleted"
+    fi
+    check_exec_output_contains "$output" "Hash table: 37 buckets, RobinHood" "Robin Hood Configured"
+    check_exec_output_contains "$output" "✓ 20 live keys in the index" "Deleted Keys Removed From Index"
+    check_exec_output_contains "$output" "No duplicate keys in the index" "No Duplicates After Backward Shifts"
+    check_exec_output_contains "$output" "Probe lengths with 908 keys in 1009 buckets (load factor 0.90)" "Probing Benchmark Runs"
+    local linear=$(echo "$output" | grep -o "LinearProbing: longest probe [0-9]*" | grep -o "[0-9]*$")
+    local robin=$(echo "$output" | grep -o "RobinHood: longest probe [0-9]*" | grep -o "[0-9]*$")
+    if [ -n "$linear" ] && [ -n "$robin" ] && [ "$robin" -lt "$linear" ]; then
+        log_test_result "Robin Hood Shortens Longest Probe" "PASS"
+    else
+        log_test_result "Robin Hood Shortens Longest Probe" "FAIL" "linear $linear, robin hood $robin"
+    fi
+}
+
+test_getmeta_command() {
+    echo -e "${BLUE}=== Testing Value Metadata Lookups ===${NC}"
+    
+    local today=$(date -u +%Y-%m-%d)
+    local output=$(run_exec --exec "insert gm_key hello" --exec "getmeta gm_key" --exec "delete gm_key" --exec "getmeta gm_key" \
+        --exec "getmeta gm_missing")
+    check_exec_output_contains "$output" "✓ gm_key: hello$" "Getmeta Returns Value"
+    check_exec_output_contains "$output" "File: data_0000000000.dat, value offset 23$" "Getmeta Reports Location"
+    check_exec_output_contains "$output" "Stored size: 5 bytes, CRC 0x[0-9a-f]\{4\}$" "Getmeta Reports Size And CRC"
+    check_exec_output_contains "$output" "Written: $today [0-9:.]* UTC (.* ago)$" "Getmeta Reports Write Time"
+    check_exec_output_contains "$output" "Key 'gm_key' has been deleted (tombstone in data_0000000000.dat" "Getmeta Reports Tombstone"
+    check_exec_output_contains "$output" "Key 'gm_missing' not found" "Getmeta Reports Missing Key"
+}
+
+test_delete_prefix() {
+    echo -e "${BLUE}=== Testing Prefix Deletes ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local args=()
+    for i in {1..5}; do
+        args+=(--exec "insert session:$i s$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "insert sessions v" --exec "insert user:1 u1" \
+        --exec "delete session:2" --exec "delprefix session:" --exec "get session:4" --exec "get sessions" --exec "delprefix session:" \
+        --exec 'delprefix ""' < /dev/null 2>&1)
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get session:1" --exec "get user:1" --exec "count" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ Deleted 4 keys starting with 'session:'" "Live Keys With Prefix Deleted"
+    check_exec_output_contains "$output" "Key 'session:4' has been deleted" "Prefixed Key Reads As Deleted"
+    check_exec_output_contains "$output" "✓ sessions: v$" "Key Outside Prefix Kept"
+    check_exec_output_contains "$output" "✓ Deleted 0 keys starting with 'session:'" "Repeated Prefix Delete Finds Nothing"
+    check_exec_output_contains "$output" "✗ Prefix must not be empty" "Empty Prefix Rejected"
+    check_exec_output_contains "$restarted" "Key 'session:1' not found" "Prefix Delete Survives Restart"
+    check_exec_output_contains "$restarted" "✓ user:1: u1$" "Other Keys Survive Restart"
+    check_exec_output_contains "$restarted" "✓ 2 live keys in the index" "Only Survivors Indexed After Restart"
+}
+
+test_read_handles() {
+    echo -e "${BLUE}=== Testing Shared Read Handles ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local args=()
+    for i in {1..30}; do
+        args+=(--exec "insert hk_$i value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "bench read 1000" --exec "stats" \
+        --exec "merge" --exec "get hk_1" --exec "insert hk_31 fresh" --exec "get hk_31" --exec "insert hk_31 fresher" \
+        --exec "get hk_31" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Read handles: 2 open; 2 opens, 998 reads reused an open handle" "Each File Opened Once For Reads"
+    check_exec_output_contains "$output" "✓ hk_1: value_1$" "Read After Merge Uses A New Handle"
+    check_exec_output_contains "$output" "✓ hk_31: fresh$" "Cached Handle Sees Appends"
+    check_exec_output_contains "$output" "✓ hk_31: fresher$" "Cached Handle Sees Later Appends"
+}
+
+test_tcp_server() {
+    echo -e "${BLUE}=== Testing TCP Server ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    sed -i 's/^# listen_address = .*/listen_address = "127.0.0.1:0"/' "$exec_dir/config.toml"
+    (cd "$exec_dir" && exec "$app_bin" > server.log 2>&1 < /dev/null) &
+    local server_pid=$!
+    local port=""
+    for _ in {1..50}; do
+        port=$(grep -o 'Listening on 127.0.0.1:[0-9]*' "$exec_dir/server.log" 2>/dev/null | cut -d: -f2)
+        [ -n "$port" ] && break
+        sleep 0.1
+    done
+    
+    local first="" second="" overlong=""
+    if [ -n "$port" ]; then
+        # Two clients connected at once; the second sees the first's write and deletes a key it wrote
+        # (bash picks the descriptors; fd 3 is the interactive app's pipe)
+        local first_fd second_fd
+        exec {first_fd}<>"/dev/tcp/127.0.0.1/$port"
+        exec {second_fd}<>"/dev/tcp/127.0.0.1/$port"
+        printf 'insert shared one\ninsert gone soon\n' >&$first_fd
+        sleep 0.3
+        printf 'get shared\ndelete gone\nexit\n' >&$second_fd
+        second=$(timeout 5 cat <&$second_fd)
+        printf 'get gone\nstats\nnot-a-command\nmerge\nexit\n' >&$first_fd
+        first=$(timeout 5 cat <&$first_fd)
+        exec {first_fd}<&- {second_fd}<&-
+        
+        # One byte past the 1 MiB line limit, with no newline: the server stops buffering and hangs up
+        local long_fd
+        exec {long_fd}<>"/dev/tcp/127.0.0.1/$port"
+        head -c 1048577 /dev/zero | tr '\0' 'x' >&$long_fd
+        overlong=$(timeout 5 cat <&$long_fd)
+        exec {long_fd}<&-
+    fi
+    kill "$server_pid" 2>/dev/null
+    wait "$server_pid" 2>/dev/null
+    local server_log=$(cat "$exec_dir/server.log")
+    local restarted=$(cd "$exec_dir" && sed -i 's/^listen_address = .*//' config.toml && timeout 10 "$app_bin" --exec "get shared" --exec "get gone" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    if [ -n "$port" ]; then
+        log_test_result "Server Listens On Configured Address" "PASS"
+    else
+        log_test_result "Server Listens On Configured Address" "FAIL" "no 'Listening on' line in the server output"
+    fi
+    check_exec_output_contains "$first" "✓ Inserted shared: one" "Client Gets Insert Response"
+    check_exec_output_contains "$second" "✓ shared: one$" "Second Client Reads First Client's Write"
+    check_exec_output_contains "$second" "✓ Deleted gone" "Second Client Deletes"
+    check_exec_output_contains "$second" "Goodbye!" "Exit Closes Connection"
+    check_exec_output_contains "$first" "Key 'gone' has been deleted" "First Client Sees Second Client's Delete"
+    check_exec_output_contains "$first" "=== Storage Statistics ===" "Stats Served Over TCP"
+    check_exec_output_contains "$first" "Unknown command: not-a-command" "Unknown Command Reported To Client"
+    check_exec_output_contains "$first" "  No inactive files to merge" "Merge Progress Sent To Client"
+    check_exec_output_contains "$overlong" "✗ Line longer than 1048576 bytes; closing the connection" "Overlong Line Disconnects Client"
+    check_exec_output_contains "$server_log" "Dropping client 127.0.0.1:[0-9]*: sent a line longer than 1048576 bytes" "Server Logs Overlong Line"
+    check_exec_output_contains "$server_log" "Client 127.0.0.1:[0-9]* disconnected" "Server Logs Disconnects"
+    check_exec_output_contains "$restarted" "✓ shared: one$" "Writes Over TCP Persist"
+    check_exec_output_contains "$restarted" "Key 'gone' not found" "Deletes Over TCP Persist"
+}
+
+test_tombstones_command() {
+    echo -e "${BLUE}=== Testing Tombstone Listing ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "tombstones" --exec "insert tb_a 1" --exec "insert tb_b 2" \
+        --exec 'insert tb_lookalike \DELETED\' --exec "delete tb_a" --exec "delete tb_b" --exec "insert tb_b 3" --exec "tombstones" < /dev/null 2>&1)
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "tombstones" --exec "compact" --exec "tombstones" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "No tombstones on disk" "Empty Store Has No Tombstones"
+    check_exec_output_contains "$output" "✓ 2 deleted keys with tombstones on disk" "Deleted Keys Counted"
+    check_exec_output_contains "$output" "  tb_a deleted [0-9-]* [0-9:.]* UTC" "Deleted Key Listed With Time"
+    check_exec_output_contains "$output" "  tb_b deleted .*written again since" "Rewritten Key Marked"
+    if echo "$output" | grep -q "tb_lookalike deleted"; then
+        log_test_result "Tombstone-Like Value Not Listed" "FAIL"
+    else
+        log_test_result "Tombstone-Like Value Not Listed" "PASS"
+    fi
+    check_exec_output_contains "$restarted" "  tb_a deleted" "Tombstones Listed After Restart"
+    check_exec_output_contains "$(echo "$restarted" | sed -n '/> compact/,$p')" "No tombstones on disk" "Compaction Discards Tombstones"
+}
+
+# Starts the interactive app with the given poll timeout and 1-second auto-merge, writes a few keys, and
+# prints "merged" or "waiting" after each of the given waits, the first counted from the last write
+auto_merge_timeline() {
+    local poll_timeout_ms="$1"
+    shift
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    sed -i "s/^merge_interval_seconds = .*/merge_interval_seconds = 1/; s/^poll_timeout_ms = .*/poll_timeout_ms = $poll_timeout_ms/" "$exec_dir/config.toml"
+    mkfifo "$exec_dir/input"
+    (cd "$exec_dir" && exec "$app_bin" < input > output.log 2>&1) &
+    local app_pid=$!
+    local input_fd
+    exec {input_fd}>"$exec_dir/input"
+    printf 'insert pt_1 one\ninsert pt_2 two\n' >&$input_fd
+    for wait_seconds in "$@"; do
+        sleep "$wait_seconds"
+        if grep -q "Auto-merge triggered" "$exec_dir/output.log"; then
+            echo "merged"
+        else
+            echo "waiting"
+        fi
+    done
+    exec {input_fd}>&-
+    wait "$app_pid" 2>/dev/null
+    rm -rf "$exec_dir"
+}
+
+test_poll_timeout() {
+    echo -e "${BLUE}=== Testing Configurable Poll Timeout ===${NC}"
+    
+    # Auto-merge is due a second after the last write, but only noticed when the poll times out
+    local fast=$(auto_merge_timeline 100 2.5)
+    local slow=$(auto_merge_timeline 4000 2.5 3)
+    
+    if [ "$fast" = "merged" ]; then
+        log_test_result "Short Poll Timeout Notices Auto-Merge Promptly" "PASS"
+    else
+        log_test_result "Short Poll Timeout Notices Auto-Merge Promptly" "FAIL" "no auto-merge 2.5s after the last write"
+    fi
+    if [ "$slow" = $'waiting\nmerged' ]; then
+        log_test_result "Long Poll Timeout Delays Auto-Merge Check" "PASS"
+    else
+        log_test_result "Long Poll Timeout Delays Auto-Merge Check" "FAIL" "expected no merge at 2.5s and one by 5.5s, got: $slow"
+    fi
+}
+
+test_mget_command() {
+    echo -e "${BLUE}=== Testing Multi-Get ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local args=()
+    for i in {1..30}; do
+        args+=(--exec "insert mg_$i value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "delete mg_2" \
+        --exec "mget mg_30 mg_nope mg_2 mg_1 mg_30" --exec "mget" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    local results=$(echo "$output" | sed -n '/> mget mg_30/,/> mget$/p' | grep "^[✓✗]")
+    if [ "$results" = $'✓ mg_30: value_30\n✗ Key \'mg_nope\' not found\n✗ Key \'mg_2\' has been deleted\n✓ mg_1: value_1\n✓ mg_30: value_30' ]; then
+        log_test_result "Multi-Get Results In Key Order" "PASS"
+    else
+        log_test_result "Multi-Get Results In Key Order" "FAIL" "got: $results"
+    fi
+    check_exec_output_contains "$output" "Usage: mget <key>..." "Multi-Get Requires Keys"
+}
+
+test_saved_index() {
+    echo -e "${BLUE}=== Testing Saved Index ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    # Interactive sessions save the index when their input closes; the pause lets the loop see the close
+    local first=$(cd "$exec_dir" && (printf 'insert si_a 1\ninsert si_b 2\ninsert si_gone x\ndelete si_gone\n'; sleep 0.5) | timeout 10 "$app_bin" 2>&1)
+    local loaded=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get si_a" --exec "get si_gone" < /dev/null 2>&1)
+    local stale=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert si_c 3" < /dev/null 2>&1 && timeout 10 "$app_bin" --exec "get si_c" < /dev/null 2>&1)
+    (cd "$exec_dir" && (printf 'get si_a\n'; sleep 0.5) | timeout 10 "$app_bin" > /dev/null 2>&1)
+    printf 'X' | dd of="$exec_dir/storage/.index" bs=1 seek=12 conv=notrunc 2>/dev/null
+    local corrupt=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get si_b" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$first" "✓ Saved the index (3 keys) for the next start" "Index Saved On Clean Exit"
+    check_exec_output_contains "$loaded" "✓ Loaded 3 keys from the saved index" "Saved Index Loaded On Start"
+    check_exec_output_contains "$loaded" "✓ si_a: 1$" "Key Readable Through Loaded Index"
+    check_exec_output_contains "$loaded" "Key 'si_gone' has been deleted" "Delete Kept In Loaded Index"
+    check_exec_output_contains "$stale" "Saved index not used (stale index file" "Stale Index Rejected"
+    check_exec_output_contains "$stale" "✓ si_c: 3$" "Write After Save Found By Replay"
+    check_exec_output_contains "$corrupt" "Saved index not used (invalid index file: checksum mismatch)" "Corrupt Index Rejected"
+    check_exec_output_contains "$corrupt" "✓ si_b: 2$" "Replay Used After Corrupt Index"
+}
+
+test_update_in_place() {
+    echo -e "${BLUE}=== Testing In-Place Updates ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert ip_a abc" --exec "update-in-place ip_a xyz" --exec "get ip_a" \
+        --exec "update-in-place ip_a longer" --exec "update-in-place ip_nope x" --exec "stats" < /dev/null 2>&1)
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "update-in-place ip_a 123456" --exec "compact" < /dev/null 2>&1 \
+        && timeout 10 "$app_bin" --exec "get ip_a" < /dev/null 2>&1)
+    sed -i 's/^compression = .*/compression = "lz4"/' "$exec_dir/config.toml"
+    local compressed=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert ip_z $(printf 'z%.0s' {1..200})" \
+        --exec "update-in-place ip_z $(printf 'y%.0s' {1..200})" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ Overwrote ip_a in place: xyz (file: data_0000000000.dat, value_offset: 21)" "Equal Length Value Overwritten"
+    check_exec_output_contains "$output" "✓ ip_a: xyz$" "Overwritten Value Read Back"
+    check_exec_output_contains "$output" "New value is 6 bytes but the stored one is 3; appending" "Length Mismatch Falls Back To Append"
+    check_exec_output_contains "$output" "Key 'ip_nope' not found" "Missing Key Not Updated"
+    check_exec_output_contains "$output" "data_0000000000.dat: 59 bytes" "Overwrite Appends Nothing"
+    check_exec_output_contains "$restarted" "✓ Overwrote ip_a in place: 123456" "Update In Place After Restart"
+    check_exec_output_contains "$restarted" "✓ ip_a: 123456$" "Overwritten Value Survives Compaction And Restart"
+    check_exec_output_contains "$compressed" "stored Lz4-compressed.*appending" "Compressed Value Falls Back To Append"
+}
+
+test_returning_old_values() {
+    echo -e "${BLUE}=== Testing Returned Previous Values ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "getset ro_key 1" --exec "getset ro_key 2" --exec "delete ro_key why" \
+        --exec "delete ro_key" --exec "getset ro_key 3" --exec "get ro_key" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    # The old value's file is renamed behind the store's back, as if a merge had moved its record elsewhere
+    local moved_dir=$(mktemp -d)
+    cp config.toml "$moved_dir/"
+    local filler=$(printf 'f%.0s' {1..500})
+    local moved=$(cd "$moved_dir" && (echo "insert ro_moved old"; echo "insert ro_filler $filler"; sleep 0.5
+        mv storage/data_0000000000.dat storage/merge_0000000000.dat; echo "getset ro_moved new"; echo "exit") | timeout 10 "$app_bin" 2>&1)
+    rm -rf "$moved_dir"
+    
+    check_exec_output_contains "$output" "✓ Set ro_key: 1 (had no value)" "Set On New Key Has No Old Value"
+    check_exec_output_contains "$output" "✓ Set ro_key: 2 (was: 1)" "Set Returns Old Value"
+    check_exec_output_contains "$output" "  Removed value: 2" "Delete Shows Removed Value"
+    check_exec_output_contains "$output" "  Key had no value to remove" "Repeated Delete Removes Nothing"
+    check_exec_output_contains "$output" "✓ Set ro_key: 3 (had no value)" "Set After Delete Has No Old Value"
+    check_exec_output_contains "$output" "✓ ro_key: 3$" "Set Value Readable"
+    check_exec_output_contains "$moved" "✓ Set ro_moved: new (was: old)" "Old Value Found After Its File Moved"
+}
+
+test_record_checksums() {
+    echo -e "${BLUE}=== Testing Record Checksums ===${NC}"
+    
+    # Records of 30, 29, 30 and 30 bytes: cs_b's key starts at 47 and cs_c's timestamp at 68
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && (printf 'insert cs_a alpha\ninsert cs_b beta\ninsert cs_c gamma\ninsert cs_d delta\n'; sleep 0.5) | timeout 10 "$app_bin" > /dev/null 2>&1)
+    printf 'x' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=50 conv=notrunc 2>/dev/null
+    printf '\x7f' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=75 conv=notrunc 2>/dev/null
+    # The saved index still points at both damaged records, so reads reach them
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get cs_a" --exec "get cs_b" --exec "get cs_c" --exec "records" \
+        --exec "compact" --exec "get cs_a" --exec "get cs_d" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ cs_a: alpha$" "Intact Record Readable"
+    check_exec_output_contains "$output" "Index entry for 'cs_b' does not point at a record for that key" "Damaged Key Detected On Read"
+    check_exec_output_contains "$output" "Record at offset 59 in 'data_0000000000.dat' fails its checksum" "Damaged Header Detected On Read"
+    check_exec_output_contains "$output" "Record at offset 30 in 'data_0000000000.dat' fails its checksum" "Damaged Key Detected By Record Scan"
+    check_exec_output_contains "$output" "✗ Data corruption: Record at offset 30 in 'data_0000000000.dat' fails its checksum; skipped 59 bytes to the next intact record" "Merge Skips Damaged Records"
+    check_exec_output_contains "$output" "✓ cs_d: delta$" "Record After Damage Survives Merge"
+    if [ "$(echo "$output" | grep -c "✓ cs_a: alpha$")" = "2" ]; then
+        log_test_result "Record Before Damage Survives Merge" "PASS"
+    else
+        log_test_result "Record Before Damage Survives Merge" "FAIL"
+    fi
+    
+    # A damaged value in the middle of a file: rebuilding the index without a saved one skips it and keeps what follows,
+    # including writes made after the damage. cs_b's value starts at 51
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert cs_a alpha" --exec "insert cs_b beta" --exec "insert cs_c gamma" --exec "insert cs_d delta" < /dev/null > /dev/null 2>&1)
+    printf 'x' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=52 conv=notrunc 2>/dev/null
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get cs_c" --exec "get cs_d" --exec "insert cs_e epsilon" --exec "simulate-crash" < /dev/null 2>&1)
+    local restarted=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get cs_e" --exec "get cs_b" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "✗ Data corruption: Record at offset 30 in 'data_0000000000.dat' fails its checksum; skipped 29 bytes to the next intact record" "Rebuild Reports Damaged Record"
+    # Once when opening the store, once more from the simulate-crash command
+    if [ "$(echo "$output" | grep -c "Record at offset 30 in 'data_0000000000.dat' fails its checksum; skipped 29 bytes")" = "2" ]; then
+        log_test_result "Command Rebuild Reports Damaged Record" "PASS"
+    else
+        log_test_result "Command Rebuild Reports Damaged Record" "FAIL"
+    fi
+    check_exec_output_contains "$output" "✓ cs_c: gamma$" "Rebuild Keeps Records After Damage"
+    check_exec_output_contains "$output" "✓ cs_d: delta$" "Rebuild Keeps Last Record After Damage"
+    check_exec_output_contains "$restarted" "✓ cs_e: epsilon$" "Write After Damage Survives Restart"
+    check_exec_output_contains "$restarted" "✗ Key 'cs_b' not found" "Damaged Record Not Indexed"
+    
+    # Deleting a key whose value fails its CRC still writes the tombstone; the saved index points at the damage,
+    # and cs_c after it keeps the damaged record from being taken for an uncommitted tail
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && (printf 'insert cs_a alpha\ninsert cs_b beta\ninsert cs_c gamma\n'; sleep 0.5) | timeout 10 "$app_bin" > /dev/null 2>&1)
+    printf 'x' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=52 conv=notrunc 2>/dev/null
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "delete cs_b" --exec "get cs_b" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "✓ Deleted cs_b" "Damaged Key Can Be Deleted"
+    check_exec_output_contains "$output" "  Removed value could not be read: Data corruption: CRC mismatch for key 'cs_b'" "Delete Reports Unreadable Old Value"
+    check_exec_output_contains "$output" "Key 'cs_b' has been deleted" "Damaged Key Reads As Deleted"
+    
+    # A version 4 store: the same header but no CRC after the value
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" < /dev/null > /dev/null 2>&1)
+    sed -i 's/^format_version = .*/format_version = 4/' "$exec_dir/storage/.meta"
+    printf '\x03\x00\x00\x00\x02\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00oldv4' > "$exec_dir/storage/data_0000000000.dat"
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get old" --exec "insert new v5" --exec "compact" --exec "get old" --exec "get new" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "✓ old: v4$" "Version 4 Record Readable"
+    check_exec_output_contains "$output" "data_0000000001.dat, value_offset: 20)" "Upgraded Store Writes A Fresh File"
+    if [ "$(echo "$output" | grep -c "✓ old: v4$")" = "2" ] && echo "$output" | grep -q "✓ new: v5$"; then
+        log_test_result "Version 4 And 5 Records Merge Together" "PASS"
+    else
+        log_test_result "Version 4 And 5 Records Merge Together" "FAIL"
+    fi
+}
+
+test_merge_preview() {
+    echo -e "${BLUE}=== Testing Merge Preview ===${NC}"
+    
+    # mp_key_1..12, an overwrite of mp_key_3 and a delete of mp_key_4 fill the 501 bytes of data_0000000000.dat;
+    # the old mp_key_3 and mp_key_4 records (36 bytes each) and the 29-byte tombstone are reclaimable
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    local args=()
+    for i in {1..12}; do
+        args+=(--exec "insert mp_key_$i value_$i")
+    done
+    args+=(--exec "insert mp_key_3 new_3" --exec "delete mp_key_4")
+    for i in {13..20}; do
+        args+=(--exec "insert mp_key_$i value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" --exec "merge --dry-run" --exec "stats" --exec "merge --now" < /dev/null 2>&1)
+    local files=$(ls "$exec_dir/storage" | grep "\.dat$" | tr '\n' ' ')
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "✓ Merge preview for 1 inactive files (dry run, nothing modified)" "Preview Covers Inactive Files"
+    check_exec_output_contains "$output" "Records: 14 (1 tombstones), unique keys: 12, live keys: 11$" "Preview Counts Records And Keys"
+    check_exec_output_contains "$output" "Bytes retained: 400$" "Preview Reports Retained Bytes"
+    check_exec_output_contains "$output" "Bytes reclaimed: 101$" "Preview Reports Reclaimed Bytes"
+    check_exec_output_contains "$output" "Usage: merge \[--dry-run\]" "Unknown Merge Option Rejected"
+    if [ "$files" = "data_0000000000.dat data_0000000001.dat " ]; then
+        log_test_result "Preview Modifies Nothing" "PASS"
+    else
+        log_test_result "Preview Modifies Nothing" "FAIL" "files: $files"
+    fi
+    
+    # mp_key_2's value (from offset 61) fails its CRC: like the merge, the preview skips it and keeps counting after it
+    exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" "${args[@]}" < /dev/null > /dev/null 2>&1)
+    printf 'x' | dd of="$exec_dir/storage/data_0000000000.dat" bs=1 seek=62 conv=notrunc 2>/dev/null
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "merge --dry-run" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "Records: 13 (1 tombstones), unique keys: 11, live keys: 10$" "Preview Counts Records After Damage"
+    check_exec_output_contains "$output" "Damaged records skipped: 1$" "Preview Reports Damaged Records"
+    check_exec_output_contains "$output" "Bytes retained: 364$" "Preview Retains Records After Damage"
+    check_exec_output_contains "$output" "Bytes reclaimed: 137$" "Preview Reclaims Damaged Record"
+}
+
+test_dead_space_merge() {
+    echo -e "${BLUE}=== Testing Dead-Space Merge ===${NC}"
+    
+    # Writing ds_key_10..25 twice fills data_0000000000.dat and data_0000000001.dat with 13 records of 38 bytes each;
+    # 608 of their 988 bytes are superseded, past the 0.5 ratio, so the event loop merges without waiting for inactivity
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed "s/^merge_interval_seconds = .*/merge_interval_seconds = 0/; s/^merge_dead_ratio = .*/merge_dead_ratio = 0.5/; s/^poll_timeout_ms = .*/poll_timeout_ms = 100/" config.toml > "$exec_dir/config.toml"
+    local commands=""
+    for round in 1 2; do
+        for i in {10..25}; do
+            commands+="insert ds_key_$i value_$i\n"
+        done
+    done
+    local output=$(cd "$exec_dir" && (printf "$commands"; sleep 0.5; printf 'stats\nget ds_key_10\nexit\n'; sleep 0.5) | timeout 10 "$app_bin" 2>&1)
+    local stats_output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert ds_key_10 again" --exec "stats" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Dead-space merge: past 50% of inactive bytes" "Dead-Space Threshold Reported At Startup"
+    check_exec_output_contains "$output" "Auto-merge triggered: 62% of the inactive files' bytes are dead space" "Merge Triggered By Dead Space"
+    check_exec_output_contains "$output" "Dead space in inactive files: 0 of 380 bytes (0%)" "Merge Leaves No Dead Space"
+    check_exec_output_contains "$output" "✓ ds_key_10: value_10" "Values Survive Dead-Space Merge"
+    check_exec_output_contains "$stats_output" "Dead space in inactive files: 38 of 380 bytes (10%)" "Overwrite Counted As Dead Space"
+    if echo "$stats_output" | grep -q "Auto-merge triggered"; then
+        log_test_result "Scripted Runs Never Merge On Dead Space" "FAIL"
+    else
+        log_test_result "Scripted Runs Never Merge On Dead Space" "PASS"
+    fi
+}
+
+test_namespaces() {
+    echo -e "${BLUE}=== Testing Namespaced Stores ===${NC}"
+    
+    # Stores in namespaces users and sessions, and one without a namespace, share a directory; the users store
+    # rotates and merges while the others hold the same key
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    sed 's/^# namespace = .*/namespace = "users"/' config.toml > "$exec_dir/users.toml"
+    sed 's/^# namespace = .*/namespace = "sessions"/' config.toml > "$exec_dir/sessions.toml"
+    cp config.toml "$exec_dir/plain.toml"
+    local args=()
+    for i in {10..40}; do
+        args+=(--exec "insert ns_user_$i value_$i")
+    done
+    (cd "$exec_dir" && cp users.toml config.toml && timeout 10 "$app_bin" "${args[@]}" --exec "insert shared users" < /dev/null > /dev/null 2>&1)
+    (cd "$exec_dir" && cp sessions.toml config.toml && timeout 10 "$app_bin" --exec "insert ns_session_1 a" --exec "insert shared sessions" < /dev/null > /dev/null 2>&1)
+    (cd "$exec_dir" && cp plain.toml config.toml && timeout 10 "$app_bin" --exec "insert shared plain" < /dev/null > /dev/null 2>&1)
+    local users=$(cd "$exec_dir" && cp users.toml config.toml && timeout 10 "$app_bin" --exec "merge" --exec "get shared" --exec "get ns_session_1" --exec "count" --exec "stats" < /dev/null 2>&1)
+    local sessions=$(cd "$exec_dir" && cp sessions.toml config.toml && timeout 10 "$app_bin" --exec "get shared" --exec "get ns_user_10" --exec "count" --exec "stats" < /dev/null 2>&1)
+    local plain=$(cd "$exec_dir" && cp plain.toml config.toml && timeout 10 "$app_bin" --exec "get shared" --exec "count" < /dev/null 2>&1)
+    local files=$(ls -a "$exec_dir/storage" | grep -E "meta$|dat$" | tr '\n' ' ')
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$users" "Namespace: users (other namespaces in 'storage/' are left alone)" "Namespace Reported At Startup"
+    check_exec_output_contains "$users" "Data files: users_data_N.dat, merge files: users_merge_N.dat" "Namespace Prefixes File Names"
+    check_exec_output_contains "$users" "✓ shared: users$" "Namespace Reads Its Own Value"
+    check_exec_output_contains "$users" "✗ Key 'ns_session_1' not found" "Namespace Ignores Other Namespace's Keys"
+    check_exec_output_contains "$users" "Total: 3 files" "Stats Count Only The Namespace's Files"
+    check_exec_output_contains "$sessions" "✓ shared: sessions$" "Other Namespace Untouched By Merge"
+    check_exec_output_contains "$sessions" "✗ Key 'ns_user_10' not found" "Other Namespace Ignores Merged Keys"
+    check_exec_output_contains "$sessions" "Total: 1 files" "Other Namespace Counts One File"
+    check_exec_output_contains "$plain" "✓ shared: plain$" "Store Without Namespace Keeps Its Value"
+    check_exec_output_contains "$plain" "1 keys" "Store Without Namespace Sees Only Its Keys"
+    if [ "$files" = ".meta .sessions.meta .users.meta data_0000000000.dat sessions_data_0000000000.dat users_data_0000000002.dat users_merge_0000000000.dat users_merge_0000000001.dat " ]; then
+        log_test_result "Each Namespace Keeps Its Own Files" "PASS"
+    else
+        log_test_result "Each Namespace Keeps Its Own Files" "FAIL" "files: $files"
+    fi
+}
+
+test_store_meta() {
+    echo -e "${BLUE}=== Testing Store Meta Validation ===${NC}"
+    
+    # A store whose .meta claims a newer format version than this build reads must not open
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert meta_key v" < /dev/null > /dev/null 2>&1)
+    local version=$(sed -n 's/^format_version = //p' "$exec_dir/storage/.meta")
+    local bumped=$((version + 1))
+    sed -i "s/^format_version = .*/format_version = $bumped/" "$exec_dir/storage/.meta"
+    local data_before=$(od -c "$exec_dir/storage/data_0000000000.dat")
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get meta_key" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "Incompatible storage format: store uses format version $bumped, this build reads version $version" "Newer Format Version Rejected"
+    if echo "$output" | grep -q "✓ meta_key"; then
+        log_test_result "Newer Store Not Read" "FAIL" "get succeeded on a store from a newer format"
+    else
+        log_test_result "Newer Store Not Read" "PASS"
+    fi
+    if grep -q "^format_version = $bumped$" "$exec_dir/storage/.meta" && [ "$(od -c "$exec_dir/storage/data_0000000000.dat")" = "$data_before" ]; then
+        log_test_result "Newer Store Left Untouched" "PASS"
+    else
+        log_test_result "Newer Store Left Untouched" "FAIL" ".meta or the data file changed"
+    fi
+    rm -rf "$exec_dir"
+}
+
+test_find_duplicates() {
+    echo -e "${BLUE}=== Testing Duplicate Key Detection ===${NC}"
+    
+    local inserts=()
+    for i in {1..20}; do
+        inserts+=(--exec "insert dup_key_$i v$i")
+    done
+    local output=$(run_exec "${inserts[@]}" --exec "delete dup_key_3" --exec "duplicates" --exec "simulate-duplicate dup_key_7" --exec "duplicates")
+    check_exec_output_contains "$output" "No duplicate keys in the index" "Normal Table Has No Duplicates"
+    check_exec_output_contains "$output" "1 duplicate keys in the index: dup_key_7$" "Injected Duplicate Detected"
+}
+
+test_auto_merge_disabled() {
+    echo -e "${BLUE}=== Testing Auto-merge Interval Setting ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local interval
+    for interval in 0 1; do
+        local exec_dir=$(mktemp -d)
+        sed "s/^merge_interval_seconds = .*/merge_interval_seconds = $interval/" config.toml > "$exec_dir/config.toml"
+        local output=$(cd "$exec_dir" && (echo "insert idle_key v"; sleep 2.5; echo "exit") | timeout 10 "$app_bin" 2>&1)
+        rm -rf "$exec_dir"
+        
+        if [ "$interval" = "0" ]; then
+            check_exec_output_contains "$output" "Auto-merge is disabled" "Auto-merge Reported Disabled"
+            if echo "$output" | grep -q "Auto-merge triggered"; then
+                log_test_result "No Auto-merge When Interval Is 0" "FAIL" "Auto-merge ran while disabled"
+            else
+                log_test_result "No Auto-merge When Interval Is 0" "PASS"
+            fi
+        else
+            check_exec_output_contains "$output" "Auto-merge triggered due to inactivity" "Auto-merge Runs When Enabled"
+        fi
+    done
+}
+
+test_storage_stats() {
+    echo -e "${BLUE}=== Testing Storage Stats ===${NC}"
+    
+    # 20 records of 34 bytes each: 15 fit in the first 512-byte file, the rest rotate into a second
+    local inserts=()
+    for i in {10..29}; do
+        inserts+=(--exec "insert st_$i value_$i")
+    done
+    local output=$(run_exec "${inserts[@]}" --exec "stats")
+    check_exec_output_contains "$output" "data_0000000000.dat: 510 bytes$" "Full File Size Reported"
+    check_exec_output_contains "$output" "data_0000000001.dat: 170 bytes (ACTIVE)$" "Active File Reported"
+    check_exec_output_contains "$output" "Total: 2 files, 680 bytes$" "File Count And Total Bytes"
+}
+
+test_all_records() {
+    echo -e "${BLUE}=== Testing Record Iteration ===${NC}"
+    
+    # Each value is read back using the offset, size and checksum the iterator reported
+    local inserts=()
+    for i in {10..29}; do
+        inserts+=(--exec "insert ar_$i value_$i")
+    done
+    local output=$(run_exec "${inserts[@]}" --exec "insert ar_10 updated" --exec "delete ar_11" --exec "records")
+    check_exec_output_contains "$output" "✓ 22 records on disk:" "Every Record Yielded"
+    check_exec_output_contains "$output" "data_0000000000.dat@0 put ar_10 = value_10$" "Superseded Record Readable"
+    check_exec_output_contains "$output" "data_0000000001.dat@0 put ar_25 = value_25$" "Records Span Rotated Files"
+    check_exec_output_contains "$output" "put ar_10 = updated$" "Latest Record Readable"
+    check_exec_output_contains "$output" "data_0000000001.dat@[0-9]* delete ar_11$" "Tombstone Yielded"
+}
+
+test_hint_files() {
+    echo -e "${BLUE}=== Testing Merge Hint Files ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    
+    local inserts=()
+    for i in {1..30}; do
+        inserts+=(--exec "insert hint_key_$i hint_value_$i")
+    done
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" "${inserts[@]}" --exec "merge" --exec "insert hint_after merge" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "Wrote hint file: merge_[0-9]*.hint" "Merge Writes Hint Files"
+    
+    # The merge files' hints cover the merged records; the later insert is scanned from the active data file
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get hint_key_1" --exec "get hint_key_30" --exec "get hint_after" < /dev/null 2>&1)
+    check_exec_output_contains "$output" "Recovered 31 keys from data files.*([1-9][0-9]* read from hint files)" "Startup Reads Hint Files"
+    check_exec_output_contains "$output" "✓ hint_key_1: hint_value_1$" "Hinted Key Readable"
+    check_exec_output_contains "$output" "✓ hint_key_30: hint_value_30$" "Last Hinted Key Readable"
+    check_exec_output_contains "$output" "✓ hint_after: merge$" "Record Appended After Hint Readable"
+    
+    rm -f "$exec_dir"/storage/*.hint
+    output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get hint_key_1" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    check_exec_output_contains "$output" "Recovered 31 keys from data files.*(0 read from hint files)" "Missing Hints Fall Back To Scanning"
+    check_exec_output_contains "$output" "✓ hint_key_1: hint_value_1$" "Key Readable Without Hints"
+}
+
+test_restart_recovery() {
+    echo -e "${BLUE}=== Testing Index Recovery On Restart ===${NC}"
+    
+    local app_bin="$(pwd)/$APP_PATH"
+    local exec_dir=$(mktemp -d)
+    cp config.toml "$exec_dir/"
+    
+    # Enough data to rotate across several files, then an update and a delete in a later run
+    local inserts=()
+    for i in {1..30}; do
+        inserts+=(--exec "insert restart_key_$i restart_value_$i")
+    done
+    (cd "$exec_dir" && timeout 10 "$app_bin" "${inserts[@]}" < /dev/null > /dev/null 2>&1)
+    (cd "$exec_dir" && timeout 10 "$app_bin" --exec "insert restart_key_1 updated" --exec "delete restart_key_2" < /dev/null > /dev/null 2>&1)
+    local output=$(cd "$exec_dir" && timeout 10 "$app_bin" --exec "get restart_key_1" --exec "get restart_key_2" --exec "get restart_key_30" < /dev/null 2>&1)
+    rm -rf "$exec_dir"
+    
+    check_exec_output_contains "$output" "Recovered 29 keys from data files" "Keys Recovered On Startup"
+    check_exec_output_contains "$output" "✓ restart_key_1: updated$" "Latest Value Wins After Restart"
+    check_exec_output_contains "$output" "Key 'restart_key_2' not found" "Deleted Key Stays Deleted After Restart"
+    check_exec_output_contains "$output" "✓ restart_key_30: restart_value_30$" "Key From Newest File Recovered"
+}
+
 run_comprehensive_tests() {
     echo -e "${YELLOW}=== Starting Comprehensive Test Suite ===${NC}"
     
@@ -294,6 +2456,85 @@ run_comprehensive_tests() {
     test_merge_operations
     test_concurrent_operations
     test_error_handling
+    test_rehash
+    test_simulate_crash
+    test_simulate_crash_dropped_entries
+    test_exec_mode
+    test_hexdump
+    test_delete_reason
+    test_binary_values
+    test_tombstone_marker_value
+    test_truncate_to
+    test_bench_read
+    test_bench_merge
+    test_cache_policy
+    test_cache_pinning
+    test_recent_deletes
+    test_write_location
+    test_scan_pagination
+    test_scan_prefix
+    test_hash_table_config
+    test_hash_table_growth
+    test_index_len
+    test_hash_function
+    test_grow_recommended
+    test_open_addressing_tombstones
+    test_list_keys
+    test_sync_only_when_dirty
+    test_relocate
+    test_durability_mode
+    test_legacy_format
+    test_store_meta
+    test_find_duplicates
+    test_merge_output_files
+    test_partial_tail_recovery
+    test_insert_batch
+    test_value_compression
+    test_exists_command
+    test_hash_table_metrics
+    test_double_hashing_step
+    test_compact_command
+    test_transaction_command
+    test_merge_timestamp_order
+    test_count_command
+    test_quoted_arguments
+    test_conditional_insert
+    test_file_naming
+    test_missing_data_file
+    test_backup_command
+    test_robin_hood
+    test_getmeta_command
+    test_delete_prefix
+    test_read_handles
+    test_tcp_server
+    test_tombstones_command
+    test_poll_timeout
+    test_mget_command
+    test_saved_index
+    test_update_in_place
+    test_returning_old_values
+    test_record_checksums
+    test_merge_preview
+    test_dead_space_merge
+    test_namespaces
+    test_size_limits
+    test_background_merge
+    test_verify_command
+    test_reopen_file_counter
+    test_merge_into_dir
+    test_read_only_mode
+    test_self_test
+    test_index_key_mismatch
+    test_auto_merge_disabled
+    test_export
+    test_age
+    test_logical_layout
+    test_prune_dead_files
+    test_locked_merge_files
+    test_storage_stats
+    test_all_records
+    test_hint_files
+    test_restart_recovery
     test_auto_merge
     
     echo -e "${YELLOW}=== Test Suite Complete ===${NC}"